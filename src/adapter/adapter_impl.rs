@@ -1,15 +1,34 @@
+use super::parameters::{self, byte_pattern, file_pattern, parameter, size_grouping};
 use super::vertex::Vertex;
-use super::SourceLocation;
+use super::AdapterBuilder;
+use super::{
+    function_containing, symbol_containing, ByteRange, CpuFeatureUsage, Function, Section,
+    SourceFile, SourceLocation, Symbol,
+};
+use crate::bytes::{BytePattern, Bytes};
+use crate::cache::{self, CacheData, CacheKey};
+use crate::coverage::{CoverageBuilder, CoverageMap, CoverageOptions};
+use crate::cpu_features;
+use crate::crates::{self, Crate, ItemPath, UnitCrates};
+use crate::demangle;
+use crate::disassembly::{self, DataInCode, DisassemblyMode};
+use crate::error::{Diagnostic, Error, Result};
+use crate::generics::{self, GenericItem};
+use crate::jump_tables::JumpTable;
 use crate::loader::*;
-use gimli::*;
-use iced_x86::{Decoder, DecoderOptions, Instruction};
-use object::{read::ObjectSection, Object};
+use crate::size::{self, SizeGrouping, SizeRow};
+use crate::source_paths::{self, FilePattern};
+use crate::symbolize::{Frame, FrameIndex};
+use crate::xrefs::{self, DataReference};
+use iced_x86::Instruction;
+use memmap2::Mmap;
+use object::read::ObjectSection;
+use object::{Architecture, Object};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use trustfall::{
     provider::{
         resolve_coercion_using_schema, resolve_property_with, AsVertex, ContextIterator,
@@ -21,11 +40,102 @@ use trustfall::{
 
 static SCHEMA: OnceLock<Schema> = OnceLock::new();
 
+fn map_file(path: &Path) -> Result<Bytes> {
+    let file = fs::File::open(path)?;
+    // SAFETY: the map is read only, modifying the file while it's loaded is unsupported.
+    let data = unsafe { Mmap::map(&file)? };
+    Ok(Bytes::new(data))
+}
+
+/// Rows of query results, streamed as the query is executed
+pub type QueryResults = Box<dyn Iterator<Item = BTreeMap<Arc<str>, FieldValue>>>;
+
+/// Limit on how many times recursive disassembly is rerun to follow newly found jump tables
+const MAX_RECURSIVE_PASSES: usize = 8;
+
+/// A loaded binary. Cloning is cheap as the loaded data is shared between clones.
 #[non_exhaustive]
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(into = "AdapterData", from = "AdapterData")]
 pub struct Adapter {
-    pub debug_info: BTreeMap<u64, Vec<Rc<SourceLocation>>>, // Address to code region
-    pub text_section: Vec<Rc<Instruction>>,
+    binary: Arc<Binary>,
+}
+
+/// The line table rows for each path and compile unit directory, in address order
+type FileRows = BTreeMap<(PathBuf, Option<Arc<Path>>), Vec<(u64, Arc<SourceLocation>)>>;
+
+/// The contents of the object file. Section data points into the (usually memory mapped) file, and
+/// everything which needs decoding is only done the first time it's used.
+#[derive(Debug, Default)]
+struct Binary {
+    data: Bytes,
+    /// Contents of the separate debug file if the DWARF was found in one
+    debug_data: Option<Bytes>,
+    options: LoadOptions,
+    bitness: u32,
+    sections: Arc<Vec<Arc<Section>>>,
+    symbols: OnceLock<Vec<Arc<Symbol>>>,
+    debug_info: OnceLock<BTreeMap<u64, Vec<Arc<SourceLocation>>>>, // Address to code region
+    text_section: OnceLock<Vec<Arc<Instruction>>>,
+    /// Start addresses of the instructions in the code sections when loaded from the cache
+    instruction_index: OnceLock<Vec<u64>>,
+    functions: OnceLock<Vec<Arc<Function>>>,
+    data_in_code: OnceLock<Vec<Arc<DataInCode>>>,
+    /// Referenced address to the instructions referencing it
+    xrefs: OnceLock<BTreeMap<u64, Vec<Arc<DataReference>>>>,
+    /// Address ranges of the Rust compile units and their crates
+    unit_crates: OnceLock<UnitCrates>,
+    crates: OnceLock<Vec<Arc<Crate>>>,
+    generic_items: OnceLock<Vec<Arc<GenericItem>>>,
+    source_files: OnceLock<Vec<Arc<SourceFile>>>,
+    file_rows: OnceLock<FileRows>,
+    /// `None` if the DWARF couldn't be indexed, which is reported as a diagnostic
+    frame_index: OnceLock<Option<FrameIndex>>,
+    diagnostics: Mutex<Vec<Arc<Diagnostic>>>,
+}
+
+/// Fully decoded form of the adapter used when serializing.
+#[derive(Deserialize, Serialize)]
+struct AdapterData {
+    debug_info: BTreeMap<u64, Vec<Arc<SourceLocation>>>,
+    text_section: Vec<Arc<Instruction>>,
+    functions: Vec<Arc<Function>>,
+    sections: Vec<Arc<Section>>,
+    data_in_code: Vec<Arc<DataInCode>>,
+    symbols: Vec<Arc<Symbol>>,
+    xrefs: BTreeMap<u64, Vec<Arc<DataReference>>>,
+}
+
+impl From<Adapter> for AdapterData {
+    fn from(adapter: Adapter) -> Self {
+        Self {
+            debug_info: adapter.debug_info().clone(),
+            text_section: adapter.text_section().to_vec(),
+            functions: adapter.functions().to_vec(),
+            sections: adapter.sections().to_vec(),
+            data_in_code: adapter.data_in_code().to_vec(),
+            symbols: adapter.symbols().to_vec(),
+            xrefs: adapter.xrefs().clone(),
+        }
+    }
+}
+
+impl From<AdapterData> for Adapter {
+    fn from(data: AdapterData) -> Self {
+        let binary = Binary {
+            sections: Arc::new(data.sections),
+            symbols: data.symbols.into(),
+            debug_info: data.debug_info.into(),
+            text_section: data.text_section.into(),
+            functions: data.functions.into(),
+            data_in_code: data.data_in_code.into(),
+            xrefs: data.xrefs.into(),
+            ..Default::default()
+        };
+        Self {
+            binary: Arc::new(binary),
+        }
+    }
 }
 
 impl Adapter {
@@ -39,156 +149,913 @@ impl Adapter {
         Self::default()
     }
 
-    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let data = fs::read(path)?;
-        let file = object::File::parse(&*data)?;
+    pub fn builder() -> AdapterBuilder {
+        AdapterBuilder::new()
+    }
 
-        let debug_info = match get_line_addresses(&file) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("No debug info: {}", e);
-                Default::default()
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with_options(path, &LoadOptions::default())
+    }
+
+    /// Loads an object file which is already in memory, the data is copied into the adapter.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_data(Bytes::from(data.to_vec()), None, &LoadOptions::default())
+    }
+
+    /// Memory maps the file and loads it. Only the section headers are read up front, symbols,
+    /// instructions and debug information are decoded when a query first needs them.
+    pub fn load_with_options(path: impl AsRef<Path>, options: &LoadOptions) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_data(map_file(path)?, path.parent(), options)
+    }
+
+    /// Loads the file using the index cache in `cache_dir`. If there's no cache for this file or
+    /// it was built from a different file or with different options, the indices are built and
+    /// the cache is written for next time. Failing to write the cache is reported as a diagnostic.
+    /// Binaries with diagnostics from loading aren't cached, so the problems are reported again
+    /// by every load rather than looking like a binary without them.
+    pub fn load_cached(path: impl AsRef<Path>, cache_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::load_cached_with_options(path, cache_dir, &LoadOptions::default())
+    }
+
+    pub fn load_cached_with_options(
+        path: impl AsRef<Path>,
+        cache_dir: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let cache_dir = cache_dir.as_ref();
+        let data = map_file(path)?;
+        let key = CacheKey::new(&data, options);
+        if let Some(cached) = cache::read(cache_dir, &key) {
+            let adapter = Self::open(data, path.parent(), options)?;
+            let _ = adapter.binary.symbols.set(cached.symbols);
+            let _ = adapter.binary.debug_info.set(cached.debug_info);
+            if let Some(instructions) = cached.instructions {
+                let _ = adapter.binary.instruction_index.set(instructions);
             }
+            return Ok(adapter);
+        }
+
+        let adapter = Self::from_data(data, path.parent(), options)?;
+        let instructions = match options.disassembly {
+            DisassemblyMode::Linear => None,
+            DisassemblyMode::Recursive => {
+                Some(adapter.text_section().iter().map(|x| x.ip()).collect())
+            }
+        };
+        let cached = CacheData {
+            symbols: adapter.symbols().to_vec(),
+            debug_info: adapter.debug_info().clone(),
+            instructions,
         };
+        if !adapter.load_diagnostics().is_empty() {
+            return Ok(adapter);
+        }
+        if let Err(e) = cache::write(cache_dir, &key, &cached) {
+            adapter.add_diagnostic(Diagnostic::warning(e));
+        }
+        Ok(adapter)
+    }
+
+    /// Loads the object file from its data. `binary_dir` is the directory the file was loaded
+    /// from if there is one, used to find separate debug info.
+    pub(crate) fn from_data(
+        data: Bytes,
+        binary_dir: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<Self> {
+        let adapter = Self::open(data, binary_dir, options)?;
+        if options.strict {
+            // Read the debug info now so any problems are reported
+            let mut errors = vec![];
+            let lines = adapter.read_debug_info(&mut errors)?;
+            if let Some(e) = errors.into_iter().next() {
+                return Err(e);
+            }
+            let _ = adapter.binary.debug_info.set(lines);
+        }
+        Ok(adapter)
+    }
 
-        let mut text_section = vec![];
+    /// Reads the section headers and finds any separate debug info.
+    fn open(data: Bytes, binary_dir: Option<&Path>, options: &LoadOptions) -> Result<Self> {
+        let file = object::File::parse(&*data)?;
+        // Instructions are only decoded as x86, a bitness override decodes any file as x86 code
+        let bitness = match (options.bitness, file.architecture()) {
+            (Some(bitness), _) => bitness,
+            (None, Architecture::X86_64) => 64,
+            (None, Architecture::I386 | Architecture::X86_64_X32) => 32,
+            (None, architecture) => {
+                return Err(Error::UnsupportedArchitecture(
+                    format!("{:?}", architecture).to_lowercase(),
+                ))
+            }
+        };
+        if !matches!(bitness, 16 | 32 | 64) {
+            return Err(Error::UnsupportedBitness(bitness));
+        }
+
+        let mut sections = vec![];
         for section in file.sections() {
             let name = match section.name() {
                 Ok(s) => s,
                 Err(_e) => continue,
             };
-            if name == ".text" {
-                let bytes = section.data()?;
-                let mut decoder = Decoder::new(64, bytes, DecoderOptions::NONE);
-                text_section = decoder.iter().map(Rc::new).collect();
-            }
+            let contents = match section.file_range() {
+                Some((offset, size)) => offset
+                    .checked_add(size)
+                    .and_then(|end| data.slice(offset as usize..end as usize))
+                    .ok_or_else(|| Error::InvalidSection(name.to_string()))?,
+                None => Bytes::default(),
+            };
+            sections.push(Arc::new(Section {
+                name: name.to_string(),
+                address: section.address(),
+                size: section.size(),
+                data: contents,
+            }));
         }
+
+        let debug_data = find_debug_file(&file, binary_dir, &options.debug_search_paths)
+            .map(|path| map_file(&path))
+            .transpose()?;
+
+        let binary = Binary {
+            options: options.clone(),
+            bitness,
+            sections: Arc::new(sections),
+            data: data.clone(),
+            debug_data,
+            ..Default::default()
+        };
         Ok(Self {
-            debug_info,
-            text_section,
+            binary: Arc::new(binary),
         })
     }
 
-    pub fn find_instruction(&self, address: u64) -> Option<Rc<Instruction>> {
-        self.text_section
+    pub(crate) fn object(&self) -> Option<object::File<'_>> {
+        object::File::parse(&*self.binary.data).ok()
+    }
+
+    /// The object file containing the DWARF, this is the binary itself unless a separate debug
+    /// file was found.
+    fn debug_object(&self) -> Option<object::File<'_>> {
+        object::File::parse(&**self.debug_data()).ok()
+    }
+
+    /// Contents of the file containing the DWARF
+    pub(crate) fn debug_data(&self) -> &Bytes {
+        self.binary.debug_data.as_ref().unwrap_or(&self.binary.data)
+    }
+
+    pub(crate) fn options(&self) -> &LoadOptions {
+        &self.binary.options
+    }
+
+    /// The sections to disassemble sorted by address
+    pub(super) fn code_sections(&self) -> Vec<Arc<Section>> {
+        let mut sections = self
+            .sections()
             .iter()
-            .find(|x| x.ip() >= address && address < (x.ip() - x.len() as u64))
+            .filter(|x| self.binary.options.code_sections.contains(&x.name))
             .cloned()
+            .collect::<Vec<_>>();
+        sections.sort_by_key(|x| x.address);
+        sections
+    }
+
+    fn read_debug_info(
+        &self,
+        errors: &mut Vec<Error>,
+    ) -> Result<BTreeMap<u64, Vec<Arc<SourceLocation>>>> {
+        let file = object::File::parse(&**self.debug_data())?;
+        get_line_addresses(&file, &self.binary.options, errors)
+    }
+
+    fn add_diagnostic(&self, diagnostic: Diagnostic) {
+        self.binary
+            .diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(diagnostic));
+    }
+
+    /// Problems found while loading the binary. Everything which can produce a diagnostic is
+    /// loaded first, so this will decode the code sections and read the debug info.
+    pub fn diagnostics(&self) -> Vec<Arc<Diagnostic>> {
+        self.debug_info();
+        self.text_section();
+        self.load_diagnostics()
+    }
+
+    /// The diagnostics found so far without loading anything else
+    fn load_diagnostics(&self) -> Vec<Arc<Diagnostic>> {
+        self.binary
+            .diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 
-    pub fn get_file_locations(&self, path: PathBuf) -> BTreeSet<Rc<SourceLocation>> {
-        self.debug_info
-            .values()
-            .flat_map(|x| x.iter().filter(|y| y.file == path).cloned())
+    /// Runs a query against the binary, returning an error if the query, its variables or the
+    /// parameters of its edges are invalid rather than panicking.
+    pub fn query(
+        self: &Arc<Self>,
+        query: &str,
+        variables: BTreeMap<Arc<str>, FieldValue>,
+    ) -> Result<QueryResults> {
+        parameters::execute(Self::schema(), self.clone(), query, variables)
+    }
+
+    /// Source locations for each address with line information, read from the DWARF line
+    /// programs on first use.
+    pub fn debug_info(&self) -> &BTreeMap<u64, Vec<Arc<SourceLocation>>> {
+        self.binary.debug_info.get_or_init(|| {
+            let mut errors = vec![];
+            let lines = match self.read_debug_info(&mut errors) {
+                Ok(s) => s,
+                Err(e) => {
+                    self.add_diagnostic(Diagnostic::warning(e));
+                    Default::default()
+                }
+            };
+            for e in errors {
+                self.add_diagnostic(Diagnostic::error(e));
+            }
+            lines
+        })
+    }
+
+    /// Every instruction found in the code sections, sorted by address
+    pub fn text_section(&self) -> &[Arc<Instruction>] {
+        self.binary.text_section.get_or_init(|| {
+            let code_sections = self.code_sections();
+            if let Some(index) = self.binary.instruction_index.get() {
+                return code_sections
+                    .iter()
+                    .flat_map(|x| {
+                        let start = index.partition_point(|i| *i < x.address);
+                        let end = index.partition_point(|i| *i < x.address.saturating_add(x.size));
+                        disassembly::decode_at(
+                            self.binary.bitness,
+                            &x.data,
+                            x.address,
+                            &index[start..end],
+                        )
+                    })
+                    .collect();
+            }
+            match self.binary.options.disassembly {
+                DisassemblyMode::Linear => code_sections
+                    .iter()
+                    .flat_map(|x| {
+                        disassembly::linear_sweep(self.binary.bitness, &x.data, x.address)
+                    })
+                    .collect(),
+                DisassemblyMode::Recursive => self.recursive_descent(&code_sections),
+            }
+        })
+    }
+
+    fn recursive_descent(&self, code_sections: &[Arc<Section>]) -> Vec<Arc<Instruction>> {
+        let (Some(file), Some(debug_file)) = (self.object(), self.debug_object()) else {
+            return vec![];
+        };
+        // Jump tables are only found once we've decoded the code using them, so keep going until
+        // their targets don't reveal any new code.
+        let mut decoded = BTreeMap::new();
+        let mut seeds = get_code_seeds(&file, &debug_file);
+        let mut tried = HashSet::new();
+        let mut failed = BTreeSet::new();
+        for _ in 0..MAX_RECURSIVE_PASSES {
+            tried.extend(seeds.iter().copied());
+            for section in code_sections {
+                failed.extend(disassembly::extend_recursive_descent(
+                    self.binary.bitness,
+                    &section.data,
+                    section.address,
+                    seeds.iter().copied(),
+                    &mut decoded,
+                ));
+            }
+            let text_section = decoded.values().cloned().collect::<Vec<_>>();
+            seeds = self
+                .functions_from(&file, &text_section)
+                .iter()
+                .flat_map(|x| x.jump_tables().iter())
+                .flat_map(|x| x.targets.iter().copied())
+                .filter(|x| !decoded.contains_key(x) && !tried.contains(x))
+                .collect::<Vec<_>>();
+            if seeds.is_empty() {
+                break;
+            }
+        }
+        for address in failed {
+            self.add_diagnostic(Diagnostic::warning(Error::DecodeError { address }));
+        }
+        decoded.into_values().collect()
+    }
+
+    /// Gets the functions from the symbol table. With recursive disassembly the functions use the
+    /// instructions already found in the code sections.
+    fn functions_from<'data>(
+        &self,
+        file: &'data impl Object<'data>,
+        text_section: &[Arc<Instruction>],
+    ) -> Vec<Arc<Function>> {
+        let functions = get_functions(
+            file,
+            &self.code_sections(),
+            &self.binary.sections,
+            self.binary.bitness,
+        );
+        if self.binary.options.disassembly == DisassemblyMode::Linear {
+            return functions;
+        }
+        functions
+            .into_iter()
+            .map(|func| {
+                let start = text_section.partition_point(|i| i.ip() < func.address);
+                let end = text_section
+                    .partition_point(|i| i.ip() < func.address.saturating_add(func.size));
+                let func =
+                    Function::clone(&func).with_instructions(text_section[start..end].to_vec());
+                Arc::new(func)
+            })
             .collect()
     }
 
-    pub fn get_file_instructions(&self, path: PathBuf) -> Vec<Rc<Instruction>> {
-        let iter = self
-            .debug_info
+    /// Functions from the symbol table sorted by address
+    pub fn functions(&self) -> &[Arc<Function>] {
+        self.binary.functions.get_or_init(|| {
+            let Some(file) = self.object() else {
+                return vec![];
+            };
+            let text_section = match self.binary.options.disassembly {
+                DisassemblyMode::Linear => &[],
+                DisassemblyMode::Recursive => self.text_section(),
+            };
+            self.functions_from(&file, text_section)
+        })
+    }
+
+    pub fn sections(&self) -> &[Arc<Section>] {
+        &self.binary.sections
+    }
+
+    /// Symbols sorted by address
+    pub fn symbols(&self) -> &[Arc<Symbol>] {
+        self.binary.symbols.get_or_init(|| match self.object() {
+            Some(file) => get_symbols(&file),
+            None => vec![],
+        })
+    }
+
+    /// Regions of the code sections which aren't reachable code
+    pub fn data_in_code(&self) -> &[Arc<DataInCode>] {
+        self.binary.data_in_code.get_or_init(|| {
+            let text_section = self.text_section();
+            self.code_sections()
+                .iter()
+                .flat_map(|section| {
+                    let start = text_section.partition_point(|x| x.ip() < section.address);
+                    let end = text_section
+                        .partition_point(|x| x.ip() < section.address.saturating_add(section.size));
+                    disassembly::data_in_code(
+                        &section.data,
+                        section.address,
+                        &text_section[start..end],
+                    )
+                })
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    /// Referenced address to the instructions referencing it
+    pub fn xrefs(&self) -> &BTreeMap<u64, Vec<Arc<DataReference>>> {
+        self.binary
+            .xrefs
+            .get_or_init(|| xrefs::reference_index(self.text_section(), self.sections()))
+    }
+
+    /// Whether the code is 16, 32 or 64 bit
+    pub fn bitness(&self) -> u32 {
+        self.binary.bitness
+    }
+
+    pub fn get_section(&self, name: &str) -> Option<Arc<Section>> {
+        self.sections().iter().find(|x| x.name == name).cloned()
+    }
+
+    /// Finds the section containing the address, sections not loaded into memory are ignored
+    pub fn find_section(&self, address: u64) -> Option<Arc<Section>> {
+        self.sections()
             .iter()
-            .filter(|(_k, x)| x.iter().any(|y| y.file == path))
-            .map(|(k, _)| k);
+            .find(|x| x.address > 0 && x.contains(address))
+            .cloned()
+    }
 
-        iter.filter_map(|x| self.find_instruction(*x)).collect()
+    /// Searches the contents of every section for the pattern
+    pub fn find_bytes(&self, pattern: &BytePattern) -> Vec<ByteRange> {
+        self.sections()
+            .iter()
+            .flat_map(|section| {
+                pattern
+                    .find_all(&section.data)
+                    .map(|offset| ByteRange {
+                        section: section.clone(),
+                        offset,
+                        length: pattern.len(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
     }
-}
 
-impl<'a> trustfall::provider::Adapter<'a> for Adapter {
-    type Vertex = Vertex;
+    /// Finds the instruction containing the address. If the text section hasn't been decoded yet
+    /// only the function containing the address is decoded.
+    pub fn find_instruction(&self, address: u64) -> Option<Arc<Instruction>> {
+        let find = |instructions: &[Arc<Instruction>]| {
+            let index = instructions.partition_point(|x| x.next_ip() <= address);
+            instructions
+                .get(index)
+                .filter(|x| x.ip() <= address)
+                .cloned()
+        };
+        if self.binary.text_section.get().is_none() {
+            if let Some(func) = self.find_function_containing(address) {
+                return find(func.instructions());
+            }
+        }
+        find(self.text_section())
+    }
 
-    fn resolve_starting_vertices(
+    /// Finds a function by its symbol name, or if there's no symbol with the name by its
+    /// demangled path with or without hashes e.g. `core::fmt::write`.
+    pub fn find_function(&self, name: &str) -> Option<Arc<Function>> {
+        let functions = self.functions();
+        functions
+            .iter()
+            .find(|x| x.name == name)
+            .or_else(|| functions.iter().find(|x| demangle::matches(&x.name, name)))
+            .cloned()
+    }
+
+    pub fn find_function_containing(&self, address: u64) -> Option<Arc<Function>> {
+        function_containing(self.functions(), address)
+    }
+
+    /// Finds a symbol by its name, or if there's no symbol with the name by its demangled path
+    /// with or without hashes.
+    pub fn get_symbol(&self, name: &str) -> Option<Arc<Symbol>> {
+        let symbols = self.symbols();
+        symbols
+            .iter()
+            .find(|x| x.name == name)
+            .or_else(|| symbols.iter().find(|x| demangle::matches(&x.name, name)))
+            .cloned()
+    }
+
+    pub fn find_symbol_containing(&self, address: u64) -> Option<Arc<Symbol>> {
+        symbol_containing(self.symbols(), address)
+    }
+
+    /// The functions and source locations for an address, starting with the innermost inlined
+    /// function and ending with the function the code was inlined into. Problems reading the
+    /// DWARF are reported as diagnostics and leave the address without frames.
+    pub fn frames(&self, address: u64) -> Vec<Frame> {
+        let index = self.binary.frame_index.get_or_init(|| {
+            FrameIndex::new(self.debug_data())
+                .map_err(|e| self.add_diagnostic(Diagnostic::warning(e)))
+                .ok()
+        });
+        let Some(index) = index else {
+            return vec![];
+        };
+        index.frames(address, self.options()).unwrap_or_else(|e| {
+            self.add_diagnostic(Diagnostic::warning(e));
+            vec![]
+        })
+    }
+
+    /// Every reference from an instruction to the address
+    pub fn references_to(&self, address: u64) -> Vec<Arc<DataReference>> {
+        self.xrefs().get(&address).cloned().unwrap_or_default()
+    }
+
+    /// Every jump table found in the functions
+    pub fn jump_tables(&self) -> Vec<Arc<JumpTable>> {
+        self.functions()
+            .iter()
+            .flat_map(|x| x.jump_tables().iter().cloned())
+            .collect()
+    }
+
+    /// Usage of each CPU feature across the text section
+    pub fn cpu_feature_usage(&self) -> Vec<CpuFeatureUsage> {
+        cpu_features::feature_usage(self.text_section(), self.functions())
+    }
+
+    /// The object file format e.g. `elf`, `macho` or `pe`
+    pub fn format(&self) -> Option<String> {
+        let file = self.object()?;
+        Some(format!("{:?}", file.format()).to_lowercase())
+    }
+
+    /// The architecture the binary is for e.g. `x86_64` or `aarch64`
+    pub fn architecture(&self) -> Option<String> {
+        let file = self.object()?;
+        Some(format!("{:?}", file.architecture()).to_lowercase())
+    }
+
+    /// The unique ID the linker gave the binary: the GNU build ID for ELF files, the UUID for
+    /// Mach-O and the PDB GUID and age for PE.
+    pub fn build_id(&self) -> Option<Vec<u8>> {
+        let file = self.object()?;
+        if let Some(id) = file.build_id().ok().flatten() {
+            return Some(id.to_vec());
+        }
+        if let Some(uuid) = file.mach_uuid().ok().flatten() {
+            return Some(uuid.to_vec());
+        }
+        let pdb = file.pdb_info().ok().flatten()?;
+        let mut id = pdb.guid().to_vec();
+        id.extend(pdb.age().to_be_bytes());
+        Some(id)
+    }
+
+    /// The minimum x86-64 microarchitecture level needed to run the binary
+    pub fn x86_64_level(&self) -> u8 {
+        cpu_features::x86_64_level(&cpu_features::required_features(self.text_section()))
+    }
+
+    /// The crate, module and item a symbol is for. This is parsed from the demangled name of Rust
+    /// symbols, other symbols are attributed to the crate of the DWARF function or compile unit
+    /// containing the address.
+    pub fn item_path(&self, name: &str, address: u64) -> Option<ItemPath> {
+        if let Some(path) = crates::symbol_path(name) {
+            return Some(path);
+        }
+        let krate = self.unit_crate(address)?;
+        Some(ItemPath {
+            crate_name: krate.to_string(),
+            module_path: krate.to_string(),
+            item_name: name.to_string(),
+        })
+    }
+
+    /// The crate of the innermost DWARF function or compile unit containing the address
+    fn unit_crate(&self, address: u64) -> Option<&Arc<str>> {
+        let unit_crates = self.binary.unit_crates.get_or_init(|| {
+            self.debug_object()
+                .and_then(|x| get_unit_crates(&x).ok())
+                .unwrap_or_default()
+        });
+        // Functions start after the unit containing them so the innermost range is found first
+        let index = unit_crates.partition_point(|x| x.0.start <= address);
+        unit_crates[..index]
+            .iter()
+            .rev()
+            .find(|x| x.0.contains(&address))
+            .map(|x| &x.1)
+    }
+
+    /// The crates the functions came from, largest first. Functions which can't be attributed
+    /// to a crate aren't included.
+    pub fn crates(&self) -> &[Arc<Crate>] {
+        self.binary.crates.get_or_init(|| {
+            crates::group_by_crate(self.functions(), |func| {
+                self.item_path(&func.name, func.address)
+                    .map(|x| x.crate_name)
+            })
+            .into_iter()
+            .map(Arc::new)
+            .collect()
+        })
+    }
+
+    /// The generic functions with their instantiations, ordered by the size of the duplicated
+    /// code with the largest first.
+    pub fn generic_items(&self) -> &[Arc<GenericItem>] {
+        self.binary.generic_items.get_or_init(|| {
+            generics::group_instantiations(self.functions())
+                .into_iter()
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    /// The line table rows of each path in the line tables, so finding a file's rows doesn't
+    /// need a scan of all the debug info
+    fn file_rows(&self) -> &FileRows {
+        self.binary.file_rows.get_or_init(|| {
+            let mut rows: FileRows = BTreeMap::new();
+            for (address, locations) in self.debug_info() {
+                for location in locations {
+                    rows.entry((location.file.clone(), location.comp_dir.clone()))
+                        .or_default()
+                        .push((*address, location.clone()));
+                }
+            }
+            rows
+        })
+    }
+
+    /// Every source file in the line tables along with the bytes of code from it, sorted by
+    /// absolute path
+    pub fn source_files(&self) -> &[Arc<SourceFile>] {
+        self.binary.source_files.get_or_init(|| {
+            let code_bytes = size::code_bytes_by_file(self.functions(), self.debug_info());
+            let mut files: BTreeMap<PathBuf, Vec<_>> = BTreeMap::new();
+            for ((path, comp_dir), rows) in self.file_rows() {
+                files
+                    .entry(source_paths::absolute_path(path, comp_dir.as_deref()))
+                    .or_default()
+                    .push(((path.clone(), comp_dir.clone()), rows[0].0));
+            }
+            files
+                .into_iter()
+                .map(|(absolute_path, paths)| {
+                    let crate_name = source_paths::path_crate(&absolute_path).or_else(|| {
+                        let address = paths.iter().map(|x| x.1).min()?;
+                        self.unit_crate(address).map(|x| x.to_string())
+                    });
+                    let line_table_paths = paths.into_iter().map(|x| x.0).collect::<Vec<_>>();
+                    let (path, comp_dir) = line_table_paths[0].clone();
+                    Arc::new(SourceFile {
+                        code_bytes: line_table_paths
+                            .iter()
+                            .filter_map(|x| code_bytes.get(x))
+                            .sum(),
+                        is_stdlib: source_paths::is_stdlib(&absolute_path),
+                        is_dependency: source_paths::is_dependency(&absolute_path),
+                        path,
+                        comp_dir,
+                        absolute_path,
+                        line_table_paths,
+                        crate_name,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// The source files matching the pattern by their absolute path or any of their paths in
+    /// the line tables
+    pub fn files_matching(&self, pattern: &FilePattern) -> Vec<Arc<SourceFile>> {
+        self.source_files()
+            .iter()
+            .filter(|x| x.matches(pattern))
+            .cloned()
+            .collect()
+    }
+
+    /// The source files whose path as given in the line tables or absolute path ends with the
+    /// components of `path`, so `src/lib.rs` finds the `lib.rs` of every crate
+    pub fn find_source_files(&self, path: &Path) -> Vec<Arc<SourceFile>> {
+        self.files_matching(&FilePattern::Suffix(path.to_path_buf()))
+    }
+
+    /// The line table rows of the source files with their addresses
+    fn rows_of<'a>(
+        &'a self,
+        files: &'a [Arc<SourceFile>],
+    ) -> impl Iterator<Item = &'a (u64, Arc<SourceLocation>)> + 'a {
+        let rows = self.file_rows();
+        files
+            .iter()
+            .flat_map(|x| &x.line_table_paths)
+            .filter_map(|x| rows.get(x))
+            .flatten()
+    }
+
+    /// The locations in the line tables for a source file
+    pub fn source_file_locations(&self, file: &Arc<SourceFile>) -> BTreeSet<Arc<SourceLocation>> {
+        self.rows_of(std::slice::from_ref(file))
+            .map(|x| x.1.clone())
+            .collect()
+    }
+
+    /// The lines a coverage tool can set breakpoints on, see [`crate::coverage`]. This reads the
+    /// line tables again as the sequences rows are in aren't kept, and only uses `is_stmt` rows.
+    pub fn coverage_map(&self, options: &CoverageOptions) -> Result<CoverageMap> {
+        let file = object::File::parse(&**self.debug_data())?;
+        let load_options = LoadOptions {
+            include_non_stmt_rows: false,
+            ..self.binary.options.clone()
+        };
+        let mut builder = CoverageBuilder::new(options.clone());
+        // Problems with units are diagnostics when the debug info is read so aren't repeated
+        let mut errors = vec![];
+        for_each_line_row(
+            &file,
+            &load_options,
+            &mut errors,
+            |sequence, address, location| builder.add_row(sequence, address, location),
+        )?;
+        Ok(builder.finish())
+    }
+
+    /// Sizes grouped by section, symbol, source file or compile unit, largest first. When
+    /// grouping by file or compile unit code which isn't in any is added up in an `[unknown]` row.
+    pub fn size_report(&self, group_by: SizeGrouping) -> Vec<SizeRow> {
+        let code_size = self.code_sections().iter().map(|x| x.size).sum();
+        match group_by {
+            SizeGrouping::Section => size::by_section(self.sections()),
+            SizeGrouping::Symbol => size::by_symbol(self.symbols(), self.sections()),
+            SizeGrouping::File => size::code_rows(
+                self.source_files()
+                    .iter()
+                    .filter(|x| x.code_bytes > 0)
+                    .map(|x| (x.absolute_path.display().to_string(), x.code_bytes)),
+                code_size,
+            ),
+            SizeGrouping::CompileUnit => {
+                let units = self
+                    .debug_object()
+                    .and_then(|x| get_unit_sizes(&x).ok())
+                    .unwrap_or_default();
+                let mut sizes: BTreeMap<String, u64> = BTreeMap::new();
+                for (name, size) in units {
+                    *sizes.entry(name).or_default() += size;
+                }
+                size::code_rows(sizes, code_size)
+            }
+        }
+    }
+
+    /// The locations in the line tables of the files matching the pattern
+    pub fn get_file_locations(&self, pattern: &FilePattern) -> BTreeSet<Arc<SourceLocation>> {
+        self.rows_of(&self.files_matching(pattern))
+            .map(|x| x.1.clone())
+            .collect()
+    }
+
+    /// The instructions at the start of the line table rows of the files matching the pattern,
+    /// sorted by address
+    pub fn get_file_instructions(&self, pattern: &FilePattern) -> Vec<Arc<Instruction>> {
+        self.rows_of(&self.files_matching(pattern))
+            .map(|x| x.0)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|x| self.find_instruction(x))
+            .collect()
+    }
+
+    /// Resolves an edge of the root query type. This is also used for the edges of each binary
+    /// when querying several binaries at once. [`Adapter::query`] rejects invalid parameters
+    /// before the query runs, without that check the edge has no vertices.
+    pub(crate) fn resolve_root_edge(
         &self,
-        edge_name: &Arc<str>,
+        edge_name: &str,
         parameters: &EdgeParameters,
-        resolve_info: &ResolveInfo,
-    ) -> VertexIterator<'a, Self::Vertex> {
-        match edge_name.as_ref() {
+    ) -> VertexIterator<'static, Vertex> {
+        self.root_edge(edge_name, parameters)
+            .unwrap_or_else(|_| Box::new(std::iter::empty()))
+    }
+
+    fn root_edge(
+        &self,
+        edge_name: &str,
+        parameters: &EdgeParameters,
+    ) -> Result<VertexIterator<'static, Vertex>> {
+        let string = |name| parameter(parameters, edge_name, name, FieldValue::as_str);
+        let int = |name| parameter(parameters, edge_name, name, FieldValue::as_i64);
+        let vertices: VertexIterator<'static, Vertex> = match edge_name {
+            "crates" => {
+                let crates = self.crates().to_vec();
+                Box::new(crates.into_iter().map(Vertex::Crate))
+            }
+            "cpuFeatureUsage" => {
+                let usage = self
+                    .cpu_feature_usage()
+                    .into_iter()
+                    .map(|x| Vertex::CpuFeature(x.into()))
+                    .collect::<Vec<_>>();
+                Box::new(usage.into_iter())
+            }
+            "dataInCode" => {
+                let data_in_code = self.data_in_code().to_vec();
+                Box::new(data_in_code.into_iter().map(Vertex::DataInCode))
+            }
+            "diagnostics" => {
+                let diagnostics = self.diagnostics();
+                Box::new(diagnostics.into_iter().map(Vertex::Diagnostic))
+            }
             "debug_info" => {
                 let locations = self
-                    .debug_info
+                    .debug_info()
                     .values()
                     .flat_map(|x| x.iter().map(|x| Vertex::SourceLocation(x.clone())))
                     .collect::<Vec<_>>();
                 Box::new(locations.into_iter())
             }
             "getFileInstructions" => {
-                let file: &str = parameters
-                    .get("file")
-                    .expect(
-                        "failed to find parameter 'file' when resolving 'getFileInstructions' starting vertices",
-                    )
-                    .as_str()
-                    .expect(
-                        "unexpected null or other incorrect datatype for Trustfall type 'String!'",
-                    );
-                let it = self
-                    .get_file_instructions(file.into())
-                    .into_iter()
-                    .map(|x| Vertex::DecodedInstruction(x));
-                Box::new(it)
+                let pattern = file_pattern(parameters, edge_name, "file")?;
+                let instructions = self.get_file_instructions(&pattern);
+                Box::new(instructions.into_iter().map(Vertex::DecodedInstruction))
+            }
+            "getFilesMatching" => {
+                let pattern = file_pattern(parameters, edge_name, "pattern")?;
+                let files = self.files_matching(&pattern);
+                Box::new(files.into_iter().map(Vertex::SourceFile))
+            }
+            "sizeReport" => {
+                let group_by = size_grouping(parameters, edge_name)?;
+                let rows = self.size_report(group_by);
+                Box::new(rows.into_iter().map(|x| Vertex::SizeRow(x.into())))
+            }
+            "sourceFiles" => {
+                let files = self.source_files().to_vec();
+                Box::new(files.into_iter().map(Vertex::SourceFile))
+            }
+            "findSourceFiles" => {
+                let files = self.find_source_files(Path::new(string("path")?));
+                Box::new(files.into_iter().map(Vertex::SourceFile))
+            }
+            "genericItems" => {
+                let items = self.generic_items().to_vec();
+                Box::new(items.into_iter().map(Vertex::GenericItem))
+            }
+            "findBytes" => {
+                let matches = self.find_bytes(&byte_pattern(parameters, edge_name)?);
+                Box::new(matches.into_iter().map(|x| Vertex::ByteRange(x.into())))
+            }
+            "functions" => {
+                let functions = self.functions().to_vec();
+                Box::new(functions.into_iter().map(Vertex::Function))
+            }
+            "getFunction" => {
+                let function = self.find_function(string("name")?).map(Vertex::Function);
+                Box::new(function.into_iter())
             }
             "getFileLocations" => {
-                let file: &str = parameters
-                    .get("file")
-                    .expect(
-                        "failed to find parameter 'file' when resolving 'getFileLocations' starting vertices",
-                    )
-                    .as_str()
-                    .expect(
-                        "unexpected null or other incorrect datatype for Trustfall type 'String!'",
-                    );
-                let it = self
-                    .get_file_locations(file.into())
-                    .into_iter()
-                    .map(|x| Vertex::SourceLocation(x));
-                Box::new(it)
+                let pattern = file_pattern(parameters, edge_name, "file")?;
+                let locations = self.get_file_locations(&pattern);
+                Box::new(locations.into_iter().map(Vertex::SourceLocation))
+            }
+            "getSection" => {
+                let section = self.get_section(string("name")?).map(Vertex::Section);
+                Box::new(section.into_iter())
+            }
+            "getSymbol" => {
+                let symbol = self.get_symbol(string("name")?).map(Vertex::Symbol);
+                Box::new(symbol.into_iter())
             }
             "getInstruction" => {
-                let address: i64 = parameters
-                    .get("address")
-                    .expect(
-                        "failed to find parameter 'address' when resolving 'getInstruction' starting vertices",
-                    )
-                    .as_i64()
-                    .expect(
-                        "unexpected null or other incorrect datatype for Trustfall type 'Int!'",
-                    );
                 let instruction = self
-                    .find_instruction(address as u64)
-                    .map(|x| Vertex::DecodedInstruction(x));
+                    .find_instruction(int("address")? as u64)
+                    .map(Vertex::DecodedInstruction);
                 Box::new(instruction.into_iter())
             }
             "getLocation" => {
-                let address = parameters
-                    .get("address")
-                    .expect(
-                        "failed to find parameter 'address' when resolving 'getLocation' starting vertices",
-                    )
-                    .as_i64()
-                    .expect(
-                        "unexpected null or other incorrect datatype for Trustfall type 'Int!'",
-                    ) as u64;
-                match self.debug_info.get(&address) {
-                    Some(val) => {
-                        Box::new(val.clone().into_iter().map(|x| Vertex::SourceLocation(x)))
-                    }
-                    None => Box::new(std::iter::empty()),
-                }
+                let locations = self
+                    .debug_info()
+                    .get(&(int("address")? as u64))
+                    .cloned()
+                    .unwrap_or_default();
+                Box::new(locations.into_iter().map(Vertex::SourceLocation))
+            }
+            "jumpTables" => Box::new(self.jump_tables().into_iter().map(Vertex::JumpTable)),
+            "sections" => {
+                let sections = self.sections().to_vec();
+                Box::new(sections.into_iter().map(Vertex::Section))
+            }
+            "symbols" => {
+                let symbols = self.symbols().to_vec();
+                Box::new(symbols.into_iter().map(Vertex::Symbol))
             }
             "text_section" => {
-                let text_section = self.text_section.clone();
-                let iter = text_section
-                    .into_iter()
-                    .map(|x| Vertex::DecodedInstruction(x.clone()));
+                let text_section = self.text_section().to_vec();
+                let iter = text_section.into_iter().map(Vertex::DecodedInstruction);
                 Box::new(iter)
             }
+            "xrefsTo" => {
+                let refs = self.references_to(int("address")? as u64);
+                Box::new(refs.into_iter().map(Vertex::DataReference))
+            }
             _ => {
                 unreachable!(
                     "attempted to resolve starting vertices for unexpected edge name: {edge_name}"
                 )
             }
-        }
+        };
+        Ok(vertices)
+    }
+}
+
+impl<'a> trustfall::provider::Adapter<'a> for Adapter {
+    type Vertex = Vertex;
+
+    fn resolve_starting_vertices(
+        &self,
+        edge_name: &Arc<str>,
+        parameters: &EdgeParameters,
+        _resolve_info: &ResolveInfo,
+    ) -> VertexIterator<'a, Self::Vertex> {
+        self.resolve_root_edge(edge_name, parameters)
     }
 
     fn resolve_property<V: AsVertex<Self::Vertex> + 'a>(
@@ -202,10 +1069,83 @@ impl<'a> trustfall::provider::Adapter<'a> for Adapter {
             return resolve_property_with(contexts, |vertex| vertex.typename().into());
         }
         match type_name.as_ref() {
+            "BasicBlock" => super::properties::resolve_basic_block_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "ByteRange" => super::properties::resolve_byte_range_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "CpuFeature" => super::properties::resolve_cpu_feature_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Crate" => super::properties::resolve_crate_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "DataInCode" => super::properties::resolve_data_in_code_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "DataReference" => super::properties::resolve_data_reference_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
             "DecodedInstruction" => super::properties::resolve_decoded_instruction_property(
                 contexts,
                 property_name.as_ref(),
                 resolve_info,
+                self,
+            ),
+            "Diagnostic" => super::properties::resolve_diagnostic_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Function" => super::properties::resolve_function_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "GenericItem" => super::properties::resolve_generic_item_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "JumpTable" => super::properties::resolve_jump_table_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Section" => super::properties::resolve_section_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "SizeRow" => super::properties::resolve_size_row_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "SourceFile" => super::properties::resolve_source_file_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Symbol" => super::properties::resolve_symbol_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+                self,
             ),
             "SourceLocation" => super::properties::resolve_source_location_property(
                 contexts,
@@ -229,6 +1169,64 @@ impl<'a> trustfall::provider::Adapter<'a> for Adapter {
         resolve_info: &ResolveEdgeInfo,
     ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Self::Vertex>> {
         match type_name.as_ref() {
+            "BasicBlock" => super::edges::resolve_basic_block_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "ByteRange" => {
+                super::edges::resolve_byte_range_edge(contexts, edge_name.as_ref(), resolve_info)
+            }
+            "CpuFeature" => {
+                super::edges::resolve_cpu_feature_edge(contexts, edge_name.as_ref(), resolve_info)
+            }
+            "Crate" => super::edges::resolve_crate_edge(contexts, edge_name.as_ref(), resolve_info),
+            "DataInCode" => super::edges::resolve_data_in_code_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "DataReference" => super::edges::resolve_data_reference_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "DecodedInstruction" => super::edges::resolve_decoded_instruction_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "Function" => {
+                super::edges::resolve_function_edge(contexts, edge_name.as_ref(), resolve_info)
+            }
+            "GenericItem" => {
+                super::edges::resolve_generic_item_edge(contexts, edge_name.as_ref(), resolve_info)
+            }
+            "JumpTable" => super::edges::resolve_jump_table_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "Section" => super::edges::resolve_section_edge(
+                contexts,
+                edge_name.as_ref(),
+                parameters,
+                resolve_info,
+            ),
+            "SourceFile" => super::edges::resolve_source_file_edge(
+                contexts,
+                edge_name.as_ref(),
+                resolve_info,
+                self,
+            ),
+            "Symbol" => {
+                super::edges::resolve_symbol_edge(contexts, edge_name.as_ref(), resolve_info, self)
+            }
             _ => {
                 unreachable!(
                     "attempted to resolve edge '{edge_name}' on unexpected type: {type_name}"
@@ -0,0 +1,107 @@
+use super::Adapter;
+use crate::bytes::Bytes;
+use crate::disassembly::DisassemblyMode;
+use crate::error::Result;
+use crate::loader::LoadOptions;
+use std::path::{Path, PathBuf};
+
+/// Configures how an object file is loaded into an [`Adapter`].
+///
+/// ```no_run
+/// use object_trustfall_adapter::adapter::Adapter;
+/// use object_trustfall_adapter::disassembly::DisassemblyMode;
+///
+/// let adapter = Adapter::builder()
+///     .disassembly(DisassemblyMode::Recursive)
+///     .debug_search_path("/usr/lib/debug")
+///     .remap_path("/rustc/abc123", "/home/me/rust")
+///     .strict(true)
+///     .load("target/debug/my-binary")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AdapterBuilder {
+    options: LoadOptions,
+}
+
+impl AdapterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from existing options
+    pub fn with_options(options: LoadOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &LoadOptions {
+        &self.options
+    }
+
+    /// How to find the instructions in the code sections, defaults to a linear sweep
+    pub fn disassembly(mut self, mode: DisassemblyMode) -> Self {
+        self.options.disassembly = mode;
+        self
+    }
+
+    /// The sections to disassemble, defaults to just `.text`
+    pub fn code_sections<S: Into<String>>(mut self, sections: impl IntoIterator<Item = S>) -> Self {
+        self.options.code_sections = sections.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Decode instructions as 16, 32 or 64 bit code regardless of the file's architecture.
+    /// Instructions are always decoded as x86, so without this loading a file for any other
+    /// architecture fails with
+    /// [`Error::UnsupportedArchitecture`](crate::error::Error::UnsupportedArchitecture).
+    pub fn bitness(mut self, bitness: u32) -> Self {
+        self.options.bitness = Some(bitness);
+        self
+    }
+
+    /// Add a directory to look for separate debug info in. Files are found by build ID under
+    /// `.build-id` or by the name in the binary's `.gnu_debuglink` section.
+    pub fn debug_search_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.debug_search_paths.push(path.into());
+        self
+    }
+
+    /// Include line table rows which aren't marked as statements. These give more complete
+    /// address coverage at the cost of locations that aren't good breakpoints.
+    pub fn include_non_stmt_rows(mut self, include: bool) -> Self {
+        self.options.include_non_stmt_rows = include;
+        self
+    }
+
+    /// Replace the `from` prefix of source paths in the debug info with `to`. Remappings are tried
+    /// in the order they're added.
+    pub fn remap_path(mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        self.options.path_remapping.push((from.into(), to.into()));
+        self
+    }
+
+    /// Fail to load when the debug info is missing or unreadable instead of carrying on without
+    /// it. This reads the debug info while loading rather than when it's first queried.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    pub fn load(self, path: impl AsRef<Path>) -> Result<Adapter> {
+        Adapter::load_with_options(path, &self.options)
+    }
+
+    /// Load the file using the index cache in `cache_dir`, see [`Adapter::load_cached`]
+    pub fn load_cached(
+        self,
+        path: impl AsRef<Path>,
+        cache_dir: impl AsRef<Path>,
+    ) -> Result<Adapter> {
+        Adapter::load_cached_with_options(path, cache_dir, &self.options)
+    }
+
+    /// Load an object file that's already in memory, the data is copied into the adapter.
+    pub fn from_bytes(self, data: &[u8]) -> Result<Adapter> {
+        Adapter::from_data(Bytes::from(data.to_vec()), None, &self.options)
+    }
+}
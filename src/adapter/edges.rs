@@ -1 +1,330 @@
+use super::parameters::section_range;
+use super::vertex::Vertex;
+use super::{function_containing, symbol_containing, Adapter, ByteRange};
+use crate::xrefs;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use trustfall::provider::{
+    resolve_neighbors_with, AsVertex, ContextIterator, ContextOutcomeIterator, EdgeParameters,
+    ResolveEdgeInfo, VertexIterator,
+};
 
+/// Resolves an edge of the vertices of one type, `downcast` is the type's `as_*` method on
+/// [`Vertex`] and `neighbors` finds the vertices at the other end. A vertex of any other type
+/// means the query was planned against the wrong schema.
+fn resolve_with<'a, V, T, F, I>(
+    contexts: ContextIterator<'a, V>,
+    downcast: fn(&Vertex) -> Option<&Arc<T>>,
+    neighbors: F,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>>
+where
+    V: AsVertex<Vertex> + 'a,
+    T: ?Sized + 'a,
+    F: Fn(&Arc<T>) -> I + 'a,
+    I: IntoIterator<Item = Vertex>,
+    I::IntoIter: 'a,
+{
+    resolve_neighbors_with(contexts, move |vertex| match downcast(vertex) {
+        Some(x) => Box::new(neighbors(x).into_iter()),
+        None => unreachable!("Invalid vertex: {:?}", vertex),
+    })
+}
+
+pub(super) fn resolve_basic_block_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "function" => resolve_with(contexts, Vertex::as_basic_block, move |block| {
+            function_containing(adapter.functions(), block.address).map(Vertex::Function)
+        }),
+        "instructions" => resolve_with(contexts, Vertex::as_basic_block, |block| {
+            let instructions = block.instructions.clone();
+            instructions.into_iter().map(Vertex::DecodedInstruction)
+        }),
+        "successors" => resolve_with(contexts, Vertex::as_basic_block, move |block| {
+            let successors = function_containing(adapter.functions(), block.address)
+                .map(|func| {
+                    block
+                        .successors
+                        .iter()
+                        .filter_map(|x| func.find_basic_block(*x))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            successors.into_iter().map(Vertex::BasicBlock)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'BasicBlock'")
+        }
+    }
+}
+
+pub(super) fn resolve_byte_range_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "section" => resolve_with(contexts, Vertex::as_byte_range, |range| {
+            Some(Vertex::Section(range.section.clone()))
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'ByteRange'")
+        }
+    }
+}
+
+pub(super) fn resolve_cpu_feature_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "functions" => resolve_with(contexts, Vertex::as_cpu_feature, |usage| {
+            let functions = usage.functions.clone();
+            functions.into_iter().map(Vertex::Function)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'CpuFeature'")
+        }
+    }
+}
+
+pub(super) fn resolve_crate_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "functions" => resolve_with(contexts, Vertex::as_crate, |krate| {
+            let functions = krate.functions.clone();
+            functions.into_iter().map(Vertex::Function)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Crate'")
+        }
+    }
+}
+
+pub(super) fn resolve_data_in_code_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "bytes" => resolve_with(contexts, Vertex::as_data_in_code, move |region| {
+            adapter.find_section(region.address).map(|section| {
+                Vertex::ByteRange(
+                    ByteRange {
+                        offset: (region.address - section.address) as usize,
+                        length: region.size as usize,
+                        section,
+                    }
+                    .into(),
+                )
+            })
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'DataInCode'")
+        }
+    }
+}
+
+pub(super) fn resolve_data_reference_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "instruction" => resolve_with(contexts, Vertex::as_data_reference, |reference| {
+            Some(Vertex::DecodedInstruction(reference.instruction.clone()))
+        }),
+        "section" => resolve_with(contexts, Vertex::as_data_reference, move |reference| {
+            adapter.find_section(reference.address).map(Vertex::Section)
+        }),
+        "symbol" => resolve_with(contexts, Vertex::as_data_reference, move |reference| {
+            symbol_containing(adapter.symbols(), reference.address).map(Vertex::Symbol)
+        }),
+        _ => {
+            unreachable!(
+                "attempted to resolve unexpected edge '{edge_name}' on type 'DataReference'"
+            )
+        }
+    }
+}
+
+pub(super) fn resolve_decoded_instruction_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "dataReferences" => resolve_with(contexts, Vertex::as_decoded_instruction, move |instr| {
+            let refs = xrefs::data_references(instr, adapter.sections());
+            refs.into_iter().map(|x| Vertex::DataReference(x.into()))
+        }),
+        _ => {
+            unreachable!(
+                "attempted to resolve unexpected edge '{edge_name}' on type 'DecodedInstruction'"
+            )
+        }
+    }
+}
+
+pub(super) fn resolve_function_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "basicBlocks" => resolve_with(contexts, Vertex::as_function, |func| {
+            let blocks = func.basic_blocks().to_vec();
+            blocks.into_iter().map(Vertex::BasicBlock)
+        }),
+        "instructions" => resolve_with(contexts, Vertex::as_function, |func| {
+            let instructions = func.instructions().to_vec();
+            instructions.into_iter().map(Vertex::DecodedInstruction)
+        }),
+        "jumpTables" => resolve_with(contexts, Vertex::as_function, |func| {
+            let tables = func.jump_tables().to_vec();
+            tables.into_iter().map(Vertex::JumpTable)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Function'")
+        }
+    }
+}
+
+pub(super) fn resolve_generic_item_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "instantiations" => resolve_with(contexts, Vertex::as_generic_item, |item| {
+            let functions = item.instantiations.clone();
+            functions.into_iter().map(Vertex::Function)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'GenericItem'")
+        }
+    }
+}
+
+pub(super) fn resolve_jump_table_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "dispatch" => resolve_with(contexts, Vertex::as_jump_table, |table| {
+            Some(Vertex::DecodedInstruction(table.dispatch.clone()))
+        }),
+        "function" => resolve_with(contexts, Vertex::as_jump_table, move |table| {
+            function_containing(adapter.functions(), table.dispatch.ip()).map(Vertex::Function)
+        }),
+        "targets" => resolve_with(contexts, Vertex::as_jump_table, move |table| {
+            let mut targets = table.targets.clone();
+            targets.sort_unstable();
+            targets.dedup();
+            let blocks = function_containing(adapter.functions(), table.dispatch.ip())
+                .map(|func| {
+                    targets
+                        .iter()
+                        .filter_map(|x| func.find_basic_block(*x))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            blocks.into_iter().map(Vertex::BasicBlock)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'JumpTable'")
+        }
+    }
+}
+
+pub(super) fn resolve_section_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    parameters: &EdgeParameters,
+    _resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "bytes" => {
+            // Checked by Adapter::query, the edge has no vertices if it's used without that
+            let range = section_range(parameters, edge_name).ok();
+            resolve_with(contexts, Vertex::as_section, move |section| {
+                let (offset, length) = range?;
+                let len = section.data.len();
+                let remaining = len.checked_sub(offset)?;
+                let range = ByteRange {
+                    section: section.clone(),
+                    offset,
+                    length: length.unwrap_or(len).min(remaining),
+                };
+                Some(Vertex::ByteRange(range.into()))
+            })
+        }
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Section'")
+        }
+    }
+}
+
+pub(super) fn resolve_source_file_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "locations" => resolve_with(contexts, Vertex::as_source_file, move |file| {
+            let locations = adapter.source_file_locations(file);
+            locations.into_iter().map(Vertex::SourceLocation)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'SourceFile'")
+        }
+    }
+}
+
+pub(super) fn resolve_symbol_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    _resolve_info: &ResolveEdgeInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let adapter = adapter.clone();
+    match edge_name {
+        "referencedBy" => resolve_with(contexts, Vertex::as_symbol, move |symbol| {
+            let end = symbol.address.saturating_add(symbol.size.max(1));
+            let mut seen = BTreeSet::new();
+            adapter
+                .xrefs()
+                .range(symbol.address..end)
+                .flat_map(|(_, refs)| refs.iter())
+                .filter(|x| seen.insert(x.instruction.ip()))
+                .map(|x| Vertex::DecodedInstruction(x.instruction.clone()))
+                .collect::<Vec<_>>()
+        }),
+        "section" => resolve_with(contexts, Vertex::as_symbol, move |symbol| {
+            adapter.find_section(symbol.address).map(Vertex::Section)
+        }),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Symbol'")
+        }
+    }
+}
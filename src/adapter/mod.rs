@@ -1,15 +1,30 @@
+use crate::bytes::Bytes;
+use crate::cfg::{self, BasicBlock};
+use crate::cpu_features;
+use crate::disassembly;
+use crate::jump_tables::{self, JumpTable};
+use crate::source_paths::FilePattern;
+use iced_x86::{CpuidFeature, Instruction};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 mod adapter_impl;
+mod builder;
 mod edges;
+mod parameters;
 mod properties;
 mod vertex;
 
 #[cfg(test)]
 mod tests;
 
-pub use adapter_impl::Adapter;
+pub use adapter_impl::{Adapter, QueryResults};
+pub use builder::AdapterBuilder;
+pub use cpu_features::CpuFeatureUsage;
+pub(crate) use parameters::execute;
 pub use vertex::Vertex;
 
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Deserialize, Serialize)]
@@ -17,4 +32,268 @@ pub struct SourceLocation {
     pub file: PathBuf,
     pub line: usize,
     pub column: usize,
+    /// Directory the compile unit was built in, which `file` is relative to if it's relative
+    #[serde(default)]
+    pub comp_dir: Option<Arc<Path>>,
+}
+
+/// A source file with code in the binary according to the DWARF line tables. Units built in
+/// different directories can give the same file with different paths, such as a dependency's
+/// `src/lib.rs` which is absolute in the units it's inlined into, so files are identified by their
+/// absolute path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceFile {
+    /// The first of `line_table_paths`
+    pub path: PathBuf,
+    pub comp_dir: Option<Arc<Path>>,
+    /// `path` resolved against `comp_dir` and normalized
+    pub absolute_path: PathBuf,
+    /// Every path and compile unit directory in the line tables which resolves to this file
+    pub line_table_paths: Vec<(PathBuf, Option<Arc<Path>>)>,
+    /// Bytes of instructions whose line table rows are in the file
+    pub code_bytes: u64,
+    pub is_stdlib: bool,
+    /// Whether the file is from a crate downloaded by cargo
+    pub is_dependency: bool,
+    /// Crate the file is part of, from its path for the standard library and dependencies or
+    /// otherwise the compile unit containing its code
+    pub crate_name: Option<String>,
+}
+
+impl SourceFile {
+    /// Whether the absolute path or any of the paths in the line tables match the pattern
+    pub fn matches(&self, pattern: &FilePattern) -> bool {
+        pattern.matches(&self.absolute_path)
+            || self.line_table_paths.iter().any(|x| pattern.matches(&x.0))
+    }
+
+    /// Whether the location is in this file
+    pub fn contains(&self, location: &SourceLocation) -> bool {
+        self.line_table_paths
+            .iter()
+            .any(|(file, comp_dir)| *file == location.file && *comp_dir == location.comp_dir)
+    }
+}
+
+/// A section from the object file along with its contents. Sections which don't take up space in
+/// the file (such as `.bss`) will have no data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Section {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    pub data: Bytes,
+}
+
+impl Section {
+    pub fn contains(&self, address: u64) -> bool {
+        self.address <= address && address < self.address.saturating_add(self.size)
+    }
+
+    /// Get the section data in the given address range if it's present.
+    pub fn data_at(&self, address: u64, length: usize) -> Option<&[u8]> {
+        let start = usize::try_from(address.checked_sub(self.address)?).ok()?;
+        self.data.get(start..start.checked_add(length)?)
+    }
+}
+
+/// A range of bytes within a section.
+#[derive(Clone, Debug)]
+pub struct ByteRange {
+    pub section: Arc<Section>,
+    /// Offset of the start of the range from the start of the section
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl ByteRange {
+    pub fn address(&self) -> u64 {
+        self.section.address + self.offset as u64
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.section.data[self.offset..(self.offset + self.length)]
+    }
+}
+
+/// A symbol from the symbol table.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    /// The kind of symbol (text, data, tls etc)
+    pub kind: String,
+}
+
+impl Symbol {
+    pub fn contains(&self, address: u64) -> bool {
+        self.address == address
+            || (self.address < address && address < self.address.saturating_add(self.size))
+    }
+}
+
+/// A function found in the symbol table. Its instructions, jump tables and control flow graph are
+/// decoded from the function's bytes the first time they're needed.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(into = "FunctionData", from = "FunctionData")]
+pub struct Function {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    code: Bytes,
+    bitness: u32,
+    sections: Arc<Vec<Arc<Section>>>,
+    instructions: OnceLock<Vec<Arc<Instruction>>>,
+    jump_tables: OnceLock<Vec<Arc<JumpTable>>>,
+    basic_blocks: OnceLock<Vec<Arc<BasicBlock>>>,
+}
+
+/// The decoded form of a function used when serializing.
+#[derive(Deserialize, Serialize)]
+struct FunctionData {
+    name: String,
+    address: u64,
+    size: u64,
+    instructions: Vec<Arc<Instruction>>,
+    jump_tables: Vec<Arc<JumpTable>>,
+    basic_blocks: Vec<Arc<BasicBlock>>,
+}
+
+impl From<Function> for FunctionData {
+    fn from(func: Function) -> Self {
+        Self {
+            instructions: func.instructions().to_vec(),
+            jump_tables: func.jump_tables().to_vec(),
+            basic_blocks: func.basic_blocks().to_vec(),
+            name: func.name,
+            address: func.address,
+            size: func.size,
+        }
+    }
+}
+
+impl From<FunctionData> for Function {
+    fn from(data: FunctionData) -> Self {
+        Self {
+            name: data.name,
+            address: data.address,
+            size: data.size,
+            instructions: data.instructions.into(),
+            jump_tables: data.jump_tables.into(),
+            basic_blocks: data.basic_blocks.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("address", &self.address)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Function {
+    /// Creates a function from its code, `sections` are used to read any jump tables it uses.
+    pub fn new(
+        name: String,
+        address: u64,
+        code: Bytes,
+        bitness: u32,
+        sections: Arc<Vec<Arc<Section>>>,
+    ) -> Self {
+        Self {
+            name,
+            address,
+            size: code.len() as u64,
+            code,
+            bitness,
+            sections,
+            ..Default::default()
+        }
+    }
+
+    /// Use already decoded instructions instead of decoding the function's code. This is used
+    /// when the instructions come from recursive disassembly which may skip data in the function.
+    pub fn with_instructions(self, instructions: Vec<Arc<Instruction>>) -> Self {
+        Self {
+            instructions: instructions.into(),
+            ..self
+        }
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.address <= address && address < self.address.saturating_add(self.size)
+    }
+
+    pub fn instructions(&self) -> &[Arc<Instruction>] {
+        self.instructions
+            .get_or_init(|| disassembly::linear_sweep(self.bitness, &self.code, self.address))
+    }
+
+    pub fn jump_tables(&self) -> &[Arc<JumpTable>] {
+        self.jump_tables.get_or_init(|| {
+            let bounds = self.address..self.address.saturating_add(self.size);
+            jump_tables::find_jump_tables(self.instructions(), &self.sections, bounds)
+                .into_iter()
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    pub fn basic_blocks(&self) -> &[Arc<BasicBlock>] {
+        self.basic_blocks.get_or_init(|| {
+            let bounds = self.address..self.address.saturating_add(self.size);
+            cfg::basic_blocks(self.instructions(), bounds, self.jump_tables())
+                .into_iter()
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    pub fn find_basic_block(&self, address: u64) -> Option<Arc<BasicBlock>> {
+        let blocks = self.basic_blocks();
+        blocks
+            .binary_search_by_key(&address, |x| x.address)
+            .ok()
+            .map(|i| blocks[i].clone())
+    }
+
+    pub fn required_cpu_features(&self) -> BTreeSet<CpuidFeature> {
+        cpu_features::required_features(self.instructions())
+    }
+
+    pub fn x86_64_level(&self) -> u8 {
+        cpu_features::x86_64_level(&self.required_cpu_features())
+    }
+}
+
+/// Finds the symbol containing the address, `symbols` should be sorted by address. Symbols without
+/// a size only contain their own address.
+pub(crate) fn symbol_containing(symbols: &[Arc<Symbol>], address: u64) -> Option<Arc<Symbol>> {
+    let index = symbols.partition_point(|x| x.address <= address);
+    let start = symbols[..index].last()?.address;
+    symbols[..index]
+        .iter()
+        .rev()
+        .take_while(|x| x.address == start)
+        .find(|x| x.contains(address))
+        .cloned()
+}
+
+/// Finds the function containing the address, `functions` should be sorted by address.
+pub(crate) fn function_containing(
+    functions: &[Arc<Function>],
+    address: u64,
+) -> Option<Arc<Function>> {
+    let index = functions.partition_point(|x| x.address <= address);
+    functions[..index]
+        .iter()
+        .rev()
+        .find(|x| x.contains(address))
+        .cloned()
 }
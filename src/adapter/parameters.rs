@@ -0,0 +1,125 @@
+//! Reading the parameters of edges, and checking the ones the schema can't describe such as
+//! patterns before a query runs so an invalid one fails the query.
+use super::adapter_impl::QueryResults;
+use crate::bytes::BytePattern;
+use crate::error::{Error, Result};
+use crate::size::SizeGrouping;
+use crate::source_paths::{FileMatch, FilePattern};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use trustfall::provider::{Adapter, EdgeParameters};
+use trustfall::{FieldValue, Schema};
+use trustfall_core::frontend;
+use trustfall_core::interpreter::execution::interpret_ir;
+use trustfall_core::ir::IRQueryComponent;
+
+/// Reads the parameter `name` of an edge with `read` e.g. [`FieldValue::as_str`]. Trustfall checks
+/// parameters against the schema, so this only fails if the schema and the adapter disagree.
+pub(super) fn parameter<'p, T>(
+    parameters: &'p EdgeParameters,
+    edge_name: &str,
+    name: &str,
+    read: impl FnOnce(&'p FieldValue) -> Option<T>,
+) -> Result<T> {
+    parameters.get(name).and_then(read).ok_or_else(|| {
+        Error::Query(format!(
+            "missing or invalid parameter '{name}' of edge '{edge_name}'"
+        ))
+    })
+}
+
+/// The file pattern from the parameter `name` of an edge and its `mode` parameter
+pub(super) fn file_pattern(
+    parameters: &EdgeParameters,
+    edge_name: &str,
+    name: &str,
+) -> Result<FilePattern> {
+    let mode = parameter(parameters, edge_name, "mode", FieldValue::as_str)?;
+    let pattern = parameter(parameters, edge_name, name, FieldValue::as_str)?;
+    FilePattern::new(pattern, mode.parse::<FileMatch>().map_err(Error::Query)?)
+}
+
+/// The `groupBy` parameter of `sizeReport`
+pub(super) fn size_grouping(parameters: &EdgeParameters, edge_name: &str) -> Result<SizeGrouping> {
+    let group_by = parameter(parameters, edge_name, "groupBy", FieldValue::as_str)?;
+    group_by.parse().map_err(Error::Query)
+}
+
+/// The `pattern` parameter of `findBytes`
+pub(super) fn byte_pattern(parameters: &EdgeParameters, edge_name: &str) -> Result<BytePattern> {
+    let pattern = parameter(parameters, edge_name, "pattern", FieldValue::as_str)?;
+    BytePattern::parse(pattern)
+        .ok_or_else(|| Error::Query(format!("invalid byte pattern '{pattern}'")))
+}
+
+/// The `offset` and optional `length` parameters of `Section.bytes`, neither can be negative
+pub(super) fn section_range(
+    parameters: &EdgeParameters,
+    edge_name: &str,
+) -> Result<(usize, Option<usize>)> {
+    let non_negative = |name: &str, value: i64| {
+        usize::try_from(value).map_err(|_| {
+            Error::Query(format!(
+                "parameter '{name}' of edge '{edge_name}' is negative: {value}"
+            ))
+        })
+    };
+    let offset = parameter(parameters, edge_name, "offset", FieldValue::as_i64)?;
+    let length = match parameters.get("length") {
+        None | Some(FieldValue::Null) => None,
+        Some(_) => {
+            let length = parameter(parameters, edge_name, "length", FieldValue::as_i64)?;
+            Some(non_negative("length", length)?)
+        }
+    };
+    Ok((non_negative("offset", offset)?, length))
+}
+
+/// Checks the parameters of one edge, `type_name` is the type the edge starts from. The edges of
+/// a `Binary` when querying several binaries are the root edges of a single binary.
+fn check_edge(type_name: &str, edge_name: &str, parameters: &EdgeParameters) -> Result<()> {
+    match (type_name, edge_name) {
+        ("RootSchemaQuery" | "Binary", "getFileInstructions" | "getFileLocations") => {
+            file_pattern(parameters, edge_name, "file").map(drop)
+        }
+        ("RootSchemaQuery" | "Binary", "getFilesMatching") => {
+            file_pattern(parameters, edge_name, "pattern").map(drop)
+        }
+        ("RootSchemaQuery" | "Binary", "sizeReport") => {
+            size_grouping(parameters, edge_name).map(drop)
+        }
+        ("RootSchemaQuery" | "Binary", "findBytes") => {
+            byte_pattern(parameters, edge_name).map(drop)
+        }
+        ("Section", "bytes") => section_range(parameters, edge_name).map(drop),
+        _ => Ok(()),
+    }
+}
+
+fn check_component(component: &IRQueryComponent) -> Result<()> {
+    for edge in component.edges.values() {
+        let from = &component.vertices[&edge.from_vid];
+        check_edge(&from.type_name, &edge.edge_name, &edge.parameters)?;
+    }
+    for fold in component.folds.values() {
+        let from = &component.vertices[&fold.from_vid];
+        check_edge(&from.type_name, &fold.edge_name, &fold.parameters)?;
+        check_component(&fold.component)?;
+    }
+    Ok(())
+}
+
+/// Runs a query like [`trustfall::execute_query`], after checking the parameters of its edges.
+/// Edges with invalid parameters have no vertices when the adapter is used without this.
+pub(crate) fn execute<A: Adapter<'static> + 'static>(
+    schema: &Schema,
+    adapter: Arc<A>,
+    query: &str,
+    variables: BTreeMap<Arc<str>, FieldValue>,
+) -> Result<QueryResults> {
+    let query = frontend::parse(schema, query).map_err(|e| Error::Query(e.to_string()))?;
+    let ir = &query.ir_query;
+    check_edge("RootSchemaQuery", &ir.root_name, &ir.root_parameters)?;
+    check_component(&ir.root_component)?;
+    interpret_ir(adapter, query, Arc::new(variables)).map_err(|e| Error::Query(e.to_string()))
+}
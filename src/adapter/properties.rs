@@ -1,63 +1,110 @@
 use super::vertex::Vertex;
+use super::Adapter;
+use crate::bytes::to_hex;
+use crate::cpu_features;
+use crate::demangle;
+use crate::disassembly;
+use crate::generics;
 use iced_x86::{Formatter, NasmFormatter};
+use std::path::Path;
 use std::sync::Arc;
 use trustfall::{
-    provider::{AsVertex, ContextIterator, ContextOutcomeIterator, DataContext, ResolveInfo},
+    provider::{
+        resolve_property_with, AsVertex, ContextIterator, ContextOutcomeIterator, ResolveInfo,
+    },
     FieldValue,
 };
 
+/// Resolves a property of the vertices of one type, `downcast` is the type's `as_*` method on
+/// [`Vertex`] and `value` reads the property. A vertex of any other type means the query was
+/// planned against the wrong schema.
+fn resolve_with<'a, V, T, F>(
+    contexts: ContextIterator<'a, V>,
+    downcast: fn(&Vertex) -> Option<&Arc<T>>,
+    value: F,
+) -> ContextOutcomeIterator<'a, V, FieldValue>
+where
+    V: AsVertex<Vertex> + 'a,
+    T: ?Sized + 'a,
+    F: Fn(&T) -> FieldValue + 'a,
+{
+    resolve_property_with(contexts, move |vertex| match downcast(vertex) {
+        Some(x) => value(x),
+        None => unreachable!("Invalid vertex: {:?}", vertex),
+    })
+}
+
+fn path_value(path: &Path) -> FieldValue {
+    path.display().to_string().into()
+}
+
+/// The crate, module path or item name of a symbol or function
+fn item_path_value(adapter: &Adapter, name: &str, address: u64, part: &str) -> FieldValue {
+    adapter
+        .item_path(name, address)
+        .map(|x| match part {
+            "crate" => x.crate_name,
+            "modulePath" => x.module_path,
+            _ => x.item_name,
+        })
+        .into()
+}
+
+fn demangled_name(name: &str) -> FieldValue {
+    demangle::demangle(name)
+        .map_or_else(|| name.to_string(), |x| x.name)
+        .into()
+}
+
+fn demangled_name_no_hash(name: &str) -> FieldValue {
+    demangle::demangle(name)
+        .map_or_else(|| name.to_string(), |x| x.name_no_hash)
+        .into()
+}
+
+fn mangling_scheme(name: &str) -> FieldValue {
+    demangle::demangle(name)
+        .map(|x| x.scheme.to_string())
+        .into()
+}
+
 pub(super) fn resolve_decoded_instruction_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
     _resolve_info: &ResolveInfo,
+    adapter: &Adapter,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
-    let func = match property_name {
-        "address" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::DecodedInstruction(instr)) => (v.clone(), FieldValue::Uint64(instr.ip())),
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
-        },
-        "length" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::DecodedInstruction(instr)) => {
-                (v.clone(), FieldValue::Uint64(instr.len() as u64))
-            }
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
-        },
-        "name" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::DecodedInstruction(instr)) => {
-                let string = format!("{:?}", instr.mnemonic());
-                (v.clone(), FieldValue::String(Arc::from(string.as_str())))
-            }
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
-        },
-        "operands" => {
-            println!(
-                "implement property 'operands' in fn `resolve_decoded_instruction_property()`"
-            );
-            |v: DataContext<V>| match v.active_vertex() {
-                Some(Vertex::DecodedInstruction(instr)) => {
-                    let mut operands = String::new();
-                    let mut fmt = NasmFormatter::new();
-                    fmt.format_all_operands(instr, &mut operands);
-                    let operands = operands
-                        .split(",")
-                        .map(|x| FieldValue::String(x.into()))
-                        .collect::<Vec<_>>();
-                    (v.clone(), FieldValue::List(operands.into()))
-                }
-                None => (v, FieldValue::Null),
-                Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
-            }
+    let value: fn(&iced_x86::Instruction) -> FieldValue = match property_name {
+        "bytes" => {
+            let adapter = adapter.clone();
+            return resolve_with(contexts, Vertex::as_decoded_instruction, move |instr| {
+                adapter
+                    .sections()
+                    .iter()
+                    .filter(|x| x.contains(instr.ip()))
+                    .find_map(|x| x.data_at(instr.ip(), instr.len()))
+                    .map(to_hex)
+                    .into()
+            });
         }
+        "address" => |instr| instr.ip().into(),
+        "isInvalid" => |instr| instr.is_invalid().into(),
+        "isPadding" => |instr| disassembly::is_padding(instr).into(),
+        "length" => |instr| (instr.len() as u64).into(),
+        "name" => |instr| format!("{:?}", instr.mnemonic()).into(),
+        "operands" => |instr| {
+            let mut operands = String::new();
+            let mut fmt = NasmFormatter::new();
+            fmt.format_all_operands(instr, &mut operands);
+            operands.split(',').collect()
+        },
         _ => {
             unreachable!(
                 "attempted to read unexpected property '{property_name}' on type 'DecodedInstruction'"
             )
         }
     };
-    Box::new(contexts.map(func))
+    resolve_with(contexts, Vertex::as_decoded_instruction, value)
 }
 
 pub(super) fn resolve_source_location_property<'a, V: AsVertex<Vertex> + 'a>(
@@ -65,30 +112,306 @@ pub(super) fn resolve_source_location_property<'a, V: AsVertex<Vertex> + 'a>(
     property_name: &str,
     _resolve_info: &ResolveInfo,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
-    let func = match property_name {
-        "column" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::SourceLocation(loc)) => (v.clone(), FieldValue::Uint64(loc.column as u64)),
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
-        },
-        "file" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::SourceLocation(loc)) => (
-                v.clone(),
-                FieldValue::String(Arc::from(loc.file.display().to_string().as_str())),
-            ),
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
+    let value: fn(&super::SourceLocation) -> FieldValue = match property_name {
+        "column" => |loc| (loc.column as u64).into(),
+        "file" => |loc| path_value(&loc.file),
+        "line" => |loc| (loc.line as u64).into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'SourceLocation'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_source_location, value)
+}
+
+pub(super) fn resolve_function_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::Function) -> FieldValue = match property_name {
+        "crate" | "modulePath" | "itemName" => {
+            let adapter = adapter.clone();
+            let property_name = property_name.to_string();
+            return resolve_with(contexts, Vertex::as_function, move |func| {
+                item_path_value(&adapter, &func.name, func.address, &property_name)
+            });
+        }
+        "address" => |func| func.address.into(),
+        "demangledName" => |func| demangled_name(&func.name),
+        "demangledNameNoHash" => |func| demangled_name_no_hash(&func.name),
+        "manglingScheme" => |func| mangling_scheme(&func.name),
+        "name" => |func| func.name.as_str().into(),
+        "requiredCpuFeatures" => |func| {
+            func.required_cpu_features()
+                .into_iter()
+                .map(|x| format!("{:?}", x))
+                .collect()
         },
-        "line" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::SourceLocation(loc)) => (v.clone(), FieldValue::Uint64(loc.line as u64)),
-            None => (v, FieldValue::Null),
-            Some(vertex) => unreachable!("Invalid vertex: {:?}", vertex),
+        "size" => |func| func.size.into(),
+        "typeArguments" => |func| {
+            generics::instantiation(&func.name)
+                .map(|(_, arguments)| arguments)
+                .unwrap_or_default()
+                .into()
         },
+        "x86_64Level" => |func| func.x86_64_level().into(),
         _ => {
             unreachable!(
-                "attempted to read unexpected property '{property_name}' on type 'SourceLocation'"
+                "attempted to read unexpected property '{property_name}' on type 'Function'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_function, value)
+}
+
+pub(super) fn resolve_cpu_feature_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::CpuFeatureUsage) -> FieldValue = match property_name {
+        "instructionCount" => |usage| (usage.instruction_count as u64).into(),
+        "name" => |usage| format!("{:?}", usage.feature).into(),
+        "x86_64Level" => |usage| cpu_features::feature_level(usage.feature).into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'CpuFeature'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_cpu_feature, value)
+}
+
+pub(super) fn resolve_section_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::Section) -> FieldValue = match property_name {
+        "address" => |section| section.address.into(),
+        "fileSize" => |section| (section.data.len() as u64).into(),
+        "name" => |section| section.name.as_str().into(),
+        "size" => |section| section.size.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'Section'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_section, value)
+}
+
+pub(super) fn resolve_byte_range_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::ByteRange) -> FieldValue = match property_name {
+        "address" => |range| range.address().into(),
+        "hex" => |range| to_hex(range.data()).into(),
+        "length" => |range| (range.length as u64).into(),
+        "offset" => |range| (range.offset as u64).into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'ByteRange'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_byte_range, value)
+}
+
+pub(super) fn resolve_crate_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::crates::Crate) -> FieldValue = match property_name {
+        "codeSize" => |krate| krate.code_size.into(),
+        "name" => |krate| krate.name.as_str().into(),
+        _ => {
+            unreachable!("attempted to read unexpected property '{property_name}' on type 'Crate'")
+        }
+    };
+    resolve_with(contexts, Vertex::as_crate, value)
+}
+
+pub(super) fn resolve_data_in_code_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&disassembly::DataInCode) -> FieldValue = match property_name {
+        "address" => |region| region.address.into(),
+        "isPadding" => |region| region.is_padding.into(),
+        "size" => |region| region.size.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'DataInCode'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_data_in_code, value)
+}
+
+pub(super) fn resolve_basic_block_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::cfg::BasicBlock) -> FieldValue = match property_name {
+        "address" => |block| block.address.into(),
+        "size" => |block| block.size.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'BasicBlock'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_basic_block, value)
+}
+
+pub(super) fn resolve_generic_item_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&generics::GenericItem) -> FieldValue = match property_name {
+        "duplicatedSize" => |item| item.duplicated_size().into(),
+        "instantiationCount" => |item| (item.instantiations.len() as u64).into(),
+        "path" => |item| item.path.as_str().into(),
+        "totalSize" => |item| item.total_size.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'GenericItem'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_generic_item, value)
+}
+
+pub(super) fn resolve_jump_table_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::jump_tables::JumpTable) -> FieldValue = match property_name {
+        "address" => |table| table.address.into(),
+        "entryCount" => |table| (table.targets.len() as u64).into(),
+        "entrySize" => |table| (table.entry_size as u64).into(),
+        "isRelative" => |table| table.is_relative.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'JumpTable'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_jump_table, value)
+}
+
+pub(super) fn resolve_size_row_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::size::SizeRow) -> FieldValue = match property_name {
+        "fileSize" => |row| row.file_size.into(),
+        "name" => |row| row.name.as_str().into(),
+        "vmSize" => |row| row.vm_size.into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'SizeRow'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_size_row, value)
+}
+
+pub(super) fn resolve_source_file_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::SourceFile) -> FieldValue = match property_name {
+        "absolutePath" => |file| path_value(&file.absolute_path),
+        "codeBytes" => |file| file.code_bytes.into(),
+        "compDir" => |file| file.comp_dir.as_deref().map(path_value).into(),
+        "crate" => |file| file.crate_name.as_deref().into(),
+        "isDependency" => |file| file.is_dependency.into(),
+        "isStdlib" => |file| file.is_stdlib.into(),
+        "path" => |file| path_value(&file.path),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'SourceFile'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_source_file, value)
+}
+
+pub(super) fn resolve_symbol_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+    adapter: &Adapter,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&super::Symbol) -> FieldValue = match property_name {
+        "crate" | "modulePath" | "itemName" => {
+            let adapter = adapter.clone();
+            let property_name = property_name.to_string();
+            return resolve_with(contexts, Vertex::as_symbol, move |symbol| {
+                item_path_value(&adapter, &symbol.name, symbol.address, &property_name)
+            });
+        }
+        "address" => |symbol| symbol.address.into(),
+        "kind" => |symbol| symbol.kind.as_str().into(),
+        "demangledName" => |symbol| demangled_name(&symbol.name),
+        "demangledNameNoHash" => |symbol| demangled_name_no_hash(&symbol.name),
+        "manglingScheme" => |symbol| mangling_scheme(&symbol.name),
+        "name" => |symbol| symbol.name.as_str().into(),
+        "size" => |symbol| symbol.size.into(),
+        _ => {
+            unreachable!("attempted to read unexpected property '{property_name}' on type 'Symbol'")
+        }
+    };
+    resolve_with(contexts, Vertex::as_symbol, value)
+}
+
+pub(super) fn resolve_data_reference_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::xrefs::DataReference) -> FieldValue = match property_name {
+        "address" => |reference| reference.address.into(),
+        "kind" => |reference| reference.kind.to_string().into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'DataReference'"
+            )
+        }
+    };
+    resolve_with(contexts, Vertex::as_data_reference, value)
+}
+
+pub(super) fn resolve_diagnostic_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let value: fn(&crate::error::Diagnostic) -> FieldValue = match property_name {
+        "address" => |diagnostic| diagnostic.error.address().into(),
+        "kind" => |diagnostic| diagnostic.error.kind().into(),
+        "message" => |diagnostic| diagnostic.error.to_string().into(),
+        "severity" => |diagnostic| diagnostic.severity.to_string().into(),
+        "unitOffset" => |diagnostic| diagnostic.error.unit_offset().into(),
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'Diagnostic'"
             )
         }
     };
-    Box::new(contexts.map(func))
+    resolve_with(contexts, Vertex::as_diagnostic, value)
 }
@@ -1,6 +1,34 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
 use trustfall::provider::check_adapter_invariants;
+use trustfall::FieldValue;
 
 use super::Adapter;
+use crate::disassembly::DisassemblyMode;
+use crate::error::Error;
+
+static TEST_BINARY: OnceLock<Arc<Adapter>> = OnceLock::new();
+
+fn run_query_with(
+    adapter: Arc<Adapter>,
+    query: &str,
+    variables: BTreeMap<Arc<str>, FieldValue>,
+) -> Vec<BTreeMap<Arc<str>, FieldValue>> {
+    adapter.query(query, variables).unwrap().collect()
+}
+
+fn run_query(adapter: Arc<Adapter>, query: &str) -> Vec<BTreeMap<Arc<str>, FieldValue>> {
+    run_query_with(adapter, query, BTreeMap::new())
+}
+
+/// Loads the test executable, this is shared between tests as it's quite large.
+fn load_test_binary() -> Arc<Adapter> {
+    TEST_BINARY
+        .get_or_init(|| Arc::new(Adapter::load(std::env::current_exe().unwrap()).unwrap()))
+        .clone()
+}
 
 #[test]
 fn adapter_satisfies_trustfall_invariants() {
@@ -8,3 +36,849 @@ fn adapter_satisfies_trustfall_invariants() {
     let schema = Adapter::schema();
     check_adapter_invariants(schema, adapter);
 }
+
+#[test]
+fn adapter_is_thread_safe() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Adapter>();
+    assert_send_sync::<super::Vertex>();
+}
+
+#[test]
+fn concurrent_queries() {
+    let adapter = load_test_binary();
+    let query = r#"
+        {
+            functions {
+                name @output
+                address @output
+                size @output
+            }
+        }
+        "#;
+    let expected = run_query(adapter.clone(), query);
+    let handles = (0..4)
+        .map(|_| {
+            let adapter = adapter.clone();
+            thread::spawn(move || run_query(adapter, query))
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+}
+
+#[test]
+fn function_cpu_features() {
+    let adapter = load_test_binary();
+    let binary_level = adapter.x86_64_level();
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            functions {
+                name @output
+                x86_64Level @output
+                requiredCpuFeatures @output
+                instructions @fold @transform(op: "count") @output(name: "instructions")
+            }
+        }
+        "#,
+    );
+    assert!(!results.is_empty());
+    for row in &results {
+        let level = row["x86_64Level"].as_u64().unwrap();
+        assert!((1..=binary_level as u64).contains(&level));
+        if row["instructions"].as_u64().unwrap() > 0 {
+            assert!(!row["requiredCpuFeatures"]
+                .as_vec_with(Some)
+                .unwrap()
+                .is_empty());
+        }
+    }
+}
+
+#[test]
+fn binary_cpu_feature_usage() {
+    let results = run_query(
+        load_test_binary(),
+        r#"
+        {
+            cpuFeatureUsage {
+                name @output
+                x86_64Level @output
+                instructionCount @output
+                functions @fold @transform(op: "count") @output(name: "functions")
+            }
+        }
+        "#,
+    );
+    // Every x86-64 binary needs the baseline 64-bit instructions
+    let x64 = results
+        .iter()
+        .find(|x| x["name"].as_str() == Some("X64"))
+        .unwrap();
+    assert_eq!(x64["x86_64Level"].as_u64(), Some(1));
+    assert!(x64["instructionCount"].as_u64().unwrap() > 0);
+    assert!(x64["functions"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn lazy_instruction_lookup() {
+    let decoded = load_test_binary();
+    let func = decoded
+        .functions()
+        .iter()
+        .find(|x| x.size > 32)
+        .unwrap()
+        .clone();
+    let address = func.address + func.size / 2;
+    let expected = decoded.text_section();
+    let expected = expected
+        .iter()
+        .find(|x| x.ip() <= address && address < x.next_ip())
+        .map(|x| x.ip());
+
+    // A freshly loaded adapter only decodes the function containing the address
+    let adapter = Adapter::load(std::env::current_exe().unwrap()).unwrap();
+    let instr = adapter.find_instruction(address).map(|x| x.ip());
+    assert_eq!(instr, expected);
+}
+
+#[test]
+fn instruction_bytes_are_searchable() {
+    let adapter = load_test_binary();
+    let instr = adapter
+        .text_section()
+        .iter()
+        .find(|x| x.len() > 4)
+        .unwrap()
+        .clone();
+    let query = format!(
+        r#"
+        {{
+            getInstruction(address: {}) {{
+                bytes @output
+            }}
+        }}
+        "#,
+        instr.ip()
+    );
+    let results = run_query(adapter.clone(), &query);
+    let hex = results[0]["bytes"].as_str().unwrap().to_string();
+    assert_eq!(hex.len(), instr.len() * 3 - 1);
+
+    // Wildcard the last byte and we should still find the instruction
+    let pattern = format!("{} ??", &hex[..hex.len() - 3]);
+    let query = format!(
+        r#"
+        {{
+            findBytes(pattern: "{}") {{
+                address @output
+                hex @output
+                section {{
+                    name @output
+                }}
+            }}
+        }}
+        "#,
+        pattern
+    );
+    let results = run_query(adapter, &query);
+    let found = results
+        .iter()
+        .find(|x| x["address"].as_u64() == Some(instr.ip()))
+        .unwrap();
+    assert_eq!(found["hex"].as_str(), Some(hex.as_str()));
+    assert_eq!(found["name"].as_str(), Some(".text"));
+}
+
+#[test]
+fn load_from_bytes_with_remapping() {
+    assert!(Adapter::from_bytes(b"not an object file").is_err());
+
+    let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let adapter = Adapter::builder()
+        .remap_path("src", "remapped")
+        .strict(true)
+        .from_bytes(&data)
+        .unwrap();
+    let results = run_query(
+        Arc::new(adapter),
+        r#"
+        {
+            getFileLocations(file: "remapped/adapter/tests.rs") {
+                line @output
+            }
+        }
+        "#,
+    );
+    assert!(!results.is_empty());
+}
+
+/// An x86-64 ELF header with no sections or segments
+fn empty_elf() -> Vec<u8> {
+    let mut data = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+    data.resize(16, 0);
+    data.extend_from_slice(&2u16.to_le_bytes()); // e_type: executable
+    data.extend_from_slice(&62u16.to_le_bytes()); // e_machine: x86-64
+    data.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    data.resize(52, 0);
+    data.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+    data.resize(64, 0);
+    data
+}
+
+#[test]
+fn missing_debug_info_diagnostic() {
+    let adapter = Arc::new(Adapter::from_bytes(&empty_elf()).unwrap());
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            diagnostics {
+                severity @output
+                kind @output
+                message @output
+                unitOffset @output
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["severity"].as_str(), Some("warning"));
+    assert_eq!(results[0]["kind"].as_str(), Some("MissingDebugInfo"));
+    assert_eq!(results[0]["unitOffset"], FieldValue::Null);
+
+    let strict = Adapter::builder().strict(true).from_bytes(&empty_elf());
+    assert!(matches!(
+        strict,
+        Err(Error::MissingDebugInfo(".debug_info"))
+    ));
+    assert!(matches!(
+        Adapter::builder().bitness(8).from_bytes(&empty_elf()),
+        Err(Error::UnsupportedBitness(8))
+    ));
+
+    let mut aarch64 = empty_elf();
+    aarch64[18..20].copy_from_slice(&183u16.to_le_bytes()); // e_machine: AArch64
+    assert!(matches!(
+        Adapter::from_bytes(&aarch64),
+        Err(Error::UnsupportedArchitecture(x)) if x == "aarch64"
+    ));
+    assert!(Adapter::builder().bitness(64).from_bytes(&aarch64).is_ok());
+}
+
+#[test]
+fn invalid_query_is_an_error() {
+    let adapter = Arc::new(Adapter::from_bytes(&empty_elf()).unwrap());
+    let result = adapter.query("{ notAnEdge { name @output } }", BTreeMap::new());
+    assert!(matches!(result, Err(Error::Query(_))));
+}
+
+#[test]
+fn cached_reload() {
+    let exe = std::env::current_exe().unwrap();
+    let cache_dir = std::env::temp_dir().join(format!("object-trustfall-{}", std::process::id()));
+    let built = Adapter::load_cached(&exe, &cache_dir).unwrap();
+    assert!(built.diagnostics().is_empty());
+    let cache_files = std::fs::read_dir(&cache_dir)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(cache_files.len(), 1);
+
+    let cached = Adapter::load_cached(&exe, &cache_dir).unwrap();
+    assert_eq!(cached.debug_info(), built.debug_info());
+    assert_eq!(cached.symbols(), built.symbols());
+
+    // A corrupt cache is rebuilt
+    std::fs::write(&cache_files[0], b"corrupt").unwrap();
+    let rebuilt = Adapter::load_cached(&exe, &cache_dir).unwrap();
+    assert_eq!(rebuilt.debug_info(), built.debug_info());
+    assert!(std::fs::read(&cache_files[0])
+        .unwrap()
+        .starts_with(crate::cache::MAGIC));
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn diagnostics_not_cached() {
+    let dir = std::env::temp_dir().join(format!("object-trustfall-empty-{}", std::process::id()));
+    let cache_dir = dir.join("cache");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("empty");
+    std::fs::write(&path, empty_elf()).unwrap();
+    for _ in 0..2 {
+        let adapter = Adapter::load_cached(&path, &cache_dir).unwrap();
+        assert_eq!(adapter.diagnostics().len(), 1);
+    }
+    assert!(!cache_dir.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn inline_frames() {
+    let adapter = Adapter::load(std::env::current_exe().unwrap()).unwrap();
+    let function = adapter
+        .find_function("object_trustfall_adapter::adapter::tests::inline_frames")
+        .unwrap();
+    let frames = adapter.frames(function.address);
+    assert_eq!(
+        frames.last().and_then(|x| x.function.as_deref()),
+        Some(function.name.as_str())
+    );
+    assert!(frames[0].file.is_some());
+    // Code removed by the linker is left at zero in the DWARF, which the symbolizer skips
+    assert!(crate::symbolize::Symbolizer::new(&adapter)
+        .frames(0)
+        .is_empty());
+}
+
+#[test]
+fn recursive_disassembly() {
+    let adapter = Arc::new(
+        Adapter::builder()
+            .disassembly(DisassemblyMode::Recursive)
+            .load(std::env::current_exe().unwrap())
+            .unwrap(),
+    );
+    assert!(!adapter.text_section().is_empty());
+    assert!(adapter.text_section().iter().all(|x| !x.is_invalid()));
+    assert!(adapter
+        .text_section()
+        .windows(2)
+        .all(|x| x[0].ip() < x[1].ip()));
+    // Every function symbol is a seed so should have been decoded
+    for func in adapter
+        .functions()
+        .iter()
+        .filter(|x| x.name.contains("recursive_disassembly"))
+    {
+        assert!(!func.instructions().is_empty());
+        assert_eq!(func.instructions()[0].ip(), func.address);
+    }
+
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            dataInCode {
+                address @output
+                size @output
+                isPadding @output
+                bytes {
+                    length @output
+                }
+            }
+        }
+        "#,
+    );
+    assert!(!results.is_empty());
+    for row in &results {
+        assert_eq!(row["size"], row["length"]);
+    }
+}
+
+#[test]
+fn jump_tables_feed_cfg() {
+    let results = run_query(
+        load_test_binary(),
+        r#"
+        {
+            jumpTables {
+                address @output
+                entryCount @output
+                dispatch {
+                    address @tag(name: "dispatch")
+                }
+                targets @fold @transform(op: "count") @output(name: "targets")
+                function {
+                    basicBlocks {
+                        instructions {
+                            address @filter(op: "=", value: ["%dispatch"])
+                        }
+                        successors @fold @transform(op: "count") @output(name: "successors")
+                    }
+                }
+            }
+        }
+        "#,
+    );
+    assert!(!results.is_empty());
+    for row in &results {
+        let targets = row["targets"].as_u64().unwrap();
+        assert!(targets > 0);
+        assert!(targets <= row["entryCount"].as_u64().unwrap());
+        assert_eq!(row["successors"].as_u64(), Some(targets));
+    }
+}
+
+static XREF_TARGET: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn static_cross_references() {
+    XREF_TARGET.fetch_add(1, Ordering::SeqCst);
+    let adapter = load_test_binary();
+    let variables = [(Arc::from("name"), FieldValue::from("XREF_TARGET"))]
+        .into_iter()
+        .collect();
+    let results = run_query_with(
+        adapter.clone(),
+        r#"
+        {
+            symbols {
+                name @filter(op: "regex", value: ["$name"])
+                address @output
+                kind @output
+                referencedBy {
+                    instruction: address @output
+                }
+            }
+        }
+        "#,
+        variables,
+    );
+    assert!(!results.is_empty());
+    assert_eq!(results[0]["kind"].as_str(), Some("data"));
+    let symbol = results[0]["address"].as_u64().unwrap();
+    let instruction = results[0]["instruction"].as_u64().unwrap();
+
+    let query = format!(
+        r#"
+        {{
+            xrefsTo(address: {}) {{
+                kind @output
+                instruction {{
+                    address @output
+                }}
+                symbol {{
+                    name @output
+                }}
+            }}
+        }}
+        "#,
+        symbol
+    );
+    let results = run_query(adapter, &query);
+    let reference = results
+        .iter()
+        .find(|x| x["address"].as_u64() == Some(instruction))
+        .unwrap();
+    assert_eq!(reference["kind"].as_str(), Some("rip-relative"));
+    assert!(reference["name"].as_str().unwrap().contains("XREF_TARGET"));
+}
+
+#[test]
+fn demangled_lookup() {
+    let adapter = load_test_binary();
+    let path = "object_trustfall_adapter::demangle::demangle";
+    let results = run_query(
+        adapter.clone(),
+        r#"
+        {
+            getFunction(name: "object_trustfall_adapter::demangle::demangle") {
+                name @output
+                demangledName @output
+                demangledNameNoHash @output
+                manglingScheme @output
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 1);
+    let row = &results[0];
+    assert_eq!(row["demangledNameNoHash"], FieldValue::from(path));
+    assert_eq!(row["manglingScheme"], FieldValue::from("rust-legacy"));
+    let symbol = adapter.get_symbol(path).unwrap();
+    assert_eq!(row["name"], FieldValue::from(symbol.name.as_str()));
+    let FieldValue::String(demangled) = &row["demangledName"] else {
+        panic!("demangledName should be a string");
+    };
+    assert!(demangled.starts_with(&format!("{}::h", path)));
+
+    let main = adapter.find_function("main").unwrap();
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            getFunction(name: "main") {
+                demangledName @output
+                manglingScheme @output
+            }
+        }
+        "#,
+    );
+    assert_eq!(
+        results[0]["demangledName"],
+        FieldValue::from(main.name.as_str())
+    );
+    assert_eq!(results[0]["manglingScheme"], FieldValue::Null);
+}
+
+#[test]
+fn crate_attribution() {
+    let adapter = load_test_binary();
+    let results = run_query(
+        adapter.clone(),
+        r#"
+        {
+            getFunction(name: "object_trustfall_adapter::crates::parse_path") {
+                crate @output
+                modulePath @output
+                itemName @output
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0]["crate"],
+        FieldValue::from("object_trustfall_adapter")
+    );
+    assert_eq!(
+        results[0]["modulePath"],
+        FieldValue::from("object_trustfall_adapter::crates")
+    );
+    assert_eq!(results[0]["itemName"], FieldValue::from("parse_path"));
+
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            crates {
+                name @output
+                codeSize @output
+                functions @fold {
+                    size @output(name: "sizes")
+                    crate @output(name: "crates")
+                }
+            }
+        }
+        "#,
+    );
+    let names = results
+        .iter()
+        .map(|x| x["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"object_trustfall_adapter"));
+    assert!(names.contains(&"core"));
+    for row in &results {
+        let FieldValue::List(sizes) = &row["sizes"] else {
+            panic!("sizes should be a list");
+        };
+        let total = sizes.iter().map(|x| x.as_u64().unwrap()).sum::<u64>();
+        assert_eq!(row["codeSize"], FieldValue::Uint64(total));
+        let FieldValue::List(crates) = &row["crates"] else {
+            panic!("crates should be a list");
+        };
+        assert!(crates.iter().all(|x| *x == row["name"]));
+    }
+    let sizes = results
+        .iter()
+        .map(|x| x["codeSize"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert!(sizes.windows(2).all(|x| x[0] >= x[1]));
+}
+
+#[test]
+fn generic_instantiations() {
+    let adapter = load_test_binary();
+    let results = run_query(
+        adapter,
+        r#"
+        {
+            genericItems {
+                path @output
+                instantiationCount @output
+                totalSize @output
+                duplicatedSize @output
+                instantiations @fold {
+                    size @output(name: "sizes")
+                }
+            }
+        }
+        "#,
+    );
+    let drop = results
+        .iter()
+        .find(|x| x["path"] == FieldValue::from("core::ptr::drop_in_place"))
+        .expect("drop_in_place should be instantiated");
+    assert!(drop["instantiationCount"].as_u64().unwrap() > 1);
+    for row in &results {
+        let FieldValue::List(sizes) = &row["sizes"] else {
+            panic!("sizes should be a list");
+        };
+        let sizes = sizes
+            .iter()
+            .map(|x| x.as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(row["instantiationCount"].as_u64(), Some(sizes.len() as u64));
+        let total = sizes.iter().sum::<u64>();
+        assert_eq!(row["totalSize"].as_u64(), Some(total));
+        let largest = sizes.iter().max().copied().unwrap_or_default();
+        assert_eq!(row["duplicatedSize"].as_u64(), Some(total - largest));
+    }
+    let duplicated = results
+        .iter()
+        .map(|x| x["duplicatedSize"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert!(duplicated.windows(2).all(|x| x[0] >= x[1]));
+}
+
+#[test]
+fn size_report() {
+    let adapter = load_test_binary();
+    let code_size = adapter.code_sections().iter().map(|x| x.size).sum::<u64>();
+    for group_by in ["file", "compileUnit"] {
+        let results = run_query(
+            adapter.clone(),
+            &format!(
+                r#"
+                {{
+                    sizeReport(groupBy: "{group_by}") {{
+                        name @output
+                        vmSize @output
+                        fileSize @output
+                    }}
+                }}
+                "#
+            ),
+        );
+        assert!(results.len() > 1, "no rows grouping by {group_by}");
+        let total = results
+            .iter()
+            .map(|x| x["vmSize"].as_u64().unwrap())
+            .sum::<u64>();
+        assert_eq!(total, code_size, "grouping by {group_by}");
+        assert!(results.iter().all(|x| x["vmSize"] == x["fileSize"]));
+    }
+
+    let results = run_query(
+        adapter.clone(),
+        r#"
+        {
+            sizeReport(groupBy: "section") {
+                name @output
+                vmSize @output
+                fileSize @output
+            }
+        }
+        "#,
+    );
+    let section = |name: &str| {
+        results
+            .iter()
+            .find(|x| x["name"] == FieldValue::from(name))
+            .unwrap_or_else(|| panic!("missing {name}"))
+    };
+    assert_eq!(section(".bss")["fileSize"].as_u64(), Some(0));
+    assert_eq!(section(".debug_info")["vmSize"].as_u64(), Some(0));
+
+    let variables = [(Arc::from("file"), FieldValue::from("src/size.rs"))]
+        .into_iter()
+        .collect();
+    let results = run_query_with(
+        adapter,
+        r#"
+        {
+            sourceFiles {
+                path @filter(op: "has_suffix", value: ["$file"]) @output
+                codeBytes @output
+                locations @fold @transform(op: "count") @output(name: "locationCount")
+            }
+        }
+        "#,
+        variables,
+    );
+    assert_eq!(results.len(), 1, "size.rs should be in the line tables");
+    assert!(results[0]["codeBytes"].as_u64().unwrap() > 0);
+    assert!(results[0]["locationCount"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn invalid_edge_parameters() {
+    let adapter = Arc::new(Adapter::from_bytes(&empty_elf()).unwrap());
+    let error = |query: &str| match adapter.query(query, BTreeMap::new()) {
+        Err(Error::Query(e)) => e,
+        _ => panic!("expected a query error for {query}"),
+    };
+    assert!(
+        error(r#"{ sizeReport(groupBy: "crate") { name @output } }"#)
+            .contains("unknown size grouping 'crate'")
+    );
+    assert!(error(r#"{ findBytes(pattern: "zz") { address @output } }"#)
+        .contains("invalid byte pattern 'zz'"));
+    assert!(error(r#"{ sections { bytes(offset: -1) { hex @output } } }"#).contains("'offset'"));
+    // Parameters of edges inside a fold are checked as well
+    assert!(
+        error(r#"{ sections { name @output bytes(length: -1) @fold { hex @output } } }"#)
+            .contains("'length'")
+    );
+    // Errors in queries aren't problems with the binary
+    assert!(adapter
+        .diagnostics()
+        .iter()
+        .all(|x| x.error.kind() != "Query"));
+}
+
+#[test]
+fn source_file_paths() {
+    let adapter = load_test_binary();
+    let results = run_query(
+        adapter.clone(),
+        r#"
+        {
+            findSourceFiles(path: "adapter/../../source_paths.rs") {
+                path @output
+                absolutePath @output
+                compDir @output
+                isStdlib @output
+                isDependency @output
+                crate @output
+                locations @fold @transform(op: "count") @output(name: "locationCount")
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 0, "the suffix is normalized before matching");
+
+    let results = run_query(
+        adapter.clone(),
+        r#"
+        {
+            findSourceFiles(path: "src/source_paths.rs") {
+                path @output
+                absolutePath @output
+                compDir @output
+                isStdlib @output
+                isDependency @output
+                crate @output
+                locations @fold @transform(op: "count") @output(name: "locationCount")
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 1);
+    let file = &results[0];
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    assert_eq!(file["path"], FieldValue::from("src/source_paths.rs"));
+    assert_eq!(file["compDir"], FieldValue::from(manifest_dir));
+    assert_eq!(
+        file["absolutePath"],
+        FieldValue::from(format!("{manifest_dir}/src/source_paths.rs").as_str())
+    );
+    assert_eq!(file["isStdlib"], FieldValue::Boolean(false));
+    assert_eq!(file["isDependency"], FieldValue::Boolean(false));
+    assert_eq!(file["crate"], FieldValue::from("object_trustfall_adapter"));
+    assert!(file["locationCount"].as_u64().unwrap() > 0);
+
+    // Every crate has a `src/lib.rs`, relative to its own compile directory
+    let files = adapter.find_source_files("src/lib.rs".as_ref());
+    assert!(files.len() > 1);
+    let absolute = files
+        .iter()
+        .map(|x| &x.absolute_path)
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(absolute.len(), files.len());
+
+    let files = adapter.source_files();
+    assert!(files
+        .iter()
+        .any(|x| x.is_stdlib && x.crate_name.as_deref() == Some("core")));
+    assert!(files.iter().all(|x| !(x.is_stdlib && x.is_dependency)));
+}
+
+#[test]
+fn file_matching_modes() {
+    let adapter = load_test_binary();
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let absolute = format!("{manifest_dir}/src/source_paths.rs");
+    let query = |pattern: &str, mode: &str| {
+        format!(
+            r#"
+            {{
+                getFileLocations(file: "{pattern}", mode: "{mode}") {{
+                    line @output
+                }}
+            }}
+            "#
+        )
+    };
+    let exact = run_query(adapter.clone(), &query("src/source_paths.rs", "exact"));
+    assert!(!exact.is_empty());
+    let by_absolute = run_query(adapter.clone(), &query(&absolute, "exact"));
+    assert_eq!(by_absolute.len(), exact.len());
+    let by_suffix = run_query(adapter.clone(), &query("source_paths.rs", "suffix"));
+    assert_eq!(by_suffix.len(), exact.len());
+    let by_glob = run_query(adapter.clone(), &query("src/source_*.rs", "glob"));
+    assert_eq!(by_glob.len(), exact.len());
+    let by_regex = run_query(adapter.clone(), &query(r"/source_paths\\.rs$", "regex"));
+    assert_eq!(by_regex.len(), exact.len());
+    let error = |query: &str| match adapter.query(query, BTreeMap::new()) {
+        Err(Error::Query(e)) => e,
+        _ => panic!("expected a query error"),
+    };
+    assert!(error(&query("(", "regex")).contains("invalid file pattern '('"));
+    assert!(error(&query("src/*.rs", "fuzzy")).contains("unknown file match 'fuzzy'"));
+
+    let instructions = run_query(
+        adapter.clone(),
+        r#"
+        {
+            getFileInstructions(file: "src/source_paths.rs") {
+                address @output
+            }
+        }
+        "#,
+    );
+    let addresses = instructions
+        .iter()
+        .map(|x| x["address"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert!(!addresses.is_empty());
+    assert!(addresses.windows(2).all(|x| x[0] < x[1]));
+
+    let files = run_query(
+        adapter.clone(),
+        r#"
+        {
+            getFilesMatching(pattern: "src/adapter/*.rs") {
+                absolutePath @output
+            }
+        }
+        "#,
+    );
+    let paths = files
+        .iter()
+        .map(|x| std::path::PathBuf::from(x["absolutePath"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&format!("{manifest_dir}/src/adapter/mod.rs").into()));
+    assert!(paths
+        .iter()
+        .all(|x| x.parent().unwrap().ends_with("src/adapter")));
+}
+
+#[test]
+fn coverage_map() {
+    let adapter = load_test_binary();
+    let map = adapter
+        .coverage_map(&crate::coverage::CoverageOptions::default())
+        .unwrap();
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/coverage.rs");
+    let lines = &map.files[&path];
+    assert!(!lines.is_empty());
+    let debug_info = adapter.debug_info();
+    for line in lines {
+        for address in &line.addresses {
+            let locations = &debug_info[address];
+            assert!(locations
+                .iter()
+                .any(|x| x.line == line.line && path.ends_with(&x.file)));
+        }
+    }
+    assert!(map
+        .files
+        .keys()
+        .all(|x| !crate::source_paths::is_stdlib(x) && !crate::source_paths::is_dependency(x)));
+}
@@ -1,10 +1,32 @@
-use super::SourceLocation;
+use super::{ByteRange, CpuFeatureUsage, Function, Section, SourceFile, SourceLocation, Symbol};
+use crate::cfg::BasicBlock;
+use crate::crates::Crate;
+use crate::disassembly::DataInCode;
+use crate::error::Diagnostic;
+use crate::generics::GenericItem;
+use crate::jump_tables::JumpTable;
+use crate::size::SizeRow;
+use crate::xrefs::DataReference;
 use iced_x86::Instruction;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[non_exhaustive]
 #[derive(Debug, Clone, trustfall::provider::TrustfallEnumVertex)]
 pub enum Vertex {
-    DecodedInstruction(Rc<Instruction>),
-    SourceLocation(Rc<SourceLocation>),
+    BasicBlock(Arc<BasicBlock>),
+    ByteRange(Arc<ByteRange>),
+    CpuFeature(Arc<CpuFeatureUsage>),
+    Crate(Arc<Crate>),
+    DataInCode(Arc<DataInCode>),
+    DataReference(Arc<DataReference>),
+    DecodedInstruction(Arc<Instruction>),
+    Diagnostic(Arc<Diagnostic>),
+    Function(Arc<Function>),
+    GenericItem(Arc<GenericItem>),
+    JumpTable(Arc<JumpTable>),
+    Section(Arc<Section>),
+    SizeRow(Arc<SizeRow>),
+    SourceFile(Arc<SourceFile>),
+    SourceLocation(Arc<SourceLocation>),
+    Symbol(Arc<Symbol>),
 }
@@ -0,0 +1,210 @@
+use clap::Parser;
+use object_trustfall_adapter::adapter::Adapter;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::symbolize::{Frame, Symbolizer};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Convert addresses into file names and line numbers, compatible with GNU addr2line
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Hex addresses to look up, read from stdin one per line if none are given
+    addresses: Vec<String>,
+    /// The object file to use
+    #[arg(short = 'e', long = "exe", default_value = "a.out")]
+    exe: PathBuf,
+    /// Show the address before each result
+    #[arg(short = 'a', long = "addresses")]
+    show_addresses: bool,
+    /// Show function names
+    #[arg(short = 'f', long)]
+    functions: bool,
+    /// Show the functions each address was inlined into
+    #[arg(short = 'i', long)]
+    inlines: bool,
+    /// Demangle function names, the style is accepted for compatibility and ignored as it's
+    /// found from the language of the function
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    demangle: Option<String>,
+    /// Turn off demangling
+    #[arg(long)]
+    no_demangle: bool,
+    /// Print each result on one line
+    #[arg(short = 'p', long)]
+    pretty_print: bool,
+    /// Only show the base of file names
+    #[arg(short = 's', long)]
+    basenames: bool,
+    /// Addresses are offsets from the start of this section
+    #[arg(short = 'j', long, value_name = "NAME")]
+    section: Option<String>,
+}
+
+struct Printer {
+    args: Args,
+    symbolizer: Symbolizer,
+    section_offset: u64,
+    address_width: usize,
+}
+
+fn parse_address(address: &str) -> u64 {
+    // Like GNU addr2line anything after the leading hex digits is ignored
+    let address = address.trim();
+    let hex = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .unwrap_or(address);
+    let end = hex
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(hex.len());
+    u64::from_str_radix(&hex[..end], 16).unwrap_or_default()
+}
+
+impl Printer {
+    fn function_name(&self, frame: &Frame) -> Option<String> {
+        if self.args.demangle.is_some() && !self.args.no_demangle {
+            frame.demangled_function()
+        } else {
+            frame.function.clone()
+        }
+    }
+
+    fn write_location(&self, out: &mut impl Write, frame: &Frame) -> io::Result<()> {
+        let file = match &frame.file {
+            Some(file) if self.args.basenames => file
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Some(file) => file.display().to_string(),
+            None => "??".to_string(),
+        };
+        match frame.line {
+            Some(line) if line != 0 => writeln!(out, "{}:{}", file, line),
+            _ => writeln!(out, "{}:?", file),
+        }
+    }
+
+    fn write_unknown(&self, out: &mut impl Write) -> Result<(), Error> {
+        if self.args.functions {
+            write!(
+                out,
+                "{}",
+                if self.args.pretty_print {
+                    "?? "
+                } else {
+                    "??\n"
+                }
+            )?;
+        }
+        writeln!(out, "??:0")?;
+        Ok(())
+    }
+
+    fn write(&self, out: &mut impl Write, address: u64) -> Result<(), Error> {
+        let args = &self.args;
+        if args.show_addresses {
+            write!(out, "0x{:0width$x}", address, width = self.address_width)?;
+            if args.pretty_print {
+                write!(out, ": ")?;
+            } else {
+                writeln!(out)?;
+            }
+        }
+
+        // Like GNU addr2line an offset address past the end of the address space is unknown
+        let Some(address) = address.checked_add(self.section_offset) else {
+            return self.write_unknown(out);
+        };
+        let mut frames = self.symbolizer.frames(address);
+        let symbol = self.symbolizer.nearest_symbol(address);
+        if frames.is_empty() {
+            let Some(symbol) = symbol else {
+                return self.write_unknown(out);
+            };
+            // Like GNU addr2line fall back to the symbol table for the function and file
+            frames.push(Frame {
+                function: Some(symbol.name.clone()),
+                file: symbol.file.as_ref().map(PathBuf::from),
+                ..Default::default()
+            });
+        }
+        let outermost = frames.len() - 1;
+        if frames[outermost].function.is_none() {
+            frames[outermost].function = symbol.map(|x| x.name.clone());
+        }
+        if !args.inlines {
+            frames.truncate(1);
+        }
+
+        for (i, frame) in frames.iter().enumerate() {
+            if i > 0 && args.pretty_print {
+                write!(out, " (inlined by) ")?;
+            }
+            if args.functions {
+                let name = self
+                    .function_name(frame)
+                    .unwrap_or_else(|| "??".to_string());
+                if args.pretty_print {
+                    write!(out, "{} at ", name)?;
+                } else {
+                    writeln!(out, "{}", name)?;
+                }
+            }
+            self.write_location(out, frame)?;
+        }
+        Ok(())
+    }
+}
+
+fn run(args: Args) -> Result<ExitCode, Error> {
+    let adapter = Adapter::load(&args.exe)?;
+    let section_offset = match &args.section {
+        Some(name) => match adapter.get_section(name) {
+            Some(section) => section.address,
+            None => {
+                eprintln!("object-addr2line: cannot find section {}", name);
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        None => 0,
+    };
+    let printer = Printer {
+        symbolizer: Symbolizer::new(&adapter),
+        section_offset,
+        address_width: if adapter.bitness() == 64 { 16 } else { 8 },
+        args,
+    };
+
+    let mut out = io::stdout().lock();
+    if printer.args.addresses.is_empty() {
+        for line in io::stdin().lock().lines() {
+            printer.write(&mut out, parse_address(&line?))?;
+            // Flush each result so addr2line can be driven interactively through a pipe
+            out.flush()?;
+        }
+    } else {
+        for address in &printer.args.addresses {
+            printer.write(&mut out, parse_address(address))?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("object-addr2line: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
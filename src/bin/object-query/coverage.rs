@@ -0,0 +1,31 @@
+use crate::Loader;
+use object_trustfall_adapter::coverage::{CoverageFormat, CoverageOptions};
+use object_trustfall_adapter::error::Error;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+pub struct CoverageArgs {
+    /// The object file to read the line tables of
+    binary: PathBuf,
+    /// tarpaulin for the JSON of cargo-tarpaulin's trace map or lcov for a tracefile with no
+    /// lines hit
+    #[arg(long, default_value_t = CoverageFormat::Tarpaulin)]
+    coverage_format: CoverageFormat,
+    /// Include the lines of the standard library
+    #[arg(long)]
+    include_stdlib: bool,
+    /// Include the lines of crates downloaded by cargo
+    #[arg(long)]
+    include_dependencies: bool,
+}
+
+pub fn run(args: &CoverageArgs, loader: &Loader) -> Result<(), Error> {
+    let adapter = loader.load(&args.binary)?;
+    let options = CoverageOptions {
+        include_stdlib: args.include_stdlib,
+        include_dependencies: args.include_dependencies,
+    };
+    let map = adapter.coverage_map(&options)?;
+    map.write(args.coverage_format, io::stdout().lock())
+}
@@ -0,0 +1,37 @@
+use crate::{read_query, Loader, Options};
+use object_trustfall_adapter::diff::BinaryDiff;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::query;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    /// The older build
+    #[arg(required_unless_present = "schema")]
+    old: Option<PathBuf>,
+    /// The newer build
+    #[arg(required_unless_present = "schema")]
+    new: Option<PathBuf>,
+    /// File containing the query, read from stdin if this is missing or `-`
+    query: Option<PathBuf>,
+    /// Print the schema for diff queries and exit
+    #[arg(long)]
+    schema: bool,
+}
+
+pub fn run(args: &DiffArgs, loader: &Loader, options: Options) -> Result<(), Error> {
+    if args.schema {
+        print!("{}", BinaryDiff::SCHEMA_TEXT);
+        return Ok(());
+    }
+    let (Some(old), Some(new)) = (&args.old, &args.new) else {
+        unreachable!("clap requires both binaries unless --schema is given");
+    };
+    let query = read_query(args.query.as_deref())?;
+    let diff = BinaryDiff::new(loader.load(old)?, loader.load(new)?);
+    let results = Arc::new(diff).query(&query, options.variables)?;
+    query::write_results(results, options.format, io::stdout().lock())?;
+    Ok(())
+}
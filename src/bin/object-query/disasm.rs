@@ -0,0 +1,30 @@
+use crate::Loader;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::listing::{self, ListingOptions};
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+pub struct DisasmArgs {
+    /// The object file to disassemble
+    binary: PathBuf,
+    /// Only list the function with this name or demangled path, can be given more than once
+    #[arg(long = "function", value_name = "NAME")]
+    functions: Vec<String>,
+    /// Show the source lines each instruction came from like `objdump -S`
+    #[arg(short = 'S', long)]
+    source: bool,
+    /// Don't show the bytes of each instruction
+    #[arg(long)]
+    no_show_raw_insn: bool,
+}
+
+pub fn run(args: &DisasmArgs, loader: &Loader) -> Result<(), Error> {
+    let adapter = loader.load(&args.binary)?;
+    let options = ListingOptions {
+        functions: args.functions.clone(),
+        source: args.source,
+        hide_bytes: args.no_show_raw_insn,
+    };
+    listing::write_listing(&adapter, &options, io::stdout().lock())
+}
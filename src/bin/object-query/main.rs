@@ -0,0 +1,173 @@
+use clap::{Parser, Subcommand};
+use object_trustfall_adapter::adapter::{Adapter, AdapterBuilder};
+use object_trustfall_adapter::disassembly::DisassemblyMode;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::query::{self, OutputFormat};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use trustfall::FieldValue;
+
+mod coverage;
+mod diff;
+mod disasm;
+mod multi;
+mod session;
+
+/// Run Trustfall queries against an object file
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+    /// The object file to query
+    #[arg(required_unless_present_any = ["schema", "repl"])]
+    binary: Option<PathBuf>,
+    /// File containing the query, read from stdin if this is missing or `-`
+    #[arg(conflicts_with = "repl")]
+    query: Option<PathBuf>,
+    /// Query variable as name=value. Values which are valid JSON are used as that, `0x` prefixed
+    /// values are hex integers and anything else is a string
+    #[arg(long = "var", value_name = "NAME=VALUE", global = true)]
+    vars: Vec<String>,
+    /// How to print the results: jsonl, table or csv
+    #[arg(long, default_value_t = OutputFormat::JsonLines, global = true)]
+    format: OutputFormat,
+    /// Print the schema and exit
+    #[arg(long)]
+    schema: bool,
+    /// Start an interactive session, the binary is loaded once and queries are read from the
+    /// terminal
+    #[arg(long)]
+    repl: bool,
+    /// Find instructions by following control flow instead of a linear sweep
+    #[arg(long, global = true)]
+    recursive: bool,
+    /// Directory to cache indices in so later queries on the same binary load faster
+    #[arg(long, value_name = "DIR", global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Replace the prefix FROM of source paths with TO, like rustc's option of the same name. The
+    /// first matching prefix is used when given more than once.
+    #[arg(long, value_name = "FROM=TO", value_parser = parse_remapping, global = true)]
+    remap_path_prefix: Vec<(PathBuf, PathBuf)>,
+}
+
+fn parse_remapping(remapping: &str) -> Result<(PathBuf, PathBuf), String> {
+    remapping
+        .split_once('=')
+        .filter(|(from, _)| !from.is_empty())
+        .map(|(from, to)| (from.into(), to.into()))
+        .ok_or_else(|| format!("invalid remapping '{}', expected FROM=TO", remapping))
+}
+
+#[derive(Debug, Subcommand)]
+enum SubCommand {
+    /// Print a disassembly listing of the binary's functions like `objdump -d`
+    Disasm(disasm::DisasmArgs),
+    /// Query what changed between two builds of a binary, functions and symbols are matched by
+    /// their demangled names without hashes
+    Diff(diff::DiffArgs),
+    /// Query several binaries at once, every root edge of the single binary schema is an edge of
+    /// each binary
+    Multi(multi::MultiArgs),
+    /// Print the lines a coverage tool can set breakpoints on, with the addresses to use
+    Coverage(coverage::CoverageArgs),
+}
+
+/// Loads binaries with the options shared by every subcommand
+pub struct Loader {
+    builder: AdapterBuilder,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Loader {
+    pub fn load(&self, path: &Path) -> Result<Adapter, Error> {
+        match &self.cache_dir {
+            Some(dir) => self.builder.clone().load_cached(path, dir),
+            None => self.builder.clone().load(path),
+        }
+    }
+}
+
+/// How queries are run and their results printed
+pub struct Options {
+    pub variables: BTreeMap<Arc<str>, FieldValue>,
+    pub format: OutputFormat,
+}
+
+/// Reads the query from the file, or from stdin if there's no file or it's `-`
+pub fn read_query(path: Option<&Path>) -> Result<String, Error> {
+    match path {
+        Some(path) if path.as_os_str() != "-" => Ok(fs::read_to_string(path)?),
+        _ => {
+            let mut query = String::new();
+            io::stdin().read_to_string(&mut query)?;
+            Ok(query)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if args.schema {
+        print!("{}", Adapter::SCHEMA_TEXT);
+        return Ok(());
+    }
+    let variables = args
+        .vars
+        .iter()
+        .map(|x| query::parse_variable(x))
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    let options = Options {
+        variables,
+        format: args.format,
+    };
+    let mut builder = AdapterBuilder::new();
+    if args.recursive {
+        builder = builder.disassembly(DisassemblyMode::Recursive);
+    }
+    for (from, to) in &args.remap_path_prefix {
+        builder = builder.remap_path(from, to);
+    }
+    let loader = Loader {
+        builder,
+        cache_dir: args.cache_dir,
+    };
+
+    match &args.command {
+        Some(SubCommand::Disasm(command)) => return disasm::run(command, &loader),
+        Some(SubCommand::Diff(command)) => return diff::run(command, &loader, options),
+        Some(SubCommand::Multi(command)) => return multi::run(command, &loader, options),
+        Some(SubCommand::Coverage(command)) => return coverage::run(command, &loader),
+        None => {}
+    }
+
+    if args.repl {
+        return session::Session::new(args.binary.as_deref(), loader, options)?.run();
+    }
+
+    let Some(binary) = args.binary else {
+        unreachable!("clap requires the binary unless --schema, --repl or a subcommand is given");
+    };
+    let query = read_query(args.query.as_deref())?;
+    let adapter = loader.load(&binary)?;
+    let results = Arc::new(adapter).query(&query, options.variables)?;
+    query::write_results(results, options.format, io::stdout().lock())?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
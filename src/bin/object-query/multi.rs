@@ -0,0 +1,85 @@
+use crate::{read_query, Loader, Options};
+use clap::error::ErrorKind;
+use object_trustfall_adapter::cargo;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::multi::{LoadedBinary, MultiAdapter};
+use object_trustfall_adapter::query;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, clap::Args)]
+pub struct MultiArgs {
+    /// The object files to query
+    #[arg(required_unless_present_any = ["schema", "cargo_messages", "cargo_metadata"])]
+    binaries: Vec<PathBuf>,
+    /// File containing the query, read from stdin if this is missing or `-`. Required, and can't
+    /// be `-`, when cargo's messages are read from stdin.
+    #[arg(long, required_if_eq("cargo_messages", "-"))]
+    query: Option<PathBuf>,
+    /// Also query the executables in the output of `cargo build --message-format=json`,
+    /// read from stdin if this is `-`
+    #[arg(long, value_name = "FILE")]
+    cargo_messages: Option<PathBuf>,
+    /// Also query the binaries and examples of the workspace in the output of
+    /// `cargo metadata --format-version 1`, which have to have been built already
+    #[arg(long, value_name = "FILE")]
+    cargo_metadata: Option<PathBuf>,
+    /// Directory in the target directory the binaries from `--cargo-metadata` are in
+    #[arg(long, value_name = "DIR", default_value = "debug")]
+    cargo_profile: String,
+    /// Print the schema for multi-binary queries and exit
+    #[arg(long)]
+    schema: bool,
+}
+
+pub fn run(args: &MultiArgs, loader: &Loader, options: Options) -> Result<(), Error> {
+    if args.schema {
+        print!("{}", MultiAdapter::SCHEMA_TEXT);
+        return Ok(());
+    }
+    let is_stdin = |path: &Option<PathBuf>| path.as_ref().is_some_and(|x| x.as_os_str() == "-");
+    if is_stdin(&args.cargo_messages) && is_stdin(&args.query) {
+        clap::Error::raw(
+            ErrorKind::ArgumentConflict,
+            "the query can't be read from stdin when `--cargo-messages -` is given\n",
+        )
+        .exit();
+    }
+    let mut artifacts = vec![];
+    if let Some(path) = &args.cargo_messages {
+        if path.as_os_str() == "-" {
+            artifacts.extend(cargo::artifacts_from_messages(io::stdin().lock())?);
+        } else {
+            let file = io::BufReader::new(fs::File::open(path)?);
+            artifacts.extend(cargo::artifacts_from_messages(file)?);
+        }
+    }
+    if let Some(path) = &args.cargo_metadata {
+        let metadata = fs::read_to_string(path)?;
+        artifacts.extend(cargo::artifacts_from_metadata(
+            &metadata,
+            &args.cargo_profile,
+        )?);
+    }
+    let query = read_query(args.query.as_deref())?;
+    let mut loaded = vec![];
+    for path in &args.binaries {
+        loaded.push(LoadedBinary {
+            path: path.clone(),
+            adapter: loader.load(path)?,
+            artifact: None,
+        });
+    }
+    for artifact in artifacts {
+        loaded.push(LoadedBinary {
+            path: artifact.path.clone(),
+            adapter: loader.load(&artifact.path)?,
+            artifact: Some(artifact),
+        });
+    }
+    let results = Arc::new(MultiAdapter::from_binaries(loaded)).query(&query, options.variables)?;
+    query::write_results(results, options.format, io::stdout().lock())?;
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use crate::{Loader, Options};
+use object_trustfall_adapter::adapter::Adapter;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::query::{self, OutputFormat};
+use object_trustfall_adapter::repl::{self, Command, SchemaIndex};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use trustfall::{FieldValue, TransparentValue};
+
+/// Saved in the home directory
+const HISTORY_FILE: &str = ".object_query_history";
+
+/// Completes queries from the schema and paths after `:load`, and keeps reading lines until the
+/// query's braces are closed.
+struct QueryHelper {
+    schema: SchemaIndex,
+    files: FilenameCompleter,
+}
+
+impl Completer for QueryHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if line.trim_start().starts_with(":load ") {
+            return self.files.complete(line, pos, ctx);
+        }
+        let (start, candidates) = self.schema.complete(&line[..pos]);
+        let candidates = candidates
+            .into_iter()
+            .map(|x| Pair {
+                display: x.clone(),
+                replacement: x,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Validator for QueryHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if repl::is_complete(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl Hinter for QueryHelper {
+    type Hint = String;
+}
+
+impl Highlighter for QueryHelper {}
+
+impl Helper for QueryHelper {}
+
+/// State of an interactive session
+pub struct Session {
+    adapter: Option<Arc<Adapter>>,
+    loader: Loader,
+    variables: BTreeMap<Arc<str>, FieldValue>,
+    format: OutputFormat,
+    last_query: Option<String>,
+}
+
+/// Why a command in the session failed
+#[derive(Debug, thiserror::Error)]
+enum CommandError {
+    /// The command can't be used in the session's current state
+    #[error("{0}")]
+    Usage(&'static str),
+    #[error(transparent)]
+    Failed(#[from] Error),
+}
+
+fn readline_error(e: ReadlineError) -> Error {
+    match e {
+        ReadlineError::Io(e) => Error::Io(e),
+        e => Error::Io(io::Error::other(e)),
+    }
+}
+
+impl Session {
+    /// Starts a session with the binary at `path` loaded if there is one
+    pub fn new(path: Option<&Path>, loader: Loader, options: Options) -> Result<Self, Error> {
+        let adapter = match path {
+            Some(path) => Some(Arc::new(loader.load(path)?)),
+            None => None,
+        };
+        Ok(Self {
+            adapter,
+            loader,
+            variables: options.variables,
+            format: options.format,
+            last_query: None,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        let helper = QueryHelper {
+            schema: SchemaIndex::new(Adapter::schema())?,
+            files: FilenameCompleter::new(),
+        };
+        let mut editor = Editor::<QueryHelper, DefaultHistory>::new().map_err(readline_error)?;
+        editor.set_helper(Some(helper));
+        let history = std::env::var_os("HOME").map(|x| Path::new(&x).join(HISTORY_FILE));
+        if let Some(history) = &history {
+            // Missing on the first run
+            let _ = editor.load_history(history);
+        }
+        eprintln!("Type :help for commands, queries run once their braces are closed");
+
+        loop {
+            let mut input = match editor.readline("> ") {
+                Ok(input) => input,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(readline_error(e)),
+            };
+            // The validator only runs on a terminal, when reading from a pipe the rest of the
+            // query has to be read here
+            while !repl::is_complete(&input) {
+                match editor.readline(".. ") {
+                    Ok(line) => {
+                        input.push('\n');
+                        input.push_str(&line);
+                    }
+                    Err(ReadlineError::Eof) => break,
+                    Err(e) => return Err(readline_error(e)),
+                }
+            }
+            if !input.trim().is_empty() {
+                editor
+                    .add_history_entry(input.as_str())
+                    .map_err(readline_error)?;
+            }
+            match Command::parse(&input) {
+                Ok(Command::Quit) => break,
+                Ok(command) => {
+                    if let Err(e) = self.execute(command) {
+                        eprintln!("error: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+
+        if let Some(history) = &history {
+            if let Err(e) = editor.save_history(history) {
+                eprintln!("warning: failed to save history: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: Command) -> Result<(), CommandError> {
+        match command {
+            Command::Empty | Command::Quit => {}
+            Command::Help => println!("{}", repl::HELP),
+            Command::Query(query) => {
+                self.last_query = Some(query.clone());
+                let Some(adapter) = &self.adapter else {
+                    return Err(CommandError::Usage("no binary loaded, use :load PATH"));
+                };
+                let results = adapter.query(&query, self.variables.clone())?;
+                query::write_results(results, self.format, io::stdout().lock())
+                    .map_err(Error::from)?;
+            }
+            Command::Vars(vars) if vars.is_empty() => {
+                for (name, value) in &self.variables {
+                    let value = TransparentValue::from(value.clone());
+                    println!(
+                        "{}={}",
+                        name,
+                        serde_json::to_value(value).unwrap_or_default()
+                    );
+                }
+            }
+            Command::Vars(vars) => self.variables.extend(vars),
+            Command::Unset(names) => {
+                for name in names {
+                    self.variables.remove(name.as_str());
+                }
+            }
+            Command::Explain(query) => {
+                let Some(query) = query.or_else(|| self.last_query.clone()) else {
+                    return Err(CommandError::Usage("no query to explain"));
+                };
+                print!("{}", repl::explain(Adapter::schema(), &query)?);
+            }
+            Command::Load(path) => {
+                let adapter = self.loader.load(&path)?;
+                self.adapter = Some(Arc::new(adapter));
+                eprintln!("loaded {}", path.display());
+            }
+            Command::Format(format) => self.format = format,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use clap::Parser;
+use object_trustfall_adapter::adapter::AdapterBuilder;
+use object_trustfall_adapter::disassembly::DisassemblyMode;
+use object_trustfall_adapter::error::Error;
+use object_trustfall_adapter::server::Server;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+/// Serve Trustfall queries on object files over HTTP
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Object files to load, as NAME=PATH or just PATH to use the file name as the name
+    #[arg(required = true, value_name = "[NAME=]PATH")]
+    binaries: Vec<String>,
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    /// Find instructions by following control flow instead of a linear sweep
+    #[arg(long)]
+    recursive: bool,
+    /// Directory to cache indices in so later loads of the same binaries are faster
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+}
+
+fn parse_binary(binary: &str) -> (String, PathBuf) {
+    match binary.split_once('=') {
+        Some((name, path)) if !name.is_empty() => (name.to_string(), path.into()),
+        _ => {
+            let path = PathBuf::from(binary);
+            let name = Path::new(&path)
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_else(|| binary.to_string());
+            (name, path)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut builder = AdapterBuilder::new();
+    if args.recursive {
+        builder = builder.disassembly(DisassemblyMode::Recursive);
+    }
+    let mut binaries = BTreeMap::new();
+    for binary in &args.binaries {
+        let (name, path) = parse_binary(binary);
+        if binaries.contains_key(&name) {
+            return Err(Error::Query(format!(
+                "more than one binary named '{}', name them with NAME=PATH",
+                name
+            )));
+        }
+        let adapter = match &args.cache_dir {
+            Some(dir) => builder.clone().load_cached(&path, dir)?,
+            None => builder.clone().load(&path)?,
+        };
+        eprintln!("loaded {} as '{}'", path.display(), name);
+        binaries.insert(name, Arc::new(adapter));
+    }
+
+    let server = Server::bind(args.listen.as_str(), binaries)?;
+    if let Some(addr) = server.local_addr() {
+        eprintln!("listening on http://{}", addr);
+    }
+    server.run(|e| eprintln!("failed to send response: {}", e));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
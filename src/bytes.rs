@@ -0,0 +1,193 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Write};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// Cheaply cloneable shared bytes, either owned or a view into a memory mapped file. Slicing
+/// doesn't copy so sections can refer directly to the file contents.
+#[derive(Clone)]
+pub struct Bytes {
+    data: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    range: Range<usize>,
+}
+
+impl Bytes {
+    pub fn new(data: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        let len = data.as_ref().len();
+        Self {
+            data: Arc::new(data),
+            range: 0..len,
+        }
+    }
+
+    /// Get a view of part of the data, returns `None` if the range is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> Option<Self> {
+        if range.start > range.end || range.end > self.len() {
+            return None;
+        }
+        Some(Self {
+            data: self.data.clone(),
+            range: (self.range.start + range.start)..(self.range.start + range.end),
+        })
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &(*self.data).as_ref()[self.range.clone()]
+    }
+}
+
+// SAFETY: the data is behind an `Arc` so it doesn't move when a `Bytes` is moved or cloned, and
+// clones deref to the same slice.
+unsafe impl stable_deref_trait::StableDeref for Bytes {}
+unsafe impl stable_deref_trait::CloneStableDeref for Bytes {}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bytes({} bytes)", self.len())
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Formats bytes as space separated lowercase hex i.e. `48 8b 05`. This is the same format
+/// [`BytePattern`] parses so results can be pasted back into a search.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 3);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            s.push(' ');
+        }
+        let _ = write!(s, "{:02x}", byte);
+    }
+    s
+}
+
+/// A sequence of bytes to search for where any byte can be a wildcard. Written as hex bytes with
+/// `??` (or `?`) for a wildcard e.g. `48 8b ?? ??`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BytePattern {
+    pattern: Vec<Option<u8>>,
+}
+
+impl BytePattern {
+    /// Parses a pattern, returns `None` if it's empty or contains something that isn't a hex byte
+    /// or wildcard. Bytes can be separated by whitespace or written contiguously.
+    pub fn parse(pattern: &str) -> Option<Self> {
+        let mut result = vec![];
+        for token in pattern.split_whitespace() {
+            if token == "?" || token == "??" {
+                result.push(None);
+                continue;
+            }
+            if token.len() % 2 != 0 {
+                return None;
+            }
+            for i in (0..token.len()).step_by(2) {
+                let byte = token.get(i..(i + 2))?;
+                if byte == "??" {
+                    result.push(None);
+                } else if byte.chars().all(|c| c.is_ascii_hexdigit()) {
+                    // from_str_radix alone would also accept a sign e.g. `+8`
+                    result.push(Some(u8::from_str_radix(byte, 16).ok()?));
+                } else {
+                    return None;
+                }
+            }
+        }
+        if result.is_empty() {
+            None
+        } else {
+            Some(Self { pattern: result })
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pattern.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_empty()
+    }
+
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() == self.pattern.len()
+            && self
+                .pattern
+                .iter()
+                .zip(bytes)
+                .all(|(p, b)| p.map(|p| p == *b).unwrap_or(true))
+    }
+
+    /// Returns the offset of every match in the data, matches may overlap.
+    pub fn find_all<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        data.windows(self.len())
+            .enumerate()
+            .filter(|(_, window)| self.matches(window))
+            .map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slicing() {
+        let bytes = Bytes::from(vec![1, 2, 3, 4, 5]);
+        let slice = bytes.slice(1..4).unwrap();
+        assert_eq!(&*slice, &[2, 3, 4]);
+        assert_eq!(&*slice.slice(1..3).unwrap(), &[3, 4]);
+        assert!(slice.slice(2..4).is_none());
+    }
+
+    #[test]
+    fn hex_formatting() {
+        assert_eq!(to_hex(&[]), "");
+        assert_eq!(to_hex(&[0x48, 0x8b, 0x05]), "48 8b 05");
+    }
+
+    #[test]
+    fn pattern_parsing() {
+        let pattern = BytePattern::parse("48 8b ?? ?").unwrap();
+        assert_eq!(pattern.pattern, vec![Some(0x48), Some(0x8b), None, None]);
+        assert_eq!(BytePattern::parse("488B??").unwrap().len(), 3);
+        assert!(BytePattern::parse("").is_none());
+        assert!(BytePattern::parse("48 8").is_none());
+        assert!(BytePattern::parse("zz").is_none());
+        assert!(BytePattern::parse("+8").is_none());
+        assert!(BytePattern::parse("48 +8").is_none());
+    }
+
+    #[test]
+    fn wildcard_search() {
+        let pattern = BytePattern::parse("48 ?? 05").unwrap();
+        let data = [0x48, 0x8b, 0x05, 0x48, 0x00, 0x05, 0x48];
+        assert_eq!(pattern.find_all(&data).collect::<Vec<_>>(), vec![0, 3]);
+    }
+}
@@ -0,0 +1,293 @@
+//! A compact binary cache of the indices which are slow to build for large binaries, so repeated
+//! loads of the same file don't have to read the DWARF or disassemble it again.
+//!
+//! A cache file starts with [`MAGIC`] and [`CACHE_VERSION`], followed by the [`CacheKey`] and then
+//! the cached indices, both encoded with postcard. Addresses are delta encoded and source paths
+//! are interned to keep the file small.
+use crate::adapter::{SourceLocation, Symbol};
+use crate::error::{Error, Result};
+use crate::loader::LoadOptions;
+use object::Object;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const MAGIC: &[u8; 8] = b"OTAINDEX";
+/// Bumped whenever the format of the cached data changes
+pub const CACHE_VERSION: u32 = 4;
+
+/// Identifies the binary and options the cache was built from, a cache is only used if these all
+/// match. The file is hashed as well as having its build ID checked, as a patched or rebuilt
+/// binary can keep its build ID.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CacheKey {
+    pub build_id: Option<Vec<u8>>,
+    /// XXH3 128 bit hash of the file contents
+    pub file_hash: u128,
+    pub file_size: u64,
+    pub options: LoadOptions,
+}
+
+impl CacheKey {
+    pub fn new(data: &[u8], options: &LoadOptions) -> Self {
+        let build_id = object::File::parse(data)
+            .ok()
+            .and_then(|x| x.build_id().ok().flatten().map(|x| x.to_vec()));
+        Self {
+            build_id,
+            file_hash: xxhash_rust::xxh3::xxh3_128(data),
+            file_size: data.len() as u64,
+            options: options.clone(),
+        }
+    }
+
+    /// Name of the cache file in the cache directory
+    pub fn file_name(&self) -> String {
+        let id = match &self.build_id {
+            Some(id) => id.iter().map(|x| format!("{:02x}", x)).collect::<String>(),
+            None => "no-build-id".to_string(),
+        };
+        format!("{}-{:032x}.idx", id, self.file_hash)
+    }
+}
+
+/// The indices stored in the cache.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheData {
+    pub symbols: Vec<Arc<Symbol>>,
+    pub debug_info: BTreeMap<u64, Vec<Arc<SourceLocation>>>,
+    /// Start address of every decoded instruction, only stored when finding them is more work
+    /// than a linear sweep
+    pub instructions: Option<Vec<u64>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EncodedData {
+    symbols: Vec<Symbol>,
+    /// Each file along with the directory of the compile unit it's from
+    files: Vec<(PathBuf, Option<PathBuf>)>,
+    /// Address delta from the previous row, file index, line and column
+    lines: Vec<(u64, u32, u32, u32)>,
+    instructions: Option<Vec<u64>>,
+}
+
+fn delta_encode(addresses: impl IntoIterator<Item = u64>) -> Vec<u64> {
+    let mut last = 0;
+    addresses
+        .into_iter()
+        .map(|x| {
+            let delta = x.wrapping_sub(last);
+            last = x;
+            delta
+        })
+        .collect()
+}
+
+fn delta_decode(deltas: impl IntoIterator<Item = u64>) -> Vec<u64> {
+    let mut last = 0u64;
+    deltas
+        .into_iter()
+        .map(|x| {
+            last = last.wrapping_add(x);
+            last
+        })
+        .collect()
+}
+
+impl From<&CacheData> for EncodedData {
+    fn from(data: &CacheData) -> Self {
+        let mut files = vec![];
+        let mut file_indices = HashMap::new();
+        let mut addresses = vec![];
+        let mut lines = vec![];
+        for (address, locations) in &data.debug_info {
+            for loc in locations {
+                let key = (
+                    loc.file.clone(),
+                    loc.comp_dir.as_deref().map(Path::to_path_buf),
+                );
+                let file = *file_indices.entry(key.clone()).or_insert_with(|| {
+                    files.push(key);
+                    files.len() as u32 - 1
+                });
+                addresses.push(*address);
+                lines.push((file, loc.line as u32, loc.column as u32));
+            }
+        }
+        let lines = delta_encode(addresses)
+            .into_iter()
+            .zip(lines)
+            .map(|(address, (file, line, column))| (address, file, line, column))
+            .collect();
+        Self {
+            symbols: data.symbols.iter().map(|x| Symbol::clone(x)).collect(),
+            files,
+            lines,
+            instructions: data
+                .instructions
+                .as_ref()
+                .map(|x| delta_encode(x.iter().copied())),
+        }
+    }
+}
+
+impl TryFrom<EncodedData> for CacheData {
+    type Error = Error;
+
+    fn try_from(data: EncodedData) -> Result<Self> {
+        let addresses = delta_decode(data.lines.iter().map(|x| x.0));
+        let mut debug_info: BTreeMap<u64, Vec<Arc<SourceLocation>>> = BTreeMap::new();
+        let mut comp_dirs: HashMap<&Path, Arc<Path>> = HashMap::new();
+        for (address, (_, file, line, column)) in addresses.into_iter().zip(data.lines) {
+            let (file, comp_dir) = data
+                .files
+                .get(file as usize)
+                .ok_or_else(|| Error::Cache("file index out of bounds".to_string()))?;
+            let comp_dir = comp_dir
+                .as_deref()
+                .map(|x| comp_dirs.entry(x).or_insert_with(|| Arc::from(x)).clone());
+            debug_info
+                .entry(address)
+                .or_default()
+                .push(Arc::new(SourceLocation {
+                    file: file.clone(),
+                    line: line as usize,
+                    column: column as usize,
+                    comp_dir,
+                }));
+        }
+        Ok(Self {
+            symbols: data.symbols.into_iter().map(Arc::new).collect(),
+            debug_info,
+            instructions: data.instructions.map(delta_decode),
+        })
+    }
+}
+
+fn postcard_error(e: postcard::Error) -> Error {
+    Error::Cache(e.to_string())
+}
+
+/// Encodes the cache file contents
+pub fn encode(key: &CacheKey, data: &CacheData) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    bytes.extend(postcard::to_stdvec(key).map_err(postcard_error)?);
+    bytes.extend(postcard::to_stdvec(&EncodedData::from(data)).map_err(postcard_error)?);
+    Ok(bytes)
+}
+
+/// Decodes a cache file, returning `None` if it's from another version or the key doesn't match.
+pub fn decode(bytes: &[u8], key: &CacheKey) -> Result<Option<CacheData>> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err(Error::Cache("not a cache file".to_string()));
+    };
+    let Some((version, rest)) = rest.split_first_chunk::<4>() else {
+        return Err(Error::Cache("truncated header".to_string()));
+    };
+    if u32::from_le_bytes(*version) != CACHE_VERSION {
+        return Ok(None);
+    }
+    let (cached_key, rest) = postcard::take_from_bytes::<CacheKey>(rest).map_err(postcard_error)?;
+    if cached_key != *key {
+        return Ok(None);
+    }
+    let data = postcard::from_bytes::<EncodedData>(rest).map_err(postcard_error)?;
+    data.try_into().map(Some)
+}
+
+/// Reads the cache for the key from the directory if there's a valid one.
+pub fn read(cache_dir: &Path, key: &CacheKey) -> Option<CacheData> {
+    let bytes = fs::read(cache_dir.join(key.file_name())).ok()?;
+    decode(&bytes, key).ok().flatten()
+}
+
+/// Writes the cache for the key into the directory, creating it if needed. The file is written
+/// under a temporary name and then renamed so other processes never see a partial cache.
+pub fn write(cache_dir: &Path, key: &CacheKey, data: &CacheData) -> Result<()> {
+    fs::create_dir_all(cache_dir)?;
+    let path = cache_dir.join(key.file_name());
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&encode(key, data)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> CacheData {
+        let loc = |file: &str, line| {
+            Arc::new(SourceLocation {
+                file: file.into(),
+                line,
+                column: 1,
+                comp_dir: (line > 1).then(|| Arc::from(Path::new("/src/app"))),
+            })
+        };
+        CacheData {
+            symbols: vec![Arc::new(Symbol {
+                name: "main".to_string(),
+                address: 0x1000,
+                size: 16,
+                kind: "text".to_string(),
+            })],
+            debug_info: BTreeMap::from([
+                (0x1000, vec![loc("src/main.rs", 1), loc("src/lib.rs", 10)]),
+                (0x1004, vec![loc("src/main.rs", 2)]),
+            ]),
+            instructions: Some(vec![0x1000, 0x1004, 0x1008]),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = CacheKey::new(b"binary", &LoadOptions::default());
+        let bytes = encode(&key, &sample()).unwrap();
+        assert_eq!(decode(&bytes, &key).unwrap(), Some(sample()));
+    }
+
+    #[test]
+    fn key_mismatch() {
+        let key = CacheKey::new(b"binary", &LoadOptions::default());
+        let bytes = encode(&key, &sample()).unwrap();
+        let other = CacheKey::new(b"other binary", &LoadOptions::default());
+        assert_eq!(decode(&bytes, &other).unwrap(), None);
+        let options = LoadOptions {
+            include_non_stmt_rows: true,
+            ..Default::default()
+        };
+        let other = CacheKey::new(b"binary", &options);
+        assert_eq!(decode(&bytes, &other).unwrap(), None);
+        assert!(decode(b"garbage", &key).is_err());
+    }
+
+    #[test]
+    fn build_id_keys() {
+        let key = CacheKey {
+            build_id: Some(vec![0xab, 0xcd]),
+            file_hash: 1,
+            file_size: 0x1000,
+            options: LoadOptions::default(),
+        };
+        assert_eq!(key.file_name(), format!("abcd-{:032x}.idx", 1));
+        let bytes = encode(&key, &sample()).unwrap();
+        assert_eq!(decode(&bytes, &key).unwrap(), Some(sample()));
+        // A patched binary keeping its build ID and size has its own cache
+        let patched = CacheKey {
+            file_hash: 2,
+            ..key.clone()
+        };
+        assert_ne!(patched.file_name(), key.file_name());
+        assert_eq!(decode(&bytes, &patched).unwrap(), None);
+
+        let key = CacheKey::new(b"binary", &LoadOptions::default());
+        assert!(key.file_name().starts_with("no-build-id-"));
+    }
+}
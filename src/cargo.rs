@@ -0,0 +1,248 @@
+//! Finding the binaries a cargo build produced, from the JSON messages printed by
+//! `cargo build --message-format=json` (or `cargo test --no-run`) or from `cargo metadata`. Only
+//! output which has already been saved is read, cargo itself is never run.
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env::consts::EXE_SUFFIX;
+use std::io::BufRead;
+use std::path::PathBuf;
+
+/// An executable built by cargo
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Name of the package the target is in
+    pub package: String,
+    /// Name of the target e.g. the binary or integration test
+    pub target_name: String,
+    /// Kind of the target: bin, example, test, bench or lib for the unit tests of a library
+    pub target_kind: String,
+    /// Whether this is a test harness rather than the target's normal executable
+    pub is_test: bool,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    kind: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    test: bool,
+}
+
+/// The fields used from a `compiler-artifact` message
+#[derive(Deserialize)]
+struct ArtifactMessage {
+    package_id: String,
+    target: Target,
+    profile: Profile,
+    executable: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    target_directory: PathBuf,
+}
+
+/// The package name from a package ID in either the format cargo used before 1.77 e.g.
+/// `serde 1.0.0 (registry+https://...)` or the newer package ID spec e.g.
+/// `registry+https://...#serde@1.0.0`. A spec with no name uses the last part of its path.
+pub fn package_name(package_id: &str) -> &str {
+    if let Some((name, _)) = package_id.split_once(' ') {
+        return name;
+    }
+    let (url, fragment) = package_id.rsplit_once('#').unwrap_or((package_id, ""));
+    if let Some((name, _)) = fragment.split_once('@') {
+        return name;
+    }
+    url.trim_end_matches('/').rsplit('/').next().unwrap_or(url)
+}
+
+/// Executables from the JSON messages of a cargo build, one message per line. Messages other
+/// than compiler artifacts, such as compiler diagnostics, and artifacts which aren't executables
+/// are skipped.
+pub fn artifacts_from_messages(reader: impl BufRead) -> Result<Vec<Artifact>> {
+    let mut artifacts = vec![];
+    let mut seen = HashSet::new();
+    for line in reader.lines() {
+        let line = line?;
+        // Cargo passes through anything a build script prints which may not be JSON
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-artifact" {
+            continue;
+        }
+        let message = serde_json::from_value::<ArtifactMessage>(message)
+            .map_err(|e| Error::CargoOutput(e.to_string()))?;
+        let Some(path) = message.executable else {
+            continue;
+        };
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        artifacts.push(Artifact {
+            path,
+            package: package_name(&message.package_id).to_string(),
+            target_kind: message.target.kind.into_iter().next().unwrap_or_default(),
+            target_name: message.target.name,
+            is_test: message.profile.test,
+        });
+    }
+    Ok(artifacts)
+}
+
+/// Executables of the workspace's binary and example targets from the output of
+/// `cargo metadata`, found in the `profile` directory of the target directory e.g. `debug`. Only
+/// the executables which exist are returned. Test binaries have a hash in their name which isn't
+/// in the metadata, so they can only be found from the build messages.
+pub fn artifacts_from_metadata(metadata: &str, profile: &str) -> Result<Vec<Artifact>> {
+    let metadata = serde_json::from_str::<Metadata>(metadata)
+        .map_err(|e| Error::CargoOutput(e.to_string()))?;
+    let profile_dir = metadata.target_directory.join(profile);
+    let mut artifacts = vec![];
+    for package in metadata
+        .packages
+        .iter()
+        .filter(|x| metadata.workspace_members.contains(&x.id))
+    {
+        for target in &package.targets {
+            let Some(kind) = target.kind.first() else {
+                continue;
+            };
+            let dir = match kind.as_str() {
+                "bin" => profile_dir.clone(),
+                "example" => profile_dir.join("examples"),
+                _ => continue,
+            };
+            let path = dir.join(format!("{}{}", target.name, EXE_SUFFIX));
+            if !path.is_file() {
+                continue;
+            }
+            artifacts.push(Artifact {
+                path,
+                package: package.name.clone(),
+                target_name: target.name.clone(),
+                target_kind: kind.clone(),
+                is_test: false,
+            });
+        }
+    }
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_names() {
+        assert_eq!(
+            package_name("hello-world 0.1.0 (path+file:///src/hello-world)"),
+            "hello-world"
+        );
+        assert_eq!(
+            package_name("registry+https://github.com/rust-lang/crates.io-index#serde@1.0.204"),
+            "serde"
+        );
+        assert_eq!(
+            package_name("path+file:///src/hello-world#0.1.0"),
+            "hello-world"
+        );
+        assert_eq!(
+            package_name("path+file:///src/workspace/crates/a#b@0.1.0"),
+            "b"
+        );
+    }
+
+    #[test]
+    fn executables_from_messages() {
+        let messages = r#"{"reason":"compiler-artifact","package_id":"path+file:///src/app#0.1.0","manifest_path":"/src/app/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"app","src_path":"/src/app/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/src/app/target/debug/libapp.rlib"],"executable":null,"fresh":true}
+warning: a build script said something
+{"reason":"compiler-artifact","package_id":"path+file:///src/app#0.1.0","manifest_path":"/src/app/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"app","src_path":"/src/app/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":true},"features":[],"filenames":["/src/app/target/debug/deps/app-0123456789abcdef"],"executable":"/src/app/target/debug/deps/app-0123456789abcdef","fresh":true}
+{"reason":"compiler-artifact","package_id":"path+file:///src/app#0.1.0","manifest_path":"/src/app/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"app-cli","src_path":"/src/app/src/main.rs","edition":"2021","doc":true,"doctest":false,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/src/app/target/debug/app-cli"],"executable":"/src/app/target/debug/app-cli","fresh":false}
+{"reason":"build-finished","success":true}"#;
+        let artifacts = artifacts_from_messages(messages.as_bytes()).unwrap();
+        assert_eq!(
+            artifacts,
+            vec![
+                Artifact {
+                    path: "/src/app/target/debug/deps/app-0123456789abcdef".into(),
+                    package: "app".to_string(),
+                    target_name: "app".to_string(),
+                    target_kind: "lib".to_string(),
+                    is_test: true,
+                },
+                Artifact {
+                    path: "/src/app/target/debug/app-cli".into(),
+                    package: "app".to_string(),
+                    target_name: "app-cli".to_string(),
+                    target_kind: "bin".to_string(),
+                    is_test: false,
+                },
+            ]
+        );
+
+        let invalid = r#"{"reason":"compiler-artifact","target":{}}"#;
+        assert!(matches!(
+            artifacts_from_messages(invalid.as_bytes()),
+            Err(Error::CargoOutput(_))
+        ));
+    }
+
+    #[test]
+    fn executables_from_metadata() {
+        let dir = std::env::temp_dir().join(format!("cargo-metadata-{}", std::process::id()));
+        let debug = dir.join("target/debug");
+        std::fs::create_dir_all(debug.join("examples")).unwrap();
+        std::fs::write(debug.join(format!("app{}", EXE_SUFFIX)), "").unwrap();
+        std::fs::write(debug.join(format!("examples/demo{}", EXE_SUFFIX)), "").unwrap();
+        let metadata = serde_json::json!({
+            "packages": [
+                {
+                    "id": "path+file:///src/app#0.1.0",
+                    "name": "app",
+                    "targets": [
+                        {"kind": ["bin"], "name": "app"},
+                        {"kind": ["bin"], "name": "not-built"},
+                        {"kind": ["example"], "name": "demo"},
+                        {"kind": ["test"], "name": "integration"}
+                    ]
+                },
+                {
+                    "id": "registry+https://github.com/rust-lang/crates.io-index#dep@1.0.0",
+                    "name": "dep",
+                    "targets": [{"kind": ["bin"], "name": "app"}]
+                }
+            ],
+            "workspace_members": ["path+file:///src/app#0.1.0"],
+            "target_directory": dir.join("target")
+        });
+        let artifacts = artifacts_from_metadata(&metadata.to_string(), "debug");
+        std::fs::remove_dir_all(&dir).unwrap();
+        let artifacts = artifacts
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.target_name, x.target_kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            artifacts,
+            vec![
+                ("app".to_string(), "bin".to_string()),
+                ("demo".to_string(), "example".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::jump_tables::JumpTable;
+use iced_x86::{FlowControl, Instruction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A straight line sequence of instructions with a single entry at the start and exit at the end.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BasicBlock {
+    pub address: u64,
+    pub size: u64,
+    pub instructions: Vec<Arc<Instruction>>,
+    /// Start addresses of the blocks control can flow to after this one
+    pub successors: Vec<u64>,
+}
+
+fn ends_block(instr: &Instruction) -> bool {
+    matches!(
+        instr.flow_control(),
+        FlowControl::ConditionalBranch
+            | FlowControl::UnconditionalBranch
+            | FlowControl::IndirectBranch
+            | FlowControl::Return
+            | FlowControl::Exception
+    )
+}
+
+/// Splits the instructions of a function into basic blocks. Direct branch targets within the
+/// bounds start new blocks as do the targets of any jump tables. `instructions` should be sorted
+/// by address.
+pub fn basic_blocks(
+    instructions: &[Arc<Instruction>],
+    bounds: Range<u64>,
+    jump_tables: &[Arc<JumpTable>],
+) -> Vec<BasicBlock> {
+    let Some(first) = instructions.first() else {
+        return vec![];
+    };
+    let mut leaders = BTreeSet::new();
+    leaders.insert(first.ip());
+    for (i, instr) in instructions.iter().enumerate() {
+        if !ends_block(instr) {
+            continue;
+        }
+        let target = instr.near_branch_target();
+        if instr.flow_control() != FlowControl::IndirectBranch && bounds.contains(&target) {
+            leaders.insert(target);
+        }
+        if let Some(next) = instructions.get(i + 1) {
+            leaders.insert(next.ip());
+        }
+    }
+    for table in jump_tables {
+        leaders.extend(table.targets.iter().copied());
+    }
+
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (i, instr) in instructions.iter().enumerate() {
+        let next = instructions.get(i + 1);
+        let falls_through = next.map(|x| x.ip() == instr.next_ip()).unwrap_or(false);
+        let is_end = next
+            .map(|x| leaders.contains(&x.ip()) || !falls_through)
+            .unwrap_or(true);
+        if !is_end {
+            continue;
+        }
+        let mut successors = BTreeSet::new();
+        let target = instr.near_branch_target();
+        match instr.flow_control() {
+            FlowControl::ConditionalBranch => {
+                if bounds.contains(&target) {
+                    successors.insert(target);
+                }
+                if falls_through {
+                    successors.insert(instr.next_ip());
+                }
+            }
+            FlowControl::UnconditionalBranch => {
+                if bounds.contains(&target) {
+                    successors.insert(target);
+                }
+            }
+            FlowControl::IndirectBranch => {
+                for table in jump_tables.iter().filter(|x| x.dispatch.ip() == instr.ip()) {
+                    successors.extend(table.targets.iter().copied());
+                }
+            }
+            FlowControl::Return | FlowControl::Exception => {}
+            _ => {
+                if falls_through {
+                    successors.insert(instr.next_ip());
+                }
+            }
+        }
+        let block = &instructions[start..=i];
+        blocks.push(BasicBlock {
+            address: block[0].ip(),
+            size: instr.next_ip() - block[0].ip(),
+            instructions: block.to_vec(),
+            successors: successors.into_iter().collect(),
+        });
+        start = i + 1;
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_x86::{Decoder, DecoderOptions};
+
+    #[test]
+    fn splits_on_branches() {
+        // 0x1000: test eax, eax
+        // 0x1002: je 0x1006
+        // 0x1004: xor eax, eax
+        // 0x1006: ret
+        let code = [0x85, 0xc0, 0x74, 0x02, 0x31, 0xc0, 0xc3];
+        let instructions = Decoder::with_ip(64, &code, 0x1000, DecoderOptions::NONE)
+            .iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        let blocks = basic_blocks(&instructions, 0x1000..0x1007, &[]);
+        let summary = blocks
+            .iter()
+            .map(|x| (x.address, x.size, x.successors.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0x1000, 4, vec![0x1004, 0x1006]),
+                (0x1004, 2, vec![0x1006]),
+                (0x1006, 1, vec![]),
+            ]
+        );
+    }
+}
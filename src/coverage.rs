@@ -0,0 +1,302 @@
+//! The lines a coverage tool can set breakpoints on, in the same way as cargo-tarpaulin: the first
+//! `is_stmt` address of each line in each line table sequence. The map can be written as the
+//! JSON tarpaulin saves its trace map in, or as an LCOV file with every line not yet hit.
+use crate::adapter::SourceLocation;
+use crate::error::Result;
+use crate::source_paths;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Which files to include in a coverage map, by default only the crates being built and not the
+/// standard library or dependencies
+#[derive(Clone, Debug, Default)]
+pub struct CoverageOptions {
+    pub include_stdlib: bool,
+    pub include_dependencies: bool,
+}
+
+/// A line with code and the addresses to set breakpoints at to see if it runs
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoverableLine {
+    pub line: usize,
+    pub addresses: BTreeSet<u64>,
+}
+
+/// The coverable lines of each source file by absolute path, with the lines in order
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CoverageMap {
+    pub files: BTreeMap<PathBuf, Vec<CoverableLine>>,
+}
+
+/// How to write a coverage map
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CoverageFormat {
+    /// The JSON of cargo-tarpaulin's `TraceMap`
+    #[default]
+    Tarpaulin,
+    /// An LCOV tracefile with no line hit
+    Lcov,
+}
+
+impl FromStr for CoverageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "tarpaulin" => Ok(Self::Tarpaulin),
+            "lcov" => Ok(Self::Lcov),
+            _ => Err(format!(
+                "unknown coverage format '{}', expected tarpaulin or lcov",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CoverageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Tarpaulin => write!(f, "tarpaulin"),
+            Self::Lcov => write!(f, "lcov"),
+        }
+    }
+}
+
+/// A path in the line tables and the directory of the compile unit it's from
+type LineTablePath = (PathBuf, Option<Arc<Path>>);
+
+/// Builds a coverage map from the rows of the line programs, which have to be given a sequence at
+/// a time in address order.
+pub(crate) struct CoverageBuilder {
+    options: CoverageOptions,
+    /// The absolute path of each path in the line tables, `None` if the file is left out
+    paths: HashMap<LineTablePath, Option<Arc<Path>>>,
+    sequence: Option<u64>,
+    /// Lines already seen in the current sequence
+    seen: HashSet<(Arc<Path>, usize)>,
+    lines: BTreeMap<Arc<Path>, BTreeMap<usize, BTreeSet<u64>>>,
+}
+
+impl CoverageBuilder {
+    pub(crate) fn new(options: CoverageOptions) -> Self {
+        Self {
+            options,
+            paths: HashMap::new(),
+            sequence: None,
+            seen: HashSet::new(),
+            lines: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn add_row(&mut self, sequence: u64, address: u64, location: SourceLocation) {
+        // The linker points the sequences of functions it removed at zero
+        if sequence == 0 {
+            return;
+        }
+        if self.sequence != Some(sequence) {
+            self.sequence = Some(sequence);
+            self.seen.clear();
+        }
+        let options = &self.options;
+        let path = self
+            .paths
+            .entry((location.file, location.comp_dir))
+            .or_insert_with_key(|(file, comp_dir)| {
+                let path = source_paths::absolute_path(file, comp_dir.as_deref());
+                let included = (options.include_stdlib || !source_paths::is_stdlib(&path))
+                    && (options.include_dependencies || !source_paths::is_dependency(&path));
+                included.then(|| Arc::from(path))
+            });
+        let Some(path) = path else {
+            return;
+        };
+        if self.seen.insert((path.clone(), location.line)) {
+            self.lines
+                .entry(path.clone())
+                .or_default()
+                .entry(location.line)
+                .or_default()
+                .insert(address);
+        }
+    }
+
+    pub(crate) fn finish(self) -> CoverageMap {
+        let files = self
+            .lines
+            .into_iter()
+            .map(|(path, lines)| {
+                let lines = lines
+                    .into_iter()
+                    .map(|(line, addresses)| CoverableLine { line, addresses })
+                    .collect();
+                (path.to_path_buf(), lines)
+            })
+            .collect();
+        CoverageMap { files }
+    }
+}
+
+/// tarpaulin's `CoverageStat`, a line which hasn't been hit
+#[derive(Serialize)]
+enum CoverageStat {
+    Line(u64),
+}
+
+/// tarpaulin's `Trace`
+#[derive(Serialize)]
+struct Trace<'a> {
+    line: usize,
+    address: &'a BTreeSet<u64>,
+    /// tarpaulin uses one for line breakpoints
+    length: usize,
+    stats: CoverageStat,
+}
+
+#[derive(Serialize)]
+struct TraceMap<'a> {
+    traces: BTreeMap<&'a Path, Vec<Trace<'a>>>,
+}
+
+impl CoverageMap {
+    /// Total number of coverable lines in every file
+    pub fn line_count(&self) -> usize {
+        self.files.values().map(Vec::len).sum()
+    }
+
+    pub fn write(&self, format: CoverageFormat, out: impl Write) -> Result<()> {
+        match format {
+            CoverageFormat::Tarpaulin => self.write_tarpaulin(out),
+            CoverageFormat::Lcov => self.write_lcov(out),
+        }
+    }
+
+    /// Writes the map as the JSON tarpaulin saves its `TraceMap` in, with no line hit
+    pub fn write_tarpaulin(&self, mut out: impl Write) -> Result<()> {
+        let traces = self
+            .files
+            .iter()
+            .map(|(path, lines)| {
+                let traces = lines
+                    .iter()
+                    .map(|x| Trace {
+                        line: x.line,
+                        address: &x.addresses,
+                        length: 1,
+                        stats: CoverageStat::Line(0),
+                    })
+                    .collect();
+                (path.as_path(), traces)
+            })
+            .collect();
+        serde_json::to_writer(&mut out, &TraceMap { traces }).map_err(std::io::Error::from)?;
+        writeln!(out)?;
+        Ok(())
+    }
+
+    /// Writes an LCOV tracefile listing every coverable line with a hit count of zero, which
+    /// coverage tools can merge with the lines that ran so lines which never ran are reported
+    pub fn write_lcov(&self, mut out: impl Write) -> Result<()> {
+        writeln!(out, "TN:")?;
+        for (path, lines) in &self.files {
+            writeln!(out, "SF:{}", path.display())?;
+            for line in lines {
+                writeln!(out, "DA:{},0", line.line)?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(out, "LH:0")?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(file: &str, line: usize) -> SourceLocation {
+        SourceLocation {
+            file: file.into(),
+            line,
+            column: 1,
+            comp_dir: Some(Arc::from(Path::new("/src/app"))),
+        }
+    }
+
+    fn sample(options: CoverageOptions) -> CoverageMap {
+        let std_file =
+            "/rustc/59807616e1fa2540724bfbac14d7976d7e4a3860/library/core/src/fmt/mod.rs";
+        let mut builder = CoverageBuilder::new(options);
+        builder.add_row(0x1000, 0x1000, row("src/main.rs", 1));
+        builder.add_row(0x1000, 0x1004, row("src/main.rs", 2));
+        builder.add_row(0x1000, 0x1008, row(std_file, 10));
+        // Only the first address of a line in a sequence
+        builder.add_row(0x1000, 0x100c, row("src/main.rs", 1));
+        builder.add_row(0x2000, 0x2000, row("./src/main.rs", 1));
+        builder.add_row(0x2000, 0x2004, row("/src/app/src/lib.rs", 3));
+        builder.add_row(0, 0x10, row("src/removed.rs", 1));
+        builder.finish()
+    }
+
+    #[test]
+    fn first_address_per_sequence() {
+        let map = sample(CoverageOptions::default());
+        let lines = |addresses: &[(usize, &[u64])]| {
+            addresses
+                .iter()
+                .map(|(line, addresses)| CoverableLine {
+                    line: *line,
+                    addresses: addresses.iter().copied().collect(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            map.files,
+            BTreeMap::from([
+                ("/src/app/src/lib.rs".into(), lines(&[(3, &[0x2004])])),
+                (
+                    "/src/app/src/main.rs".into(),
+                    lines(&[(1, &[0x1000, 0x2000]), (2, &[0x1004])])
+                ),
+            ])
+        );
+        assert_eq!(map.line_count(), 3);
+
+        let map = sample(CoverageOptions {
+            include_stdlib: true,
+            ..Default::default()
+        });
+        assert_eq!(map.files.len(), 3);
+    }
+
+    #[test]
+    fn output_formats() {
+        let map = sample(CoverageOptions::default());
+        let mut json = vec![];
+        map.write(CoverageFormat::Tarpaulin, &mut json).unwrap();
+        let json = serde_json::from_slice::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            json["traces"]["/src/app/src/main.rs"][0],
+            serde_json::json!({
+                "line": 1,
+                "address": [0x1000, 0x2000],
+                "length": 1,
+                "stats": {"Line": 0}
+            })
+        );
+
+        let mut lcov = vec![];
+        map.write("lcov".parse().unwrap(), &mut lcov).unwrap();
+        assert_eq!(
+            String::from_utf8(lcov).unwrap(),
+            "TN:\n\
+             SF:/src/app/src/lib.rs\nDA:3,0\nLF:1\nLH:0\nend_of_record\n\
+             SF:/src/app/src/main.rs\nDA:1,0\nDA:2,0\nLF:2\nLH:0\nend_of_record\n"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::adapter::Function;
+use iced_x86::{CpuidFeature, Instruction};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// How a single CPU feature is used across the binary.
+#[derive(Clone, Debug)]
+pub struct CpuFeatureUsage {
+    pub feature: CpuidFeature,
+    pub instruction_count: usize,
+    pub functions: Vec<Arc<Function>>,
+}
+
+/// Get the x86-64 microarchitecture level (as defined in the x86-64 psABI) that introduces the
+/// feature. Features which aren't part of any level return `None`.
+pub fn feature_level(feature: CpuidFeature) -> Option<u8> {
+    use CpuidFeature::*;
+    match feature {
+        INTEL8086 | INTEL186 | INTEL286 | INTEL386 | INTEL486 | X64 | CMOV | CX8 | FPU | FPU287
+        | FPU387 | FXSR | MMX | SSE | SSE2 | SYSCALL | CPUID | TSC | MULTIBYTENOP => Some(1),
+        CMPXCHG16B | POPCNT | SSE3 | SSSE3 | SSE4_1 | SSE4_2 => Some(2),
+        AVX | AVX2 | BMI1 | BMI2 | F16C | FMA | LZCNT | MOVBE | XSAVE => Some(3),
+        AVX512F | AVX512BW | AVX512CD | AVX512DQ | AVX512VL => Some(4),
+        _ => None,
+    }
+}
+
+/// Get the CPU features needed to execute the given instructions.
+pub fn required_features<'a>(
+    instructions: impl IntoIterator<Item = &'a Arc<Instruction>>,
+) -> BTreeSet<CpuidFeature> {
+    instructions
+        .into_iter()
+        .flat_map(|x| x.cpuid_features().iter().copied())
+        .collect()
+}
+
+/// Get the minimum x86-64 microarchitecture level needed to run code using the given features.
+/// Everything runs on at least level 1 so this is the lowest value returned.
+pub fn x86_64_level<'a>(features: impl IntoIterator<Item = &'a CpuidFeature>) -> u8 {
+    features
+        .into_iter()
+        .filter_map(|x| feature_level(*x))
+        .max()
+        .unwrap_or(1)
+}
+
+/// Aggregates the CPU feature usage of every instruction in the text section, with the functions
+/// each feature appears in.
+pub fn feature_usage(
+    text_section: &[Arc<Instruction>],
+    functions: &[Arc<Function>],
+) -> Vec<CpuFeatureUsage> {
+    let mut usage: BTreeMap<CpuidFeature, CpuFeatureUsage> = BTreeMap::new();
+    for instr in text_section {
+        for feature in instr.cpuid_features() {
+            usage
+                .entry(*feature)
+                .or_insert_with(|| CpuFeatureUsage {
+                    feature: *feature,
+                    instruction_count: 0,
+                    functions: vec![],
+                })
+                .instruction_count += 1;
+        }
+    }
+    for func in functions {
+        for feature in func.required_cpu_features() {
+            if let Some(entry) = usage.get_mut(&feature) {
+                entry.functions.push(func.clone());
+            }
+        }
+    }
+    usage.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_is_max_of_features() {
+        assert_eq!(x86_64_level(&[]), 1);
+        assert_eq!(x86_64_level(&[CpuidFeature::SSE2, CpuidFeature::X64]), 1);
+        assert_eq!(x86_64_level(&[CpuidFeature::SSE2, CpuidFeature::POPCNT]), 2);
+        assert_eq!(
+            x86_64_level(&[CpuidFeature::AVX2, CpuidFeature::SSE4_2, CpuidFeature::AES]),
+            3
+        );
+        assert_eq!(x86_64_level(&[CpuidFeature::AVX512VL]), 4);
+        // Not part of any level so doesn't raise it
+        assert_eq!(x86_64_level(&[CpuidFeature::AES]), 1);
+    }
+}
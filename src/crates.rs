@@ -0,0 +1,333 @@
+//! Attributes code to the Rust crates it came from, similar to `cargo bloat --crates`. Crates are
+//! found from the demangled paths of symbols, falling back to the DWARF for symbols which aren't
+//! mangled such as `main` or `#[no_mangle]` functions, or whose demangled path doesn't include
+//! the crate such as methods on primitive types.
+use crate::adapter::Function;
+use crate::demangle::{self, ManglingScheme};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Crates whose generic code is usually instantiated in other crates. When a trait from another
+/// crate is implemented for one of their types the code is attributed to the trait's crate.
+const STD_CRATES: [&str; 3] = ["core", "alloc", "std"];
+
+/// Address ranges of DWARF compile units and functions and the crate each is from, sorted by the
+/// start of the range
+pub type UnitCrates = Vec<(Range<u64>, Arc<str>)>;
+
+/// A Rust item path split into its parts e.g. `core::fmt::write` is the item `write` in the module
+/// `core::fmt` of the crate `core`. Generic arguments are left out of the module path and item.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ItemPath {
+    pub crate_name: String,
+    pub module_path: String,
+    pub item_name: String,
+}
+
+/// Code from a crate
+#[derive(Clone, Debug)]
+pub struct Crate {
+    pub name: String,
+    /// Total size of the crate's functions in bytes
+    pub code_size: u64,
+    /// The crate's functions sorted by address
+    pub functions: Vec<Arc<Function>>,
+}
+
+/// Splits a path on `::` outside of any generic arguments, qualified paths or closures. A `::`
+/// before generic arguments (`drop_in_place::<T>` in v0 names) doesn't start a new segment.
+pub(crate) fn split_path(path: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut depth = 0i32;
+    let mut start = 0;
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'<' | b'(' | b'[' | b'{' => depth += 1,
+            // `->` in function pointer types doesn't close anything
+            b'>' if i > 0 && bytes[i - 1] == b'-' => {}
+            b'>' | b')' | b']' | b'}' => depth -= 1,
+            b':' if depth == 0
+                && bytes.get(i + 1) == Some(&b':')
+                && bytes.get(i + 2) != Some(&b'<') =>
+            {
+                segments.push(&path[start..i]);
+                i += 2;
+                start = i;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    segments.push(&path[start..]);
+    segments
+}
+
+/// The segment without any generic arguments e.g. `Vec` for `Vec<u8>` or `drop_in_place::<u8>`
+pub(crate) fn strip_generics(segment: &str) -> &str {
+    match segment.find('<') {
+        Some(i) if i > 0 => segment[..i].trim_end_matches("::"),
+        _ => segment,
+    }
+}
+
+/// Splits a qualified path segment such as `<alloc::vec::Vec<T> as core::clone::Clone>` into the
+/// type and trait.
+fn split_qualified(segment: &str) -> Option<(&str, Option<&str>)> {
+    let inner = segment.strip_prefix('<')?.strip_suffix('>')?;
+    let mut depth = 0i32;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if inner[..i].ends_with('-') => {}
+            '>' | ')' | ']' => depth -= 1,
+            ' ' if depth == 0 && inner[i..].starts_with(" as ") => {
+                return Some((&inner[..i], Some(&inner[i + 4..])));
+            }
+            _ => {}
+        }
+    }
+    Some((inner, None))
+}
+
+/// The path segments of a type with generics removed, or `None` if the type isn't a path such as
+/// a slice, tuple or primitive.
+fn type_path(ty: &str) -> Option<Vec<&str>> {
+    let ty = ["&mut ", "&", "*const ", "*mut ", "dyn "]
+        .iter()
+        .fold(ty, |ty, prefix| ty.strip_prefix(prefix).unwrap_or(ty));
+    let segments = split_path(ty)
+        .into_iter()
+        .map(strip_generics)
+        .collect::<Vec<_>>();
+    let is_path = segments.len() > 1
+        && segments
+            .iter()
+            .all(|x| x.chars().all(|c| c.is_alphanumeric() || c == '_') && !x.is_empty());
+    is_path.then_some(segments)
+}
+
+/// Parses a demangled Rust path without hashes. For trait methods the crate is the one defining
+/// the type, unless the type is from the standard library and the trait isn't, as the code is
+/// then there because of the trait's crate.
+pub fn parse_path(path: &str) -> Option<ItemPath> {
+    let segments = split_path(path);
+    let (item, parents) = segments.split_last()?;
+    let (first, rest) = parents.split_first()?;
+    let base = match split_qualified(first) {
+        Some((ty, trait_name)) => {
+            let ty = type_path(ty);
+            let trait_name = trait_name.and_then(type_path);
+            match (ty, trait_name) {
+                (Some(ty), Some(trait_name))
+                    if STD_CRATES.contains(&ty[0]) && !STD_CRATES.contains(&trait_name[0]) =>
+                {
+                    trait_name
+                }
+                (Some(ty), _) => ty,
+                (None, trait_name) => trait_name?,
+            }
+        }
+        None => vec![strip_generics(first)],
+    };
+    let module = base
+        .into_iter()
+        .chain(rest.iter().map(|x| strip_generics(x)))
+        .collect::<Vec<_>>();
+    Some(ItemPath {
+        crate_name: module[0].to_string(),
+        module_path: module.join("::"),
+        item_name: strip_generics(item).to_string(),
+    })
+}
+
+/// Reads the module path from the start of a Rust v0 mangled path. Paths in impls end at the
+/// module containing the impl, as the type after it isn't parsed. Returns whether the path is
+/// complete along with its segments.
+fn v0_module_path(mangled: &mut &[u8]) -> Option<(bool, Vec<String>)> {
+    fn skip_disambiguator(mangled: &mut &[u8]) -> Option<()> {
+        if let Some(rest) = mangled.strip_prefix(b"s") {
+            let end = rest.iter().position(|x| *x == b'_')?;
+            *mangled = &rest[end + 1..];
+        }
+        Some(())
+    }
+    fn ident(mangled: &mut &[u8]) -> Option<String> {
+        skip_disambiguator(mangled)?;
+        let digits = mangled.iter().take_while(|x| x.is_ascii_digit()).count();
+        let len = std::str::from_utf8(&mangled[..digits]).ok()?.parse().ok()?;
+        let mut rest = &mangled[digits..];
+        if let Some(x) = rest.strip_prefix(b"_") {
+            rest = x;
+        }
+        let ident = std::str::from_utf8(rest.get(..len)?).ok()?.to_string();
+        *mangled = &rest[len..];
+        Some(ident)
+    }
+
+    let (&tag, rest) = mangled.split_first()?;
+    *mangled = rest;
+    match tag {
+        b'C' => Some((true, vec![ident(mangled)?])),
+        b'N' => {
+            let (&namespace, rest) = mangled.split_first()?;
+            *mangled = rest;
+            let (complete, mut path) = v0_module_path(mangled)?;
+            if complete {
+                let name = ident(mangled)?;
+                // Closures and other uppercase namespaces aren't modules
+                if namespace.is_ascii_lowercase() {
+                    path.push(name);
+                }
+            }
+            Some((complete, path))
+        }
+        b'M' | b'X' => {
+            skip_disambiguator(mangled)?;
+            Some((false, v0_module_path(mangled)?.1))
+        }
+        b'I' => Some((false, v0_module_path(mangled)?.1)),
+        _ => None,
+    }
+}
+
+/// The item path of a symbol if it has a Rust mangled name.
+pub fn symbol_path(name: &str) -> Option<ItemPath> {
+    let demangled = demangle::demangle(name)?;
+    match demangled.scheme {
+        ManglingScheme::RustLegacy => parse_path(&demangled.name_no_hash),
+        ManglingScheme::RustV0 => parse_path(&demangled.name_no_hash).or_else(|| {
+            // The demangled path of a method on a primitive type such as `<[u8]>::starts_with`
+            // doesn't include the crate, but the mangled name has the module of the impl
+            let mut mangled = name.trim_start_matches('_').strip_prefix('R')?.as_bytes();
+            let version = mangled.iter().take_while(|x| x.is_ascii_digit()).count();
+            mangled = &mangled[version..];
+            let (_, module) = v0_module_path(&mut mangled)?;
+            let item = split_path(&demangled.name_no_hash).pop()?;
+            Some(ItemPath {
+                crate_name: module.first()?.clone(),
+                module_path: module.join("::"),
+                item_name: strip_generics(item).to_string(),
+            })
+        }),
+        ManglingScheme::Itanium => None,
+    }
+}
+
+/// The crate a Rust compile unit is for from the unit's name, which rustc sets to
+/// `<source file>/@/<crate name>.<hash>-cgu.<n>`. Incremental builds use a hash instead so the
+/// crate isn't known.
+pub fn unit_crate(unit_name: &str) -> Option<&str> {
+    let (_, unit) = unit_name.rsplit_once("/@/")?;
+    let (name, rest) = unit.split_once('.')?;
+    (!name.is_empty() && rest.contains("-cgu.")).then_some(name)
+}
+
+/// Groups the functions by crate, ordered by code size with the largest first.
+pub fn group_by_crate(
+    functions: &[Arc<Function>],
+    crate_of: impl Fn(&Function) -> Option<String>,
+) -> Vec<Crate> {
+    let mut crates: BTreeMap<String, Crate> = BTreeMap::new();
+    for func in functions {
+        let Some(name) = crate_of(func) else {
+            continue;
+        };
+        let krate = crates.entry(name.clone()).or_insert_with(|| Crate {
+            name,
+            code_size: 0,
+            functions: vec![],
+        });
+        krate.code_size += func.size;
+        krate.functions.push(func.clone());
+    }
+    let mut crates = crates.into_values().collect::<Vec<_>>();
+    crates.sort_by(|a, b| b.code_size.cmp(&a.code_size).then(a.name.cmp(&b.name)));
+    crates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(path: &str) -> (String, String, String) {
+        let path = parse_path(path).unwrap();
+        (path.crate_name, path.module_path, path.item_name)
+    }
+
+    fn expected(krate: &str, module: &str, item: &str) -> (String, String, String) {
+        (krate.to_string(), module.to_string(), item.to_string())
+    }
+
+    #[test]
+    fn item_paths() {
+        assert_eq!(
+            parse("core::fmt::write"),
+            expected("core", "core::fmt", "write")
+        );
+        assert_eq!(
+            parse("core::ptr::drop_in_place<mycrate::Foo>"),
+            expected("core", "core::ptr", "drop_in_place")
+        );
+        assert_eq!(
+            parse("alloc::vec::Vec<T,A>::push"),
+            expected("alloc", "alloc::vec::Vec", "push")
+        );
+        assert_eq!(
+            parse("<alloc::vec::Vec<T> as core::clone::Clone>::clone"),
+            expected("alloc", "alloc::vec::Vec", "clone")
+        );
+        assert_eq!(
+            parse("<alloc::string::String as serde::ser::Serialize>::serialize"),
+            expected("serde", "serde::ser::Serialize", "serialize")
+        );
+        assert_eq!(
+            parse("<&mycrate::Foo as core::fmt::Debug>::fmt"),
+            expected("mycrate", "mycrate::Foo", "fmt")
+        );
+        assert_eq!(
+            parse("<[u8] as core::fmt::Debug>::fmt"),
+            expected("core", "core::fmt::Debug", "fmt")
+        );
+        assert_eq!(
+            parse("mycrate::run::{{closure}}"),
+            expected("mycrate", "mycrate::run", "{{closure}}")
+        );
+        assert_eq!(
+            parse("<fn() -> u8 as mycrate::Call>::call"),
+            expected("mycrate", "mycrate::Call", "call")
+        );
+        assert_eq!(
+            parse("core::ptr::drop_in_place::<alloc::string::String>"),
+            expected("core", "core::ptr", "drop_in_place")
+        );
+        assert_eq!(
+            parse("<alloc::collections::btree::node::NodeRef<u64>>::new_internal::<alloc::alloc::Global>"),
+            expected("alloc", "alloc::collections::btree::node::NodeRef", "new_internal")
+        );
+        assert_eq!(parse_path("main"), None);
+    }
+
+    #[test]
+    fn v0_impl_paths() {
+        let path =
+            symbol_path("_RNvMNtCsgEmfK2I1SDS_4core5sliceSh11starts_withCs4X4t9plMPHF_9addr2line")
+                .unwrap();
+        assert_eq!(path.crate_name, "core");
+        assert_eq!(path.module_path, "core::slice");
+        assert_eq!(path.item_name, "starts_with");
+    }
+
+    #[test]
+    fn unit_names() {
+        assert_eq!(
+            unit_crate("src/lib.rs/@/object_trustfall_adapter.5a1f2c3d-cgu.0"),
+            Some("object_trustfall_adapter")
+        );
+        assert_eq!(unit_crate("src/main.rs/@/014w9tp2xgu0plopa0ivov4jd"), None);
+        assert_eq!(unit_crate("main.c"), None);
+    }
+}
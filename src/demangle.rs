@@ -0,0 +1,103 @@
+//! Demangling of Rust (legacy and v0) and Itanium C++ symbol names, so symbols can be shown and
+//! looked up by the paths they have in the source.
+use std::fmt;
+
+/// How a symbol name was mangled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ManglingScheme {
+    /// Rust's original scheme, Itanium-like names ending with a hash e.g. `_ZN4core3fmt5write17h..E`
+    RustLegacy,
+    /// Rust's v0 scheme e.g. `_RNvCs..._4core3fmt5write`
+    RustV0,
+    /// The Itanium C++ ABI scheme used by GCC and Clang
+    Itanium,
+}
+
+impl fmt::Display for ManglingScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RustLegacy => write!(f, "rust-legacy"),
+            Self::RustV0 => write!(f, "rust-v0"),
+            Self::Itanium => write!(f, "itanium"),
+        }
+    }
+}
+
+/// A demangled symbol name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Demangled {
+    pub scheme: ManglingScheme,
+    /// The full demangled name
+    pub name: String,
+    /// The demangled name without the hashes Rust adds to keep names unique e.g. `core::fmt::write`.
+    /// This is the same as `name` for C++.
+    pub name_no_hash: String,
+}
+
+/// Demangles the name, returning `None` if it isn't mangled with a known scheme.
+pub fn demangle(name: &str) -> Option<Demangled> {
+    if let Ok(rust) = rustc_demangle::try_demangle(name) {
+        let full = rust.to_string();
+        let no_hash = format!("{:#}", rust);
+        let is_v0 = ["_R", "R", "__R"].iter().any(|x| name.starts_with(x));
+        // Legacy Rust names always end with a hash, without one this is a C++ name which happens
+        // to be valid as a Rust name too
+        if is_v0 || full != no_hash {
+            return Some(Demangled {
+                scheme: if is_v0 {
+                    ManglingScheme::RustV0
+                } else {
+                    ManglingScheme::RustLegacy
+                },
+                name: full,
+                name_no_hash: no_hash,
+            });
+        }
+    }
+    let symbol = cpp_demangle::Symbol::new(name).ok()?;
+    let name = symbol.demangle(&Default::default()).ok()?;
+    Some(Demangled {
+        scheme: ManglingScheme::Itanium,
+        name_no_hash: name.clone(),
+        name,
+    })
+}
+
+/// Whether the symbol name is `path`, either exactly or once demangled with or without hashes.
+pub fn matches(name: &str, path: &str) -> bool {
+    name == path || demangle(name).is_some_and(|x| x.name == path || x.name_no_hash == path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangles_schemes() {
+        let legacy = demangle("_ZN4core3fmt5write17h1234567890abcdefE").unwrap();
+        assert_eq!(legacy.scheme, ManglingScheme::RustLegacy);
+        assert_eq!(legacy.name, "core::fmt::write::h1234567890abcdef");
+        assert_eq!(legacy.name_no_hash, "core::fmt::write");
+
+        let v0 = demangle("_RNvCs1234_4core5write").unwrap();
+        assert_eq!(v0.scheme, ManglingScheme::RustV0);
+        assert_eq!(v0.name_no_hash, "core::write");
+
+        let cpp = demangle("_ZN3foo3barEi").unwrap();
+        assert_eq!(cpp.scheme, ManglingScheme::Itanium);
+        assert_eq!(cpp.name, "foo::bar(int)");
+        assert_eq!(cpp.name_no_hash, cpp.name);
+
+        assert_eq!(demangle("main"), None);
+    }
+
+    #[test]
+    fn matches_paths() {
+        let name = "_ZN4core3fmt5write17h1234567890abcdefE";
+        assert!(matches(name, name));
+        assert!(matches(name, "core::fmt::write"));
+        assert!(matches(name, "core::fmt::write::h1234567890abcdef"));
+        assert!(!matches(name, "core::fmt"));
+        assert!(matches("main", "main"));
+    }
+}
@@ -0,0 +1,273 @@
+use super::{DiffStatus, FunctionDiff, InstructionDiff, SymbolDiff};
+use crate::adapter::{Adapter, QueryResults};
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use trustfall::{
+    execute_query,
+    provider::{
+        resolve_coercion_using_schema, resolve_neighbors_with, resolve_property_with, AsVertex,
+        ContextIterator, ContextOutcomeIterator, EdgeParameters, ResolveEdgeInfo, ResolveInfo,
+        Typename, VertexIterator,
+    },
+    FieldValue, Schema,
+};
+
+static SCHEMA: OnceLock<Schema> = OnceLock::new();
+
+#[non_exhaustive]
+#[derive(Debug, Clone, trustfall::provider::TrustfallEnumVertex)]
+pub enum DiffVertex {
+    FunctionDiff(Arc<FunctionDiff>),
+    InstructionDiff(Arc<InstructionDiff>),
+    SymbolDiff(Arc<SymbolDiff>),
+}
+
+#[derive(Debug, Default)]
+struct Diffs {
+    functions: OnceLock<Vec<Arc<FunctionDiff>>>,
+    symbols: OnceLock<Vec<Arc<SymbolDiff>>>,
+}
+
+/// Two loaded builds of a binary, queried for what changed between them. Cloning is cheap as the
+/// binaries and diffs are shared between clones.
+#[derive(Clone, Debug)]
+pub struct BinaryDiff {
+    old: Adapter,
+    new: Adapter,
+    diffs: Arc<Diffs>,
+}
+
+impl BinaryDiff {
+    pub const SCHEMA_TEXT: &'static str = include_str!("./schema.graphql");
+
+    pub fn schema() -> &'static Schema {
+        SCHEMA.get_or_init(|| Schema::parse(Self::SCHEMA_TEXT).expect("not a valid schema"))
+    }
+
+    pub fn new(old: Adapter, new: Adapter) -> Self {
+        Self {
+            old,
+            new,
+            diffs: Default::default(),
+        }
+    }
+
+    pub fn load(old: impl AsRef<Path>, new: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(Adapter::load(old)?, Adapter::load(new)?))
+    }
+
+    pub fn old_binary(&self) -> &Adapter {
+        &self.old
+    }
+
+    pub fn new_binary(&self) -> &Adapter {
+        &self.new
+    }
+
+    /// Functions from both binaries, sorted by the size of the change with the largest first
+    pub fn functions(&self) -> &[Arc<FunctionDiff>] {
+        self.diffs.functions.get_or_init(|| {
+            super::diff_functions(&self.old, &self.new)
+                .into_iter()
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    /// Symbols from both binaries, sorted by the size of the change with the largest first
+    pub fn symbols(&self) -> &[Arc<SymbolDiff>] {
+        self.diffs.symbols.get_or_init(|| {
+            super::diff_symbols(self.old.symbols(), self.new.symbols())
+                .into_iter()
+                .map(Arc::new)
+                .collect()
+        })
+    }
+
+    pub fn instruction_diff(&self, diff: &FunctionDiff) -> Vec<InstructionDiff> {
+        super::instruction_diff(&self.old, &self.new, diff)
+    }
+
+    /// Runs a query against the diff, returning an error if the query or its variables are
+    /// invalid rather than panicking.
+    pub fn query(
+        self: &Arc<Self>,
+        query: &str,
+        variables: BTreeMap<Arc<str>, FieldValue>,
+    ) -> Result<QueryResults> {
+        execute_query(Self::schema(), self.clone(), query, variables)
+            .map_err(|e| Error::Query(e.to_string()))
+    }
+
+    fn functions_with(&self, status: DiffStatus) -> VertexIterator<'static, DiffVertex> {
+        let functions = self
+            .functions()
+            .iter()
+            .filter(|x| x.status == status)
+            .cloned()
+            .collect::<Vec<_>>();
+        Box::new(functions.into_iter().map(DiffVertex::FunctionDiff))
+    }
+}
+
+fn string(value: impl ToString) -> FieldValue {
+    FieldValue::String(Arc::from(value.to_string().as_str()))
+}
+
+fn optional<T>(value: Option<T>, f: impl FnOnce(T) -> FieldValue) -> FieldValue {
+    value.map_or(FieldValue::Null, f)
+}
+
+fn function_diff_property(diff: &FunctionDiff, property_name: &str) -> FieldValue {
+    match property_name {
+        "name" => string(&diff.name),
+        "status" => string(diff.status),
+        "oldName" => optional(diff.old.as_ref(), |x| string(&x.name)),
+        "newName" => optional(diff.new.as_ref(), |x| string(&x.name)),
+        "oldAddress" => optional(diff.old.as_ref(), |x| FieldValue::Uint64(x.address)),
+        "newAddress" => optional(diff.new.as_ref(), |x| FieldValue::Uint64(x.address)),
+        "oldSize" => FieldValue::Uint64(diff.old_size()),
+        "newSize" => FieldValue::Uint64(diff.new_size()),
+        "sizeDelta" => FieldValue::Int64(diff.size_delta()),
+        _ => unreachable!(
+            "attempted to read unexpected property '{property_name}' on type 'FunctionDiff'"
+        ),
+    }
+}
+
+fn symbol_diff_property(diff: &SymbolDiff, property_name: &str) -> FieldValue {
+    match property_name {
+        "name" => string(&diff.name),
+        "status" => string(diff.status),
+        "oldName" => optional(diff.old.as_ref(), |x| string(&x.name)),
+        "newName" => optional(diff.new.as_ref(), |x| string(&x.name)),
+        "oldAddress" => optional(diff.old.as_ref(), |x| FieldValue::Uint64(x.address)),
+        "newAddress" => optional(diff.new.as_ref(), |x| FieldValue::Uint64(x.address)),
+        "oldSize" => FieldValue::Uint64(diff.old_size()),
+        "newSize" => FieldValue::Uint64(diff.new_size()),
+        "sizeDelta" => FieldValue::Int64(diff.size_delta()),
+        "kind" => optional(diff.new.as_ref().or(diff.old.as_ref()), |x| string(&x.kind)),
+        _ => unreachable!(
+            "attempted to read unexpected property '{property_name}' on type 'SymbolDiff'"
+        ),
+    }
+}
+
+fn instruction_diff_property(diff: &InstructionDiff, property_name: &str) -> FieldValue {
+    match property_name {
+        "status" => string(diff.status),
+        "oldAddress" => optional(diff.old_address, FieldValue::Uint64),
+        "newAddress" => optional(diff.new_address, FieldValue::Uint64),
+        "text" => string(&diff.text),
+        _ => unreachable!(
+            "attempted to read unexpected property '{property_name}' on type 'InstructionDiff'"
+        ),
+    }
+}
+
+impl<'a> trustfall::provider::Adapter<'a> for BinaryDiff {
+    type Vertex = DiffVertex;
+
+    fn resolve_starting_vertices(
+        &self,
+        edge_name: &Arc<str>,
+        _parameters: &EdgeParameters,
+        _resolve_info: &ResolveInfo,
+    ) -> VertexIterator<'a, Self::Vertex> {
+        match edge_name.as_ref() {
+            "added" => self.functions_with(DiffStatus::Added),
+            "changed" => self.functions_with(DiffStatus::Changed),
+            "functions" => {
+                let functions = self.functions().to_vec();
+                Box::new(functions.into_iter().map(DiffVertex::FunctionDiff))
+            }
+            "removed" => self.functions_with(DiffStatus::Removed),
+            "symbols" => {
+                let symbols = self.symbols().to_vec();
+                Box::new(symbols.into_iter().map(DiffVertex::SymbolDiff))
+            }
+            _ => {
+                unreachable!(
+                    "attempted to resolve starting vertices for unexpected edge name: {edge_name}"
+                )
+            }
+        }
+    }
+
+    fn resolve_property<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        type_name: &Arc<str>,
+        property_name: &Arc<str>,
+        _resolve_info: &ResolveInfo,
+    ) -> ContextOutcomeIterator<'a, V, FieldValue> {
+        if property_name.as_ref() == "__typename" {
+            return resolve_property_with(contexts, |vertex| vertex.typename().into());
+        }
+        let property_name = property_name.clone();
+        match type_name.as_ref() {
+            "FunctionDiff" => resolve_property_with(contexts, move |vertex| match vertex {
+                DiffVertex::FunctionDiff(diff) => function_diff_property(diff, &property_name),
+                vertex => unreachable!("Invalid vertex: {:?}", vertex),
+            }),
+            "InstructionDiff" => resolve_property_with(contexts, move |vertex| match vertex {
+                DiffVertex::InstructionDiff(diff) => {
+                    instruction_diff_property(diff, &property_name)
+                }
+                vertex => unreachable!("Invalid vertex: {:?}", vertex),
+            }),
+            "SymbolDiff" => resolve_property_with(contexts, move |vertex| match vertex {
+                DiffVertex::SymbolDiff(diff) => symbol_diff_property(diff, &property_name),
+                vertex => unreachable!("Invalid vertex: {:?}", vertex),
+            }),
+            _ => {
+                unreachable!(
+                    "attempted to read property '{property_name}' on unexpected type: {type_name}"
+                )
+            }
+        }
+    }
+
+    fn resolve_neighbors<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        type_name: &Arc<str>,
+        edge_name: &Arc<str>,
+        _parameters: &EdgeParameters,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Self::Vertex>> {
+        match (type_name.as_ref(), edge_name.as_ref()) {
+            ("FunctionDiff", "instructions") => {
+                let diff = self.clone();
+                resolve_neighbors_with(contexts, move |vertex| match vertex {
+                    DiffVertex::FunctionDiff(function) => {
+                        let lines = diff.instruction_diff(function);
+                        Box::new(
+                            lines
+                                .into_iter()
+                                .map(|x| DiffVertex::InstructionDiff(x.into())),
+                        )
+                    }
+                    vertex => unreachable!("Invalid vertex: {:?}", vertex),
+                })
+            }
+            _ => {
+                unreachable!(
+                    "attempted to resolve edge '{edge_name}' on unexpected type: {type_name}"
+                )
+            }
+        }
+    }
+
+    fn resolve_coercion<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        _type_name: &Arc<str>,
+        coerce_to_type: &Arc<str>,
+        _resolve_info: &ResolveInfo,
+    ) -> ContextOutcomeIterator<'a, V, bool> {
+        resolve_coercion_using_schema(contexts, Self::schema(), coerce_to_type.as_ref())
+    }
+}
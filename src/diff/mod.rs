@@ -0,0 +1,366 @@
+//! Differences between two builds of a binary, to find out why one is bigger than the other.
+//! Functions and symbols are matched by their demangled names without hashes, since the hashes
+//! Rust adds change whenever a crate's dependencies or compiler flags do.
+use crate::adapter::{Adapter, Function, Symbol};
+use crate::demangle;
+use crate::listing;
+use iced_x86::{Formatter, Instruction, NasmFormatter, OpKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+mod adapter;
+
+#[cfg(test)]
+mod tests;
+
+pub use adapter::{BinaryDiff, DiffVertex};
+
+/// Largest number of instruction pairs compared when diffing a function, past this the
+/// differing instructions are shown as all removed then all added
+const MAX_DIFF_CELLS: usize = 1 << 24;
+
+/// How an item differs between the old and new binary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffStatus {
+    /// Only in the new binary
+    Added,
+    /// Only in the old binary
+    Removed,
+    /// In both binaries but with a different size or different instructions
+    Changed,
+    Unchanged,
+}
+
+impl fmt::Display for DiffStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Removed => write!(f, "removed"),
+            Self::Changed => write!(f, "changed"),
+            Self::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// A function from either or both binaries.
+#[derive(Clone, Debug)]
+pub struct FunctionDiff {
+    /// Demangled name without hashes that the functions were matched by
+    pub name: String,
+    pub old: Option<Arc<Function>>,
+    pub new: Option<Arc<Function>>,
+    pub status: DiffStatus,
+}
+
+impl FunctionDiff {
+    pub fn old_size(&self) -> u64 {
+        self.old.as_ref().map(|x| x.size).unwrap_or_default()
+    }
+
+    pub fn new_size(&self) -> u64 {
+        self.new.as_ref().map(|x| x.size).unwrap_or_default()
+    }
+
+    /// How much bigger the function is in the new binary, negative if it shrank
+    pub fn size_delta(&self) -> i64 {
+        self.new_size() as i64 - self.old_size() as i64
+    }
+}
+
+/// A symbol from either or both binaries. Symbols are changed if their size is.
+#[derive(Clone, Debug)]
+pub struct SymbolDiff {
+    /// Demangled name without hashes that the symbols were matched by
+    pub name: String,
+    pub old: Option<Arc<Symbol>>,
+    pub new: Option<Arc<Symbol>>,
+    pub status: DiffStatus,
+}
+
+impl SymbolDiff {
+    pub fn old_size(&self) -> u64 {
+        self.old.as_ref().map(|x| x.size).unwrap_or_default()
+    }
+
+    pub fn new_size(&self) -> u64 {
+        self.new.as_ref().map(|x| x.size).unwrap_or_default()
+    }
+
+    /// How much bigger the symbol is in the new binary, negative if it shrank
+    pub fn size_delta(&self) -> i64 {
+        self.new_size() as i64 - self.old_size() as i64
+    }
+}
+
+/// A line of the diff between the instructions of a function in the two binaries. Addresses are
+/// left out of the text as they change whenever anything before them does, the targets of
+/// branches and memory operands are shown as the symbol they're in instead.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstructionDiff {
+    /// `Added`, `Removed` or `Unchanged`
+    pub status: DiffStatus,
+    pub old_address: Option<u64>,
+    pub new_address: Option<u64>,
+    pub text: String,
+}
+
+/// The name items are matched by, the demangled name without hashes if it's mangled
+pub fn match_key(name: &str) -> String {
+    demangle::demangle(name)
+        .map(|x| x.name_no_hash)
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Pairs up the items with the same key in address order, items left over once one side runs out
+/// are unmatched.
+fn pair_by_key<T>(
+    old: impl IntoIterator<Item = T>,
+    new: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> String,
+) -> Vec<(String, Option<T>, Option<T>)> {
+    let mut items: BTreeMap<String, (Vec<T>, Vec<T>)> = BTreeMap::new();
+    for item in old {
+        items.entry(key(&item)).or_default().0.push(item);
+    }
+    for item in new {
+        items.entry(key(&item)).or_default().1.push(item);
+    }
+    let mut pairs = vec![];
+    for (key, (old, new)) in items {
+        let mut old = old.into_iter();
+        let mut new = new.into_iter();
+        loop {
+            match (old.next(), new.next()) {
+                (None, None) => break,
+                (old, new) => pairs.push((key.clone(), old, new)),
+            }
+        }
+    }
+    pairs
+}
+
+/// Status of a matched pair from whether both are present and whether they're the same
+fn status<T>(old: &Option<T>, new: &Option<T>, same: impl FnOnce(&T, &T) -> bool) -> DiffStatus {
+    match (old, new) {
+        (Some(old), Some(new)) if same(old, new) => DiffStatus::Unchanged,
+        (Some(_), Some(_)) => DiffStatus::Changed,
+        (Some(_), None) => DiffStatus::Removed,
+        _ => DiffStatus::Added,
+    }
+}
+
+/// Sorts by the size of the change, largest first
+fn by_delta<T>(items: &mut [T], delta: impl Fn(&T) -> i64, name: impl Fn(&T) -> &str) {
+    items.sort_by(|a, b| {
+        delta(b)
+            .unsigned_abs()
+            .cmp(&delta(a).unsigned_abs())
+            .then_with(|| name(a).cmp(name(b)))
+    });
+}
+
+/// Matches up the symbols of the two binaries, sorted by the size of the change.
+pub fn diff_symbols(old: &[Arc<Symbol>], new: &[Arc<Symbol>]) -> Vec<SymbolDiff> {
+    let named = |symbols: &[Arc<Symbol>]| {
+        symbols
+            .iter()
+            .filter(|x| !x.name.is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let mut diffs = pair_by_key(named(old), named(new), |x| match_key(&x.name))
+        .into_iter()
+        .map(|(name, old, new)| SymbolDiff {
+            status: status(&old, &new, |old, new| old.size == new.size),
+            name,
+            old,
+            new,
+        })
+        .collect::<Vec<_>>();
+    by_delta(&mut diffs, SymbolDiff::size_delta, |x| &x.name);
+    diffs
+}
+
+/// Matches up the functions of the two binaries, sorted by the size of the change. Functions
+/// with the same size are compared byte for byte, then by their instructions if the bytes differ
+/// so code which only moved isn't changed.
+pub fn diff_functions(old: &Adapter, new: &Adapter) -> Vec<FunctionDiff> {
+    let pairs = pair_by_key(old.functions().to_vec(), new.functions().to_vec(), |x| {
+        match_key(&x.name)
+    });
+    let mut diffs = pairs
+        .into_iter()
+        .map(|(name, old_func, new_func)| FunctionDiff {
+            status: status(&old_func, &new_func, |a, b| {
+                a.size == b.size
+                    && (same_code(old, a, new, b)
+                        || normalized_instructions(old, a) == normalized_instructions(new, b))
+            }),
+            name,
+            old: old_func,
+            new: new_func,
+        })
+        .collect::<Vec<_>>();
+    by_delta(&mut diffs, FunctionDiff::size_delta, |x| &x.name);
+    diffs
+}
+
+/// Whether the functions have the same bytes, so they only differ in where they are
+fn same_code(old: &Adapter, old_func: &Function, new: &Adapter, new_func: &Function) -> bool {
+    let (Some(old_section), Some(new_section)) = (
+        old.find_section(old_func.address),
+        new.find_section(new_func.address),
+    ) else {
+        return false;
+    };
+    old_section
+        .data_at(old_func.address, old_func.size as usize)
+        .is_some_and(|x| Some(x) == new_section.data_at(new_func.address, new_func.size as usize))
+}
+
+/// The symbol an address is in, relative to the start of `func` if it's inside it
+fn symbolic_target(adapter: &Adapter, func: &Function, address: u64) -> String {
+    if func.contains(address) {
+        return format!("<.+0x{:x}>", address - func.address);
+    }
+    match adapter.find_symbol_containing(address) {
+        Some(symbol) if symbol.address == address => format!("<{}>", match_key(&symbol.name)),
+        Some(symbol) => format!(
+            "<{}+0x{:x}>",
+            match_key(&symbol.name),
+            address - symbol.address
+        ),
+        None => "<?>".to_string(),
+    }
+}
+
+/// The function's instructions as text which doesn't depend on where the function or what it
+/// refers to are in the binary
+pub fn normalized_instructions(adapter: &Adapter, func: &Function) -> Vec<String> {
+    let mut formatter = NasmFormatter::new();
+    func.instructions()
+        .iter()
+        .map(|instr| {
+            let target = listing::target(instr, adapter);
+            let mut copy: Instruction = **instr;
+            if target.is_some() {
+                if matches!(
+                    copy.op0_kind(),
+                    OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64
+                ) {
+                    copy.set_near_branch64(0);
+                } else {
+                    copy.set_memory_displacement64(0);
+                }
+            }
+            let mut text = String::new();
+            formatter.format(&copy, &mut text);
+            if let Some(target) = target {
+                text.push(' ');
+                text.push_str(&symbolic_target(adapter, func, target));
+            }
+            text
+        })
+        .collect()
+}
+
+/// Indices of the lines in a longest common subsequence of `old` and `new`
+fn common_lines(old: &[String], new: &[String]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let mut common = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if n > 0 && m > 0 && (n + 1) * (m + 1) <= MAX_DIFF_CELLS {
+        // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        let at = |i: usize, j: usize| i * (m + 1) + j;
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[at(i, j)] = if old_middle[i] == new_middle[j] {
+                    lengths[at(i + 1, j + 1)] + 1
+                } else {
+                    lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_middle[i] == new_middle[j] {
+                common.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    common.extend((0..suffix).map(|i| (old.len() - suffix + i, new.len() - suffix + i)));
+    common
+}
+
+/// Diffs two lists of lines, calling `line` with the status and indices of each line of the diff.
+/// Removed lines come before the added lines which replace them.
+fn diff_lines(
+    old: &[String],
+    new: &[String],
+    mut line: impl FnMut(DiffStatus, Option<usize>, Option<usize>),
+) {
+    let (mut i, mut j) = (0, 0);
+    for (old_index, new_index) in common_lines(old, new) {
+        for i in i..old_index {
+            line(DiffStatus::Removed, Some(i), None);
+        }
+        for j in j..new_index {
+            line(DiffStatus::Added, None, Some(j));
+        }
+        line(DiffStatus::Unchanged, Some(old_index), Some(new_index));
+        (i, j) = (old_index + 1, new_index + 1);
+    }
+    for i in i..old.len() {
+        line(DiffStatus::Removed, Some(i), None);
+    }
+    for j in j..new.len() {
+        line(DiffStatus::Added, None, Some(j));
+    }
+}
+
+/// The instruction level diff of a function, with every instruction from both binaries. Added
+/// and removed functions have all their instructions added or removed.
+pub fn instruction_diff(old: &Adapter, new: &Adapter, diff: &FunctionDiff) -> Vec<InstructionDiff> {
+    let side = |adapter: &Adapter, func: &Option<Arc<Function>>| match func {
+        Some(func) => (
+            func.instructions().iter().map(|x| x.ip()).collect(),
+            normalized_instructions(adapter, func),
+        ),
+        None => (vec![], vec![]),
+    };
+    let (old_addresses, old_text) = side(old, &diff.old);
+    let (new_addresses, new_text) = side(new, &diff.new);
+    let mut lines = vec![];
+    diff_lines(&old_text, &new_text, |status, i, j| {
+        let text = match (i, j) {
+            (Some(i), _) => &old_text[i],
+            (_, Some(j)) => &new_text[j],
+            _ => unreachable!("every line is from at least one side"),
+        };
+        lines.push(InstructionDiff {
+            status,
+            old_address: i.map(|i| old_addresses[i]),
+            new_address: j.map(|j| new_addresses[j]),
+            text: text.clone(),
+        });
+    });
+    lines
+}
@@ -0,0 +1,93 @@
+use super::*;
+
+fn lines(text: &str) -> Vec<String> {
+    text.split_whitespace().map(|x| x.to_string()).collect()
+}
+
+fn diff(old: &str, new: &str) -> Vec<(DiffStatus, Option<usize>, Option<usize>)> {
+    let mut diff = vec![];
+    diff_lines(&lines(old), &lines(new), |status, i, j| {
+        diff.push((status, i, j))
+    });
+    diff
+}
+
+#[test]
+fn matches_by_name_without_hash() {
+    assert_eq!(
+        match_key("_ZN4core3fmt5write17h0123456789abcdefE"),
+        "core::fmt::write"
+    );
+    assert_eq!(
+        match_key("_ZN4core3fmt5write17hfedcba9876543210E"),
+        "core::fmt::write"
+    );
+    assert_eq!(match_key("main"), "main");
+
+    let symbol = |name: &str, address, size| {
+        Arc::new(Symbol {
+            name: name.to_string(),
+            address,
+            size,
+            kind: "text".to_string(),
+        })
+    };
+    let old = [
+        symbol("_ZN4core3fmt5write17h0123456789abcdefE", 0x1000, 0x40),
+        symbol("removed", 0x1040, 0x10),
+        symbol("same", 0x1050, 0x8),
+    ];
+    let new = [
+        symbol("_ZN4core3fmt5write17hfedcba9876543210E", 0x2000, 0x60),
+        symbol("added", 0x2060, 0x100),
+        symbol("same", 0x2160, 0x8),
+    ];
+    let diffs = diff_symbols(&old, &new)
+        .into_iter()
+        .map(|x| (x.name.clone(), x.status, x.size_delta()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        diffs,
+        vec![
+            ("added".to_string(), DiffStatus::Added, 0x100),
+            ("core::fmt::write".to_string(), DiffStatus::Changed, 0x20),
+            ("removed".to_string(), DiffStatus::Removed, -0x10),
+            ("same".to_string(), DiffStatus::Unchanged, 0),
+        ]
+    );
+}
+
+#[test]
+fn diffs_lines() {
+    use DiffStatus::*;
+    assert_eq!(
+        diff("a b c d", "a x c d e"),
+        vec![
+            (Unchanged, Some(0), Some(0)),
+            (Removed, Some(1), None),
+            (Added, None, Some(1)),
+            (Unchanged, Some(2), Some(2)),
+            (Unchanged, Some(3), Some(3)),
+            (Added, None, Some(4)),
+        ]
+    );
+    assert_eq!(
+        diff("a b", ""),
+        vec![(Removed, Some(0), None), (Removed, Some(1), None)]
+    );
+    assert_eq!(
+        diff("a b c", "c a b"),
+        vec![
+            (Added, None, Some(0)),
+            (Unchanged, Some(0), Some(1)),
+            (Unchanged, Some(1), Some(2)),
+            (Removed, Some(2), None),
+        ]
+    );
+}
+
+#[test]
+fn adapter_satisfies_trustfall_invariants() {
+    let diff = BinaryDiff::new(Adapter::new(), Adapter::new());
+    trustfall::provider::check_adapter_invariants(BinaryDiff::schema(), diff);
+}
@@ -0,0 +1,225 @@
+use iced_x86::{Decoder, DecoderOptions, FlowControl, Instruction, Mnemonic};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// How instructions are found in the text section.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum DisassemblyMode {
+    /// Decode every byte of the section in order. Fast and complete, but anything in the section
+    /// which isn't code will be decoded as (often invalid) instructions.
+    #[default]
+    Linear,
+    /// Start at known code addresses (symbols, the entry point and DWARF functions) and follow
+    /// control flow from there. Anything not reached is treated as data.
+    Recursive,
+}
+
+/// A region of the text section which doesn't contain code reachable by the disassembler. Either
+/// padding between functions or data embedded in the code such as jump tables and constants.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataInCode {
+    pub address: u64,
+    pub size: u64,
+    pub is_padding: bool,
+}
+
+/// Padding is either a nop (of any length) or an `int3` which compilers use to fill the space
+/// between functions.
+pub fn is_padding(instr: &Instruction) -> bool {
+    matches!(instr.mnemonic(), Mnemonic::Nop | Mnemonic::Int3)
+}
+
+fn is_padding_byte(byte: u8) -> bool {
+    matches!(byte, 0x00 | 0x90 | 0xcc)
+}
+
+/// Decodes every instruction in the section one after another.
+pub fn linear_sweep(bitness: u32, bytes: &[u8], address: u64) -> Vec<Arc<Instruction>> {
+    let mut decoder = Decoder::with_ip(bitness, bytes, address, DecoderOptions::NONE);
+    decoder.iter().map(Arc::new).collect()
+}
+
+/// Decodes a single instruction at each of the addresses, addresses outside the section are
+/// skipped.
+pub fn decode_at(
+    bitness: u32,
+    bytes: &[u8],
+    address: u64,
+    addresses: &[u64],
+) -> Vec<Arc<Instruction>> {
+    let mut decoder = Decoder::with_ip(bitness, bytes, address, DecoderOptions::NONE);
+    let mut instructions = Vec::with_capacity(addresses.len());
+    for ip in addresses.iter().copied().filter(|x| *x >= address) {
+        if decoder.set_position((ip - address) as usize).is_err() {
+            continue;
+        }
+        decoder.set_ip(ip);
+        instructions.push(Arc::new(decoder.decode()));
+    }
+    instructions
+}
+
+/// Decodes the instructions reachable from the seed addresses. Direct branch and call targets
+/// within the section are followed, indirect ones end the current path. Seeds outside the section
+/// are ignored. The result is sorted by address.
+pub fn recursive_descent(
+    bitness: u32,
+    bytes: &[u8],
+    address: u64,
+    seeds: impl IntoIterator<Item = u64>,
+) -> Vec<Arc<Instruction>> {
+    let mut instructions = BTreeMap::new();
+    extend_recursive_descent(bitness, bytes, address, seeds, &mut instructions);
+    instructions.into_values().collect()
+}
+
+/// Continues a recursive descent from new seeds, adding any newly reached instructions to the
+/// already decoded ones. Code already decoded isn't revisited. Returns the addresses reached which
+/// couldn't be decoded.
+pub fn extend_recursive_descent(
+    bitness: u32,
+    bytes: &[u8],
+    address: u64,
+    seeds: impl IntoIterator<Item = u64>,
+    instructions: &mut BTreeMap<u64, Arc<Instruction>>,
+) -> Vec<u64> {
+    let end = address + bytes.len() as u64;
+    let in_section = |x: u64| x >= address && x < end;
+    let mut decoder = Decoder::with_ip(bitness, bytes, address, DecoderOptions::NONE);
+    let mut worklist = seeds
+        .into_iter()
+        .filter(|x| in_section(*x))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut failed = vec![];
+
+    while let Some(start) = worklist.pop() {
+        let mut ip = start;
+        while in_section(ip) && !instructions.contains_key(&ip) {
+            decoder.set_ip(ip);
+            if decoder.set_position((ip - address) as usize).is_err() {
+                break;
+            }
+            let instr = decoder.decode();
+            if instr.is_invalid() {
+                failed.push(ip);
+                break;
+            }
+            instructions.insert(ip, Arc::new(instr));
+            let flow = instr.flow_control();
+            let target = instr.near_branch_target();
+            let is_direct_branch = matches!(
+                flow,
+                FlowControl::ConditionalBranch
+                    | FlowControl::UnconditionalBranch
+                    | FlowControl::Call
+                    | FlowControl::XbeginXabortXend
+            );
+            if is_direct_branch && in_section(target) {
+                worklist.push(target);
+            }
+            let ends_path = matches!(
+                flow,
+                FlowControl::UnconditionalBranch
+                    | FlowControl::IndirectBranch
+                    | FlowControl::Return
+                    | FlowControl::Exception
+            ) || matches!(
+                instr.mnemonic(),
+                Mnemonic::Int3 | Mnemonic::Hlt | Mnemonic::Ud2
+            );
+            if ends_path {
+                break;
+            }
+            ip = instr.next_ip();
+        }
+    }
+    failed
+}
+
+/// Finds the regions of the section not covered by valid instructions. Padding instructions and
+/// invalid instructions also count as not being code. `instructions` should be sorted by address.
+pub fn data_in_code(
+    bytes: &[u8],
+    address: u64,
+    instructions: &[Arc<Instruction>],
+) -> Vec<DataInCode> {
+    let mut regions = vec![];
+    let mut push_region = |start: u64, end: u64| {
+        if end <= start {
+            return;
+        }
+        let region = &bytes[(start - address) as usize..(end - address) as usize];
+        regions.push(DataInCode {
+            address: start,
+            size: end - start,
+            is_padding: region.iter().copied().all(is_padding_byte),
+        });
+    };
+    let mut covered = address;
+    let mut gap_start = None;
+    for instr in instructions {
+        if instr.ip() < covered {
+            // Overlapping instructions, we've already accounted for these bytes
+            continue;
+        }
+        if instr.ip() > covered {
+            gap_start.get_or_insert(covered);
+        }
+        if instr.is_invalid() || is_padding(instr) {
+            gap_start.get_or_insert(instr.ip());
+        } else if let Some(start) = gap_start.take() {
+            push_region(start, instr.ip());
+        }
+        covered = instr.next_ip();
+    }
+    let end = address + bytes.len() as u64;
+    if covered < end {
+        gap_start.get_or_insert(covered);
+    }
+    if let Some(start) = gap_start {
+        push_region(start, end);
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // jmp +2; two bytes of data; ret; nop; nop
+    const CODE: [u8; 7] = [0xeb, 0x02, 0xff, 0xff, 0xc3, 0x90, 0x90];
+
+    #[test]
+    fn recursive_skips_data() {
+        let instructions = recursive_descent(64, &CODE, 0x1000, [0x1000]);
+        let addresses = instructions.iter().map(|x| x.ip()).collect::<Vec<_>>();
+        assert_eq!(addresses, vec![0x1000, 0x1004]);
+
+        let regions = data_in_code(&CODE, 0x1000, &instructions);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].address, regions[0].size), (0x1002, 2));
+        assert!(!regions[0].is_padding);
+        assert_eq!((regions[1].address, regions[1].size), (0x1005, 2));
+        assert!(regions[1].is_padding);
+    }
+
+    #[test]
+    fn decode_known_addresses() {
+        let instructions = decode_at(64, &CODE, 0x1000, &[0x1000, 0x1004, 0x2000]);
+        let addresses = instructions.iter().map(|x| x.ip()).collect::<Vec<_>>();
+        assert_eq!(addresses, vec![0x1000, 0x1004]);
+        assert_eq!(instructions[1].mnemonic(), Mnemonic::Ret);
+    }
+
+    #[test]
+    fn linear_marks_padding() {
+        let instructions = linear_sweep(64, &CODE[4..], 0x1004);
+        let regions = data_in_code(&CODE[4..], 0x1004, &instructions);
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].address, regions[0].size), (0x1005, 2));
+        assert!(regions[0].is_padding);
+    }
+}
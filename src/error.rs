@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors from loading a binary or querying it.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("failed to read file: {0}")]
+    Io(#[from] io::Error),
+    #[error("unsupported object file format: {0}")]
+    UnsupportedFormat(#[from] object::read::Error),
+    #[error("unsupported bitness {0}, expected 16, 32 or 64")]
+    UnsupportedBitness(u32),
+    #[error("unsupported architecture {0}, only x86 code can be decoded")]
+    UnsupportedArchitecture(String),
+    #[error("section '{0}' extends past the end of the file")]
+    InvalidSection(String),
+    #[error("no debug info: missing {0} section")]
+    MissingDebugInfo(&'static str),
+    #[error(
+        "DWARF error{}: {source}",
+        .unit_offset.map(|x| format!(" in unit at offset {:#x}", x)).unwrap_or_default()
+    )]
+    DwarfError {
+        /// Offset of the compile unit in `.debug_info` if the error is specific to one
+        unit_offset: Option<u64>,
+        source: gimli::Error,
+    },
+    #[error("failed to decode instruction at {address:#x}")]
+    DecodeError { address: u64 },
+    #[error("invalid index cache: {0}")]
+    Cache(String),
+    #[error("invalid query: {0}")]
+    Query(String),
+    #[error("invalid cargo output: {0}")]
+    CargoOutput(String),
+}
+
+impl Error {
+    /// The name of the error variant, used to group diagnostics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "Io",
+            Self::UnsupportedFormat(_) => "UnsupportedFormat",
+            Self::UnsupportedBitness(_) => "UnsupportedBitness",
+            Self::UnsupportedArchitecture(_) => "UnsupportedArchitecture",
+            Self::InvalidSection(_) => "InvalidSection",
+            Self::MissingDebugInfo(_) => "MissingDebugInfo",
+            Self::DwarfError { .. } => "DwarfError",
+            Self::DecodeError { .. } => "DecodeError",
+            Self::Cache(_) => "Cache",
+            Self::Query(_) => "Query",
+            Self::CargoOutput(_) => "CargoOutput",
+        }
+    }
+
+    pub fn unit_offset(&self) -> Option<u64> {
+        match self {
+            Self::DwarfError { unit_offset, .. } => *unit_offset,
+            _ => None,
+        }
+    }
+
+    pub fn address(&self) -> Option<u64> {
+        match self {
+            Self::DecodeError { address } => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+impl From<gimli::Error> for Error {
+    fn from(source: gimli::Error) -> Self {
+        Self::DwarfError {
+            unit_offset: None,
+            source,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Severity {
+    /// Some information may be missing but what was loaded is correct
+    Warning,
+    /// Part of the binary couldn't be read so results will be incomplete
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while loading a binary which didn't stop it from loading.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: Error,
+}
+
+impl Diagnostic {
+    pub fn warning(error: Error) -> Self {
+        Self {
+            severity: Severity::Warning,
+            error,
+        }
+    }
+
+    pub fn error(error: Error) -> Self {
+        Self {
+            severity: Severity::Error,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dwarf_error_message() {
+        let error = Error::DwarfError {
+            unit_offset: Some(0x2a),
+            source: gimli::Error::UnexpectedEof(gimli::ReaderOffsetId(0)),
+        };
+        assert_eq!(error.kind(), "DwarfError");
+        assert_eq!(error.unit_offset(), Some(0x2a));
+        assert!(error
+            .to_string()
+            .starts_with("DWARF error in unit at offset 0x2a: "));
+
+        let error = Error::from(gimli::Error::UnexpectedEof(gimli::ReaderOffsetId(0)));
+        assert!(!error.to_string().contains("unit"));
+    }
+}
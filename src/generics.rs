@@ -0,0 +1,210 @@
+//! Groups the instantiations of generic functions together to find code bloat from
+//! monomorphization. Instantiations are found by erasing the generic arguments from the demangled
+//! names of functions, so `Vec<u8>::push` and `Vec<String>::push` are both `Vec::push`.
+use crate::adapter::Function;
+use crate::demangle::{self, ManglingScheme};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// A generic function and the functions instantiated from it
+#[derive(Clone, Debug)]
+pub struct GenericItem {
+    /// Demangled path with the generic arguments and hash removed
+    pub path: String,
+    /// Instantiations sorted by address
+    pub instantiations: Vec<Arc<Function>>,
+    /// Total size of the instantiations in bytes
+    pub total_size: u64,
+}
+
+impl GenericItem {
+    /// Bytes which wouldn't be needed if only the largest instantiation was kept
+    pub fn duplicated_size(&self) -> u64 {
+        let largest = self.instantiations.iter().map(|x| x.size).max();
+        self.total_size - largest.unwrap_or_default()
+    }
+}
+
+/// Whether the `<` at `i` starts generic arguments rather than a qualified path such as
+/// `<T as Trait>`. Generic arguments follow a name or a `::`.
+fn is_generic_start(path: &str, i: usize) -> bool {
+    path[..i]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Calls `f` with the contents of each outermost list of generic arguments in the path, returning
+/// the path with them removed.
+fn visit_generics(path: &str, mut f: impl FnMut(&str)) -> String {
+    let mut erased = String::with_capacity(path.len());
+    let mut chars = path.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '<' || !is_generic_start(path, i) {
+            erased.push(c);
+            continue;
+        }
+        let start = i + 1;
+        let mut depth = 1;
+        let mut end = path.len();
+        let mut previous = c;
+        for (j, c) in chars.by_ref() {
+            match c {
+                '<' => depth += 1,
+                '>' if previous != '-' => depth -= 1,
+                _ => {}
+            }
+            previous = c;
+            if depth == 0 {
+                end = j;
+                break;
+            }
+        }
+        f(&path[start..end]);
+        // Drop the `::` of a turbofish along with the arguments
+        if erased.ends_with("::") {
+            erased.truncate(erased.len() - 2);
+        }
+    }
+    erased
+}
+
+/// The demangled path without generic arguments, keeping the brackets of qualified paths e.g.
+/// `<alloc::vec::Vec as core::clone::Clone>::clone`.
+pub fn erase_generics(path: &str) -> String {
+    visit_generics(path, |_| {})
+}
+
+/// Splits generic arguments on the commas between them
+fn split_arguments(arguments: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut parts = vec![];
+    let mut previous = ' ';
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if previous == '-' => {}
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    parts.push(&arguments[start..]);
+    parts.into_iter().map(str::trim).filter(|x| !x.is_empty())
+}
+
+/// The type arguments in a demangled path, in the order they appear. Lifetimes are skipped. Only
+/// v0 mangled names have the concrete types of an instantiation, legacy names mostly have the
+/// names of the type parameters.
+pub fn type_arguments(path: &str) -> Vec<String> {
+    let mut arguments = vec![];
+    visit_generics(path, |list| {
+        arguments.extend(
+            split_arguments(list)
+                .filter(|x| !x.starts_with('\''))
+                .map(|x| x.to_string()),
+        );
+    });
+    arguments
+}
+
+/// The generic path and type arguments of a function, or `None` if it isn't a Rust function.
+pub fn instantiation(name: &str) -> Option<(String, Vec<String>)> {
+    let demangled = demangle::demangle(name)?;
+    if demangled.scheme == ManglingScheme::Itanium {
+        return None;
+    }
+    let path = &demangled.name_no_hash;
+    Some((erase_generics(path), type_arguments(path)))
+}
+
+/// Groups the functions by their generic path, ordered by duplicated size with the largest first.
+/// Functions which aren't generic are left out, that is those with no generic arguments in
+/// their name and nothing else sharing their path.
+pub fn group_instantiations(functions: &[Arc<Function>]) -> Vec<GenericItem> {
+    let mut items: BTreeMap<String, (bool, Vec<Arc<Function>>)> = BTreeMap::new();
+    for func in functions {
+        let Some(demangled) = demangle::demangle(&func.name) else {
+            continue;
+        };
+        if demangled.scheme == ManglingScheme::Itanium {
+            continue;
+        }
+        let path = erase_generics(&demangled.name_no_hash);
+        let has_generics = path != demangled.name_no_hash;
+        let item = items.entry(path).or_default();
+        item.0 |= has_generics;
+        item.1.push(func.clone());
+    }
+    let mut items = items
+        .into_iter()
+        .filter(|(_, (has_generics, functions))| *has_generics || functions.len() > 1)
+        .map(|(path, (_, instantiations))| GenericItem {
+            total_size: instantiations.iter().map(|x| x.size).sum(),
+            path,
+            instantiations,
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| {
+        (b.duplicated_size(), b.total_size)
+            .cmp(&(a.duplicated_size(), a.total_size))
+            .then(a.path.cmp(&b.path))
+    });
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erases_generics() {
+        assert_eq!(
+            erase_generics("core::ptr::drop_in_place::<alloc::string::String>"),
+            "core::ptr::drop_in_place"
+        );
+        assert_eq!(
+            erase_generics("<alloc::vec::Vec<T> as core::clone::Clone>::clone"),
+            "<alloc::vec::Vec as core::clone::Clone>::clone"
+        );
+        assert_eq!(
+            erase_generics("<[u8]>::copy_within::<core::ops::range::RangeInclusive<usize>>"),
+            "<[u8]>::copy_within"
+        );
+        assert_eq!(
+            erase_generics("mycrate::call::<fn() -> u8, u8>"),
+            "mycrate::call"
+        );
+        assert_eq!(erase_generics("mycrate::run"), "mycrate::run");
+    }
+
+    #[test]
+    fn parses_type_arguments() {
+        assert_eq!(
+            type_arguments(
+                "<alloc::collections::btree::node::NodeRef<'_, u64, gimli::Abbreviation>>::new::<alloc::alloc::Global>"
+            ),
+            vec!["u64", "gimli::Abbreviation", "alloc::alloc::Global"]
+        );
+        assert_eq!(
+            type_arguments("mycrate::call::<fn() -> u8, (u8, u16)>"),
+            vec!["fn() -> u8", "(u8, u16)"]
+        );
+        assert!(type_arguments("mycrate::run").is_empty());
+    }
+
+    #[test]
+    fn v0_instantiations() {
+        let (path, arguments) = instantiation(
+            "_RINvNtCsgEmfK2I1SDS_4core3ptr13drop_in_placeNtNtCslNYArtu3iFV_5alloc6string6StringECs4X4t9plMPHF_9addr2line",
+        )
+        .unwrap();
+        assert_eq!(path, "core::ptr::drop_in_place");
+        assert_eq!(arguments, vec!["alloc::string::String"]);
+    }
+}
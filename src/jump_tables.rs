@@ -0,0 +1,241 @@
+use crate::adapter::Section;
+use iced_x86::{FlowControl, Instruction, Mnemonic, OpKind, Register};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How far back from an indirect jump we look for the instructions setting up the table
+const WINDOW: usize = 12;
+/// Upper limit on entries read from a table when there's no bounds check to tell us the size
+const MAX_ENTRIES: usize = 4096;
+
+/// A table of jump targets used to dispatch an indirect jump, typically generated for a `match`
+/// or `switch` statement.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JumpTable {
+    /// Address of the start of the table
+    pub address: u64,
+    /// Size of each entry in bytes
+    pub entry_size: usize,
+    /// Whether entries are offsets from the table address rather than absolute addresses
+    pub is_relative: bool,
+    /// The indirect jump which uses the table
+    pub dispatch: Arc<Instruction>,
+    /// The target of each entry in the table in order, targets can be repeated
+    pub targets: Vec<u64>,
+}
+
+fn read_bytes(sections: &[Arc<Section>], address: u64, length: usize) -> Option<&[u8]> {
+    sections
+        .iter()
+        .filter(|x| x.address > 0 && x.contains(address))
+        .find_map(|x| x.data_at(address, length))
+}
+
+fn writes_register(instr: &Instruction, register: Register) -> bool {
+    instr.op_count() > 0
+        && instr.op0_kind() == OpKind::Register
+        && instr.op0_register().full_register() == register.full_register()
+}
+
+/// Finds the number of entries from a bounds check on the index before the dispatch, looking for
+/// a `cmp index, N` followed by `ja`/`jae` to the default case. The index is often copied or zero
+/// extended into another register between the check and the table load, so if no check on the
+/// index register is found the nearest check on any register is used.
+fn find_entry_count(instructions: &[Arc<Instruction>], index: Register) -> Option<usize> {
+    let mut bound_jump = None;
+    let mut fallback = None;
+    for instr in instructions.iter().rev() {
+        match instr.mnemonic() {
+            Mnemonic::Ja | Mnemonic::Jae if bound_jump.is_none() => {
+                bound_jump = Some(instr.mnemonic());
+            }
+            Mnemonic::Cmp
+                if bound_jump.is_some()
+                    && instr.op0_kind() == OpKind::Register
+                    && !matches!(instr.op1_kind(), OpKind::Register | OpKind::Memory) =>
+            {
+                let bound = instr.immediate(1) as usize;
+                let count = match bound_jump {
+                    Some(Mnemonic::Ja) => bound.checked_add(1),
+                    _ => Some(bound),
+                };
+                if instr.op0_register().full_register() == index.full_register() {
+                    return count;
+                }
+                fallback = fallback.or(count);
+            }
+            _ => {}
+        }
+    }
+    fallback
+}
+
+/// Works out where the table is for an indirect jump. Returns the table address, entry size,
+/// whether the entries are relative and the register used to index it.
+fn find_table(
+    instructions: &[Arc<Instruction>],
+    dispatch: &Instruction,
+) -> Option<(u64, usize, bool, Register)> {
+    // Finds the address loaded into a register via `lea reg, [rip + X]`
+    let find_lea = |before: usize, register: Register| {
+        instructions[..before]
+            .iter()
+            .rev()
+            .find(|x| writes_register(x, register))
+            .filter(|x| x.mnemonic() == Mnemonic::Lea && x.is_ip_rel_memory_operand())
+            .map(|x| x.ip_rel_memory_address())
+    };
+    // Table address for a memory operand of the form `[base + index*scale]` or
+    // `[disp + index*scale]`
+    let memory_table = |before: usize, instr: &Instruction| {
+        if instr.memory_index() == Register::None {
+            return None;
+        }
+        if instr.memory_base() == Register::None {
+            Some(instr.memory_displacement64())
+        } else {
+            find_lea(before, instr.memory_base())
+        }
+    };
+
+    match dispatch.op0_kind() {
+        // jmp qword [table + index*8]
+        OpKind::Memory if dispatch.memory_index_scale() == 8 => {
+            let table = memory_table(instructions.len(), dispatch)?;
+            Some((table, 8, false, dispatch.memory_index()))
+        }
+        OpKind::Register => {
+            let target = dispatch.op0_register();
+            let (i, load) = instructions
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(_, x)| writes_register(x, target))?;
+            match load.mnemonic() {
+                // mov target, [table + index*8]; jmp target
+                Mnemonic::Mov
+                    if load.op1_kind() == OpKind::Memory && load.memory_index_scale() == 8 =>
+                {
+                    let table = memory_table(i, load)?;
+                    Some((table, 8, false, load.memory_index()))
+                }
+                // lea base, [rip + table]; movsxd target, [base + index*4]; add target, base;
+                // jmp target
+                Mnemonic::Add if load.op1_kind() == OpKind::Register => {
+                    let base = load.op1_register();
+                    let (j, entry) = instructions[..i]
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .find(|(_, x)| writes_register(x, target))?;
+                    let is_entry_load = entry.mnemonic() == Mnemonic::Movsxd
+                        && entry.op1_kind() == OpKind::Memory
+                        && entry.memory_index_scale() == 4
+                        && entry.memory_base().full_register() == base.full_register();
+                    if !is_entry_load {
+                        return None;
+                    }
+                    let table = find_lea(j, base)?;
+                    Some((table, 4, true, entry.memory_index()))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn read_entry(
+    sections: &[Arc<Section>],
+    table: u64,
+    entry_size: usize,
+    is_relative: bool,
+    index: usize,
+) -> Option<u64> {
+    let address = table.checked_add((index * entry_size) as u64)?;
+    let bytes = read_bytes(sections, address, entry_size)?;
+    if is_relative {
+        let offset = i32::from_le_bytes(bytes.try_into().ok()?);
+        Some(table.wrapping_add_signed(offset as i64))
+    } else {
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// Finds the jump tables used by indirect jumps in the instructions, reading the tables from the
+/// sections. Only targets within `bounds` (usually the containing function) are accepted.
+pub fn find_jump_tables(
+    instructions: &[Arc<Instruction>],
+    sections: &[Arc<Section>],
+    bounds: Range<u64>,
+) -> Vec<JumpTable> {
+    let mut tables = vec![];
+    for (i, dispatch) in instructions.iter().enumerate() {
+        if dispatch.flow_control() != FlowControl::IndirectBranch {
+            continue;
+        }
+        let window = &instructions[i.saturating_sub(WINDOW)..i];
+        let Some((address, entry_size, is_relative, index)) = find_table(window, dispatch) else {
+            continue;
+        };
+        let count = find_entry_count(window, index).unwrap_or(MAX_ENTRIES);
+        let targets = (0..count.min(MAX_ENTRIES))
+            .map_while(|i| read_entry(sections, address, entry_size, is_relative, i))
+            .take_while(|x| bounds.contains(x))
+            .collect::<Vec<_>>();
+        if !targets.is_empty() {
+            tables.push(JumpTable {
+                address,
+                entry_size,
+                is_relative,
+                dispatch: dispatch.clone(),
+                targets,
+            });
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_x86::{Decoder, DecoderOptions};
+
+    #[test]
+    fn relative_table() {
+        // 0x1000: cmp eax, 2
+        // 0x1003: ja 0x1018
+        // 0x1005: lea rcx, [rip + 0xff4] (0x2000)
+        // 0x100c: movsxd rax, dword [rcx + rax*4]
+        // 0x1010: add rax, rcx
+        // 0x1013: jmp rax
+        // 0x1015: ret; ret; ret; ret
+        let code = [
+            0x83, 0xf8, 0x02, 0x77, 0x13, 0x48, 0x8d, 0x0d, 0xf4, 0x0f, 0x00, 0x00, 0x48, 0x63,
+            0x04, 0x81, 0x48, 0x01, 0xc8, 0xff, 0xe0, 0xc3, 0xc3, 0xc3, 0xc3,
+        ];
+        let instructions = Decoder::with_ip(64, &code, 0x1000, DecoderOptions::NONE)
+            .iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        let mut table = vec![];
+        for target in [0x1015i64, 0x1016, 0x1017, 0x1015] {
+            table.extend_from_slice(&((target - 0x2000) as i32).to_le_bytes());
+        }
+        let sections = vec![Arc::new(Section {
+            name: ".rodata".to_string(),
+            address: 0x2000,
+            size: table.len() as u64,
+            data: table.into(),
+        })];
+
+        let tables = find_jump_tables(&instructions, &sections, 0x1000..0x1019);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].address, 0x2000);
+        assert!(tables[0].is_relative);
+        assert_eq!(tables[0].dispatch.ip(), 0x1013);
+        // Bounds check limits us to 3 entries even though there's 4 in the section
+        assert_eq!(tables[0].targets, vec![0x1015, 0x1016, 0x1017]);
+    }
+}
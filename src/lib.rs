@@ -1,2 +1,25 @@
 pub mod adapter;
+pub mod bytes;
+pub mod cache;
+pub mod cargo;
+pub mod cfg;
+pub mod coverage;
+pub mod cpu_features;
+pub mod crates;
+pub mod demangle;
+pub mod diff;
+pub mod disassembly;
+pub mod error;
+pub mod generics;
+pub mod jump_tables;
+pub mod listing;
 pub mod loader;
+pub mod multi;
+pub mod query;
+pub mod repl;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod size;
+pub mod source_paths;
+pub mod symbolize;
+pub mod xrefs;
@@ -0,0 +1,199 @@
+//! Disassembly listings in the style of `objdump -d`, optionally with the source lines each
+//! instruction came from like `objdump -S`. Everything printed comes from the adapter so the
+//! listing shows the same instructions, bytes and locations as queries do.
+use crate::adapter::{Adapter, Function, SourceLocation};
+use crate::bytes::to_hex;
+use crate::demangle;
+use crate::error::Result;
+use crate::xrefs::{self, ReferenceKind};
+use iced_x86::{FlowControl, Formatter, Instruction, NasmFormatter, OpKind};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Bytes shown on each line, longer instructions continue on the following lines
+const BYTES_PER_LINE: usize = 7;
+
+/// Lines of source before a location which are also shown if they haven't been already, so a
+/// statement split over several lines is shown in full
+const SOURCE_CONTEXT: usize = 4;
+
+/// What to include in a listing
+#[derive(Clone, Debug, Default)]
+pub struct ListingOptions {
+    /// Names or demangled paths of the functions to list, every function is listed if this is
+    /// empty
+    pub functions: Vec<String>,
+    /// Show the source lines for the instructions, read from disk if the files can be found
+    pub source: bool,
+    /// Leave out the bytes of each instruction
+    pub hide_bytes: bool,
+}
+
+/// Source files read so far, `None` if the file couldn't be read, along with the last line shown
+/// from each file
+#[derive(Default)]
+struct SourceFiles {
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+    shown: HashMap<PathBuf, usize>,
+}
+
+impl SourceFiles {
+    fn lines(&mut self, path: &Path) -> Option<&[String]> {
+        self.files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let text = fs::read_to_string(path).ok()?;
+                Some(text.lines().map(|x| x.to_string()).collect())
+            })
+            .as_deref()
+    }
+
+    /// Writes the location followed by its source line, along with any lines just before it
+    /// which haven't been shown yet
+    fn write(&mut self, out: &mut impl Write, location: &SourceLocation) -> Result<()> {
+        writeln!(out, "{}:{}", location.file.display(), location.line)?;
+        let shown = self.shown.get(&location.file).copied().unwrap_or_default();
+        let Some(lines) = self.lines(&location.file) else {
+            return Ok(());
+        };
+        if location.line == 0 || location.line > lines.len() {
+            return Ok(());
+        }
+        let start = if shown < location.line {
+            (shown + 1).max(location.line.saturating_sub(SOURCE_CONTEXT))
+        } else {
+            location.line
+        };
+        for line in &lines[start - 1..location.line] {
+            writeln!(out, "{}", line)?;
+        }
+        self.shown
+            .insert(location.file.clone(), shown.max(location.line));
+        Ok(())
+    }
+}
+
+/// Writes a listing of the functions in the binary, in address order.
+pub fn write_listing(
+    adapter: &Adapter,
+    options: &ListingOptions,
+    mut out: impl Write,
+) -> Result<()> {
+    let mut sources = SourceFiles::default();
+    let mut last_address = None;
+    for func in adapter.functions() {
+        // Aliases of an already listed function would just repeat it
+        if last_address == Some(func.address) {
+            continue;
+        }
+        if !options.functions.is_empty()
+            && !options
+                .functions
+                .iter()
+                .any(|x| demangle::matches(&func.name, x))
+        {
+            continue;
+        }
+        last_address = Some(func.address);
+        write_function(adapter, func, options, &mut sources, &mut out)?;
+    }
+    Ok(())
+}
+
+/// The symbol an address is in written as `<name+offset>`
+fn symbolize(adapter: &Adapter, address: u64) -> Option<String> {
+    let (name, start) = match adapter.find_symbol_containing(address) {
+        Some(symbol) => (symbol.name.clone(), symbol.address),
+        None => {
+            let func = adapter.find_function_containing(address)?;
+            (func.name.clone(), func.address)
+        }
+    };
+    Some(match address - start {
+        0 => format!("<{}>", name),
+        offset => format!("<{}+0x{:x}>", name, offset),
+    })
+}
+
+/// The address an instruction branches to or reads from, if it has one
+pub(crate) fn target(instr: &Arc<Instruction>, adapter: &Adapter) -> Option<u64> {
+    let is_direct_branch = matches!(
+        instr.flow_control(),
+        FlowControl::ConditionalBranch | FlowControl::UnconditionalBranch | FlowControl::Call
+    ) && matches!(
+        instr.op0_kind(),
+        OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64
+    );
+    if is_direct_branch {
+        return Some(instr.near_branch_target());
+    }
+    xrefs::data_references(instr, adapter.sections())
+        .into_iter()
+        .find(|x| x.kind == ReferenceKind::RipRelative)
+        .map(|x| x.address)
+}
+
+fn write_function(
+    adapter: &Adapter,
+    func: &Function,
+    options: &ListingOptions,
+    sources: &mut SourceFiles,
+    out: &mut impl Write,
+) -> Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{:016x} <{}>:", func.address, func.name)?;
+    let mut formatter = NasmFormatter::new();
+    let mut last_location: Option<Arc<SourceLocation>> = None;
+    for instr in func.instructions() {
+        if options.source {
+            let locations = adapter
+                .debug_info()
+                .get(&instr.ip())
+                .map(|x| x.as_slice())
+                .unwrap_or_default();
+            for location in locations {
+                if last_location
+                    .as_ref()
+                    .is_some_and(|x| (&x.file, x.line) == (&location.file, location.line))
+                {
+                    continue;
+                }
+                sources.write(out, location)?;
+                last_location = Some(location.clone());
+            }
+        }
+
+        let mut text = String::new();
+        formatter.format(instr, &mut text);
+        if let Some(name) = target(instr, adapter).and_then(|x| symbolize(adapter, x)) {
+            text.push(' ');
+            text.push_str(&name);
+        }
+        let bytes = adapter
+            .find_section(instr.ip())
+            .and_then(|x| x.data_at(instr.ip(), instr.len()).map(|x| x.to_vec()))
+            .unwrap_or_default();
+        if options.hide_bytes {
+            writeln!(out, "{:>8x}:\t{}", instr.ip(), text)?;
+            continue;
+        }
+        let mut chunks = bytes.chunks(BYTES_PER_LINE);
+        let first = chunks.next().unwrap_or_default();
+        writeln!(
+            out,
+            "{:>8x}:\t{:<width$}\t{}",
+            instr.ip(),
+            to_hex(first),
+            text,
+            width = BYTES_PER_LINE * 3 - 1
+        )?;
+        for (i, chunk) in chunks.enumerate() {
+            let address = instr.ip() + ((i + 1) * BYTES_PER_LINE) as u64;
+            writeln!(out, "{:>8x}:\t{}", address, to_hex(chunk))?;
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,64 @@
-use crate::adapter::SourceLocation;
-use anyhow::Context;
+use crate::adapter::{Function, Section, SourceLocation, Symbol};
+use crate::crates::{self, UnitCrates};
+use crate::disassembly::DisassemblyMode;
+use crate::error::Error;
 use gimli::*;
-use object::{read::ObjectSection, Object};
+use object::{read::ObjectSection, ObjectSymbol, SymbolKind};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::path::PathBuf;
-use std::rc::Rc;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Options controlling how an object file is loaded into the adapter, see
+/// [`AdapterBuilder`](crate::adapter::AdapterBuilder) for setting them.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct LoadOptions {
+    /// How to find the instructions in the code sections
+    pub disassembly: DisassemblyMode,
+    /// Names of the sections to disassemble
+    pub code_sections: Vec<String>,
+    /// Decode instructions with this bitness (16, 32 or 64) instead of the one from the file
+    pub bitness: Option<u32>,
+    /// Directories to look for separate debug info in when the binary has been stripped
+    pub debug_search_paths: Vec<PathBuf>,
+    /// Include line table rows which aren't recommended breakpoint locations
+    pub include_non_stmt_rows: bool,
+    /// Prefixes of source paths to replace, applied in order with the first match winning
+    pub path_remapping: Vec<(PathBuf, PathBuf)>,
+    /// Fail to load if the debug info is missing or can't be read instead of continuing without it
+    pub strict: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            disassembly: DisassemblyMode::default(),
+            code_sections: vec![".text".to_string()],
+            bitness: None,
+            debug_search_paths: vec![],
+            include_non_stmt_rows: false,
+            path_remapping: vec![],
+            strict: false,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Applies the first matching path remapping to the path.
+    pub fn remap_path(&self, path: PathBuf) -> PathBuf {
+        for (from, to) in &self.path_remapping {
+            if let Ok(rest) = path.strip_prefix(from) {
+                return if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                };
+            }
+        }
+        path
+    }
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DecodedInstruction {
@@ -15,10 +68,14 @@ pub struct DecodedInstruction {
     pub length: usize,
 }
 
+/// Calls `row` with the start address of the sequence, the address and the location of every
+/// useful row of the line program. The rows of a sequence are in address order.
 pub(crate) fn get_addresses_from_program<R, Offset>(
     prog: IncompleteLineProgram<R>,
     debug_strs: &DebugStr<R>,
-    result: &mut BTreeMap<u64, Vec<Rc<SourceLocation>>>,
+    comp_dir: Option<Arc<Path>>,
+    options: &LoadOptions,
+    row: &mut impl FnMut(u64, u64, SourceLocation),
 ) -> Result<()>
 where
     R: Reader<Offset = Offset>,
@@ -30,7 +87,7 @@ where
         let mut sm = cprog.resume_from(&s);
         while let Ok(Some((header, &ln_row))) = sm.next_row() {
             // If this row isn't useful move on
-            if !ln_row.is_stmt() || ln_row.line().is_none() {
+            if (!ln_row.is_stmt() && !options.include_non_stmt_rows) || ln_row.line().is_none() {
                 continue;
             }
             if let Some(file) = ln_row.file(header) {
@@ -54,11 +111,12 @@ where
                     let address = ln_row.address();
                     if address > 0 {
                         let loc = SourceLocation {
-                            file: path.into(),
+                            file: options.remap_path(path),
                             line: line.get() as usize,
                             column,
+                            comp_dir: comp_dir.clone(),
                         };
-                        result.entry(address).or_default().push(loc.into());
+                        row(s.start, address, loc);
                     }
                 }
             }
@@ -67,49 +125,390 @@ where
     Ok(())
 }
 
+/// Reads the line programs of every compile unit into a map from address to the locations there.
+/// Units which can't be read are skipped and the problem added to `diagnostics`, an error is only
+/// returned if there's no debug info at all.
 pub(crate) fn get_line_addresses<'data>(
     obj: &'data impl object::read::Object<'data>,
-) -> anyhow::Result<BTreeMap<u64, Vec<Rc<SourceLocation>>>> {
+    options: &LoadOptions,
+    diagnostics: &mut Vec<Error>,
+) -> std::result::Result<BTreeMap<u64, Vec<Arc<SourceLocation>>>, Error> {
+    let mut result: BTreeMap<u64, Vec<Arc<SourceLocation>>> = BTreeMap::new();
+    for_each_line_row(obj, options, diagnostics, |_, address, location| {
+        result.entry(address).or_default().push(location.into());
+    })?;
+    Ok(result)
+}
+
+/// Reads the line programs of every compile unit, calling `row` as described in
+/// [`get_addresses_from_program`]. Units which can't be read are skipped and the problem added to
+/// `diagnostics`.
+pub(crate) fn for_each_line_row<'data>(
+    obj: &'data impl object::read::Object<'data>,
+    options: &LoadOptions,
+    diagnostics: &mut Vec<Error>,
+    mut row: impl FnMut(u64, u64, SourceLocation),
+) -> std::result::Result<(), Error> {
     let endian = if obj.is_little_endian() {
         RunTimeEndian::Little
     } else {
         RunTimeEndian::Big
     };
-    let debug_info = obj
-        .section_by_name(".debug_info")
-        .context("No debug_info")?;
-    let debug_info = DebugInfo::new(debug_info.data()?, endian);
-    let debug_abbrev = obj
-        .section_by_name(".debug_abbrev")
-        .context("No debug_abbrev")?;
-    let debug_abbrev = DebugAbbrev::new(debug_abbrev.data()?, endian);
-    let debug_strings = obj.section_by_name(".debug_str").context("No debug_str")?;
-    let debug_strings = DebugStr::new(debug_strings.data()?, endian);
-    let debug_line = obj
-        .section_by_name(".debug_line")
-        .context("No debug_line")?;
-    let debug_line = DebugLine::new(debug_line.data()?, endian);
+    let section_data = |name: &'static str| {
+        obj.section_by_name(name)
+            .ok_or(Error::MissingDebugInfo(name))
+            .and_then(|x| x.data().map_err(Error::from))
+    };
+    let debug_info = DebugInfo::new(section_data(".debug_info")?, endian);
+    let debug_abbrev = DebugAbbrev::new(section_data(".debug_abbrev")?, endian);
+    let debug_strings = DebugStr::new(section_data(".debug_str")?, endian);
+    let debug_line = DebugLine::new(section_data(".debug_line")?, endian);
+    // Only DWARF 5 has this section
+    let debug_line_strings =
+        DebugLineStr::new(section_data(".debug_line_str").unwrap_or(&[]), endian);
 
     let mut iter = debug_info.units();
-    let mut result = BTreeMap::new();
-    while let Ok(Some(cu)) = iter.next() {
+    loop {
+        let cu = match iter.next() {
+            Ok(Some(cu)) => cu,
+            Ok(None) => break,
+            Err(e) => {
+                // We can't find the next unit without a valid header so give up on the rest
+                diagnostics.push(e.into());
+                break;
+            }
+        };
+        let unit_offset = cu.offset().as_debug_info_offset().map(|x| x.0 as u64);
+        let unit_error = |source| Error::DwarfError {
+            unit_offset,
+            source,
+        };
         let addr_size = cu.address_size();
         let abbr = match cu.abbreviations(&debug_abbrev) {
             Ok(a) => a,
+            Err(e) => {
+                diagnostics.push(unit_error(e));
+                continue;
+            }
+        };
+
+        let root = match cu.entries(&abbr).next_dfs() {
+            Ok(Some((_, root))) => root.clone(),
+            Ok(None) => continue,
+            Err(e) => {
+                diagnostics.push(unit_error(e));
+                continue;
+            }
+        };
+        let offset = match root.attr_value(DW_AT_stmt_list) {
+            Ok(Some(AttributeValue::DebugLineRef(o))) => o,
             _ => continue,
         };
+        let comp_dir = match root.attr_value(DW_AT_comp_dir) {
+            Ok(Some(AttributeValue::String(x))) => Some(x),
+            Ok(Some(AttributeValue::DebugStrRef(o))) => debug_strings.get_str(o).ok(),
+            Ok(Some(AttributeValue::DebugLineStrRef(o))) => debug_line_strings.get_str(o).ok(),
+            _ => None,
+        }
+        .map(|x| Arc::from(options.remap_path(x.to_string_lossy().into_owned().into())));
+        let res = debug_line
+            .program(offset, addr_size, None, None)
+            .and_then(|prog| {
+                get_addresses_from_program(prog, &debug_strings, comp_dir, options, &mut row)
+            });
+        if let Err(e) = res {
+            diagnostics.push(unit_error(e));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the functions in the symbol table within the code sections. Nothing is decoded here, each
+/// function decodes its own code when its instructions are first needed.
+pub(crate) fn get_functions<'data>(
+    obj: &'data impl object::read::Object<'data>,
+    code_sections: &[Arc<Section>],
+    sections: &Arc<Vec<Arc<Section>>>,
+    bitness: u32,
+) -> Vec<Arc<Function>> {
+    let mut seen = HashSet::new();
+    let mut functions = obj
+        .symbols()
+        .filter(|x| x.kind() == SymbolKind::Text && x.size() > 0 && x.address() > 0)
+        .filter(|x| seen.insert(x.address()))
+        .filter_map(|x| {
+            let name = x.name().ok()?.to_string();
+            let section = code_sections.iter().find(|s| s.contains(x.address()))?;
+            let start = (x.address() - section.address) as usize;
+            let end = start
+                .saturating_add(x.size() as usize)
+                .min(section.data.len());
+            let code = section.data.slice(start..end)?;
+            Some(Function::new(
+                name,
+                x.address(),
+                code,
+                bitness,
+                sections.clone(),
+            ))
+        })
+        .map(Arc::new)
+        .collect::<Vec<_>>();
+    functions.sort_by_key(|x| x.address);
+    functions
+}
+
+/// Borrows the DWARF sections of the object file, any missing sections are empty
+fn load_dwarf<'data>(
+    obj: &'data impl object::read::Object<'data>,
+) -> std::result::Result<Dwarf<EndianSlice<'data, RunTimeEndian>>, Error> {
+    let endian = if obj.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = Dwarf::load(|id| -> Result<_> {
+        let data = obj
+            .section_by_name(id.name())
+            .and_then(|x| x.data().ok())
+            .unwrap_or_default();
+        Ok(EndianSlice::new(data, endian))
+    })?;
+    Ok(dwarf)
+}
 
-        if let Ok(Some((_, root))) = cu.entries(&abbr).next_dfs() {
-            let offset = match root.attr_value(DW_AT_stmt_list) {
-                Ok(Some(AttributeValue::DebugLineRef(o))) => o,
-                _ => continue,
-            };
-            let prog = debug_line.program(offset, addr_size, None, None)?; // Here?
+/// Get the start address of every function described in the DWARF debug information.
+pub(crate) fn get_dwarf_function_addresses<'data>(
+    obj: &'data impl object::read::Object<'data>,
+) -> std::result::Result<Vec<u64>, Error> {
+    let dwarf = load_dwarf(obj)?;
 
-            if let Err(e) = get_addresses_from_program(prog, &debug_strings, &mut result) {
-                eprintln!("Potential issue reading test addresses {}", e);
+    let mut result = vec![];
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let unit = match dwarf.unit(header) {
+            Ok(u) => u,
+            Err(_) => continue,
+        };
+        let mut entries = unit.entries();
+        while let Ok(Some((_, entry))) = entries.next_dfs() {
+            if entry.tag() != DW_TAG_subprogram {
+                continue;
+            }
+            if let Ok(Some(low_pc)) = entry.attr_value(DW_AT_low_pc) {
+                if let Ok(Some(address)) = dwarf.attr_address(&unit, low_pc) {
+                    if address > 0 {
+                        result.push(address);
+                    }
+                }
             }
         }
     }
     Ok(result)
 }
+
+/// The address ranges of Rust code in the DWARF along with the crate it's from. Functions are
+/// attributed to the outermost namespace they're in, which is the crate defining them, and
+/// compile units to the crate in the unit's name when it has one.
+pub(crate) fn get_unit_crates<'data>(
+    obj: &'data impl object::read::Object<'data>,
+) -> std::result::Result<UnitCrates, Error> {
+    let dwarf = load_dwarf(obj)?;
+
+    let mut result = vec![];
+    let mut push_ranges = |ranges: Result<RangeIter<_>>, krate: &Arc<str>| {
+        let Ok(mut ranges) = ranges else {
+            return;
+        };
+        while let Ok(Some(range)) = ranges.next() {
+            if range.begin > 0 && range.begin < range.end {
+                result.push((range.begin..range.end, krate.clone()));
+            }
+        }
+    };
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let Ok(unit) = dwarf.unit(header) else {
+            continue;
+        };
+        let unit_crate = unit
+            .name
+            .map(|x| x.to_string_lossy())
+            .and_then(|x| crates::unit_crate(&x).map(Arc::<str>::from));
+        if let Some(krate) = &unit_crate {
+            push_ranges(dwarf.unit_ranges(&unit), krate);
+        }
+
+        // The namespaces enclosing the current entry and their depths
+        let mut namespaces: Vec<(isize, Arc<str>)> = vec![];
+        let mut depth = 0;
+        let mut entries = unit.entries();
+        while let Ok(Some((delta, entry))) = entries.next_dfs() {
+            depth += delta;
+            while namespaces.last().is_some_and(|x| x.0 >= depth) {
+                namespaces.pop();
+            }
+            if entry.tag() == DW_TAG_namespace {
+                let name = entry
+                    .attr_value(DW_AT_name)
+                    .ok()
+                    .flatten()
+                    .and_then(|x| dwarf.attr_string(&unit, x).ok())
+                    .map(|x| Arc::<str>::from(x.to_string_lossy().as_ref()));
+                if let Some(name) = name {
+                    namespaces.push((depth, name));
+                }
+            } else if entry.tag() == DW_TAG_subprogram {
+                if let Some((_, krate)) = namespaces.first() {
+                    push_ranges(dwarf.die_ranges(&unit, entry), krate);
+                }
+            }
+        }
+    }
+    result.sort_by_key(|x| x.0.start);
+    Ok(result)
+}
+
+/// The name of each compile unit and the number of bytes of code in its address ranges. Units
+/// without a name or any code are skipped.
+pub(crate) fn get_unit_sizes<'data>(
+    obj: &'data impl object::read::Object<'data>,
+) -> std::result::Result<Vec<(String, u64)>, Error> {
+    let dwarf = load_dwarf(obj)?;
+
+    let mut result = vec![];
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let Ok(unit) = dwarf.unit(header) else {
+            continue;
+        };
+        let Some(name) = unit.name.map(|x| x.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let Ok(mut ranges) = dwarf.unit_ranges(&unit) else {
+            continue;
+        };
+        let mut size = 0;
+        while let Ok(Some(range)) = ranges.next() {
+            if range.begin > 0 && range.begin < range.end {
+                size += range.end - range.begin;
+            }
+        }
+        if size > 0 {
+            result.push((name, size));
+        }
+    }
+    Ok(result)
+}
+
+/// Addresses we know are the start of code, used to seed recursive disassembly. Function addresses
+/// are read from the DWARF in `debug_obj`, which may be a separate debug file.
+pub(crate) fn get_code_seeds<'data>(
+    obj: &'data impl object::read::Object<'data>,
+    debug_obj: &'data impl object::read::Object<'data>,
+) -> Vec<u64> {
+    let mut seeds = obj
+        .symbols()
+        .filter(|x| x.kind() == SymbolKind::Text && x.address() > 0)
+        .map(|x| x.address())
+        .collect::<Vec<_>>();
+    seeds.push(obj.entry());
+    seeds.extend(get_dwarf_function_addresses(debug_obj).unwrap_or_default());
+    seeds
+}
+
+/// Get every symbol with an address, sorted by address. Section and file symbols are skipped.
+pub(crate) fn get_symbols<'data>(obj: &'data impl object::read::Object<'data>) -> Vec<Arc<Symbol>> {
+    let mut symbols = obj
+        .symbols()
+        .chain(obj.dynamic_symbols())
+        .filter(|x| x.address() > 0 && !matches!(x.kind(), SymbolKind::Section | SymbolKind::File))
+        .filter_map(|x| {
+            let name = x.name().ok().filter(|x| !x.is_empty())?;
+            Some(Symbol {
+                name: name.to_string(),
+                address: x.address(),
+                size: x.size(),
+                kind: format!("{:?}", x.kind()).to_lowercase(),
+            })
+        })
+        .collect::<Vec<_>>();
+    symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+    symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+    symbols.into_iter().map(Arc::new).collect()
+}
+
+/// Looks for separate debug info for a stripped binary. First by build ID in
+/// `<search path>/.build-id/xx/yyyy.debug`, then by the `.gnu_debuglink` file name next to the
+/// binary, in a `.debug` directory next to it or in the search paths. Debuglink candidates are only
+/// accepted if their CRC matches. Returns `None` if the binary already has debug info.
+pub(crate) fn find_debug_file<'data>(
+    obj: &'data impl object::read::Object<'data>,
+    binary_dir: Option<&Path>,
+    search_paths: &[PathBuf],
+) -> Option<PathBuf> {
+    if obj.section_by_name(".debug_info").is_some() {
+        return None;
+    }
+    if let Ok(Some(build_id)) = obj.build_id() {
+        if build_id.len() > 1 {
+            let hex = build_id
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>();
+            let (dir, file) = hex.split_at(2);
+            let found = search_paths
+                .iter()
+                .map(|x| {
+                    x.join(".build-id")
+                        .join(dir)
+                        .join(format!("{}.debug", file))
+                })
+                .find(|x| x.is_file());
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    let (name, crc) = obj.gnu_debuglink().ok()??;
+    let name = Path::new(std::str::from_utf8(name).ok()?);
+    let mut candidates = vec![];
+    if let Some(dir) = binary_dir {
+        candidates.push(dir.join(name));
+        candidates.push(dir.join(".debug").join(name));
+    }
+    candidates.extend(search_paths.iter().map(|x| x.join(name)));
+    candidates.into_iter().find(|x| {
+        fs::read(x)
+            .map(|x| crc32fast::hash(&x) == crc)
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_remapping() {
+        let options = LoadOptions {
+            path_remapping: vec![
+                ("/build/src".into(), "/home/me/src".into()),
+                ("/build".into(), "/other".into()),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            options.remap_path("/build/src/main.rs".into()),
+            PathBuf::from("/home/me/src/main.rs")
+        );
+        assert_eq!(
+            options.remap_path("/build/lib.rs".into()),
+            PathBuf::from("/other/lib.rs")
+        );
+        assert_eq!(
+            options.remap_path("/builds/lib.rs".into()),
+            PathBuf::from("/builds/lib.rs")
+        );
+    }
+}
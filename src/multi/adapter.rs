@@ -0,0 +1,289 @@
+use super::{LoadedBinary, MultiAdapter, MultiVertex};
+use crate::adapter::Vertex;
+use std::iter;
+use std::sync::Arc;
+use trustfall::{
+    provider::{
+        resolve_coercion_using_schema, resolve_neighbors_with, resolve_property_with, AsVertex,
+        ContextIterator, ContextOutcomeIterator, DataContext, EdgeParameters, ResolveEdgeInfo,
+        ResolveInfo, Typename, VertexIterator,
+    },
+    FieldValue,
+};
+
+impl Typename for MultiVertex {
+    fn typename(&self) -> &'static str {
+        match self {
+            Self::Binary(_) => "Binary",
+            Self::SourceLine(_) => "SourceLine",
+            Self::Item { vertex, .. } => vertex.typename(),
+        }
+    }
+}
+
+/// A context passed on to the adapter of a single binary, which sees the vertex from its binary
+/// inside the multi-binary vertex
+#[derive(Clone, Debug)]
+struct Scoped<V>(V);
+
+impl<V: AsVertex<MultiVertex>> AsVertex<Vertex> for Scoped<V> {
+    fn as_vertex(&self) -> Option<&Vertex> {
+        match self.0.as_vertex()? {
+            MultiVertex::Item { vertex, .. } => Some(vertex),
+            _ => None,
+        }
+    }
+
+    fn into_vertex(self) -> Option<Vertex> {
+        match self.0.into_vertex()? {
+            MultiVertex::Item { vertex, .. } => Some(vertex),
+            _ => None,
+        }
+    }
+}
+
+/// The binary the active vertex came from
+fn binary_of<V: AsVertex<MultiVertex>>(vertex: Option<&V>) -> Option<Arc<LoadedBinary>> {
+    match vertex?.as_vertex()? {
+        MultiVertex::Item { binary, .. } => Some(binary.clone()),
+        vertex => unreachable!("Invalid vertex: {:?}", vertex),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn binary_property(binary: &LoadedBinary, property_name: &str) -> FieldValue {
+    let adapter = &binary.adapter;
+    let artifact = binary.artifact.as_ref();
+    match property_name {
+        "path" => FieldValue::String(Arc::from(binary.path.display().to_string().as_str())),
+        "format" => adapter.format().unwrap_or_default().into(),
+        "arch" => adapter.architecture().unwrap_or_default().into(),
+        "x86_64Level" => adapter.x86_64_level().into(),
+        "buildId" => adapter
+            .build_id()
+            .map(|x| FieldValue::String(Arc::from(hex(&x).as_str())))
+            .unwrap_or(FieldValue::Null),
+        "package" => artifact.map_or(FieldValue::Null, |x| x.package.as_str().into()),
+        "targetName" => artifact.map_or(FieldValue::Null, |x| x.target_name.as_str().into()),
+        "targetKind" => artifact.map_or(FieldValue::Null, |x| x.target_kind.as_str().into()),
+        "isTest" => artifact.map_or(FieldValue::Null, |x| x.is_test.into()),
+        _ => {
+            unreachable!("attempted to read unexpected property '{property_name}' on type 'Binary'")
+        }
+    }
+}
+
+fn source_line_property(line: &super::SourceLine, property_name: &str) -> FieldValue {
+    match property_name {
+        "file" => FieldValue::String(Arc::from(line.file.display().to_string().as_str())),
+        "line" => FieldValue::Uint64(line.line as u64),
+        _ => unreachable!(
+            "attempted to read unexpected property '{property_name}' on type 'SourceLine'"
+        ),
+    }
+}
+
+/// A run of contexts whose active vertices are from the same binary, or have no active vertex
+type Batch<V> = (Option<Arc<LoadedBinary>>, Vec<DataContext<V>>);
+
+/// Splits the contexts into runs from the same binary so each run can be passed to the binary's
+/// adapter at once. Only consecutive contexts are grouped as the results have to stay in order.
+fn batches<'a, V: AsVertex<MultiVertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+) -> impl Iterator<Item = Batch<V>> + 'a {
+    let mut contexts = contexts.peekable();
+    iter::from_fn(move || {
+        let first = contexts.next()?;
+        let binary = binary_of(first.active_vertex::<V>());
+        let mut batch = vec![first];
+        while let Some(context) =
+            contexts.next_if(|x| match (&binary, binary_of(x.active_vertex::<V>())) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, &b),
+                (a, b) => a.is_none() && b.is_none(),
+            })
+        {
+            batch.push(context);
+        }
+        Some((binary, batch))
+    })
+}
+
+/// Resolves a property of vertices from the binaries, each run of contexts from the same binary
+/// is resolved by the binary's adapter
+fn resolve_item_property<'a, V: AsVertex<MultiVertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    type_name: Arc<str>,
+    property_name: Arc<str>,
+    resolve_info: ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    Box::new(batches(contexts).flat_map(move |(binary, batch)| {
+        let Some(binary) = binary else {
+            return Box::new(batch.into_iter().map(|x| (x, FieldValue::Null)))
+                as ContextOutcomeIterator<'a, V, FieldValue>;
+        };
+        let contexts = Box::new(batch.into_iter().map(|x| x.map(&mut Scoped)));
+        let resolved = trustfall::provider::Adapter::resolve_property(
+            &binary.adapter,
+            contexts,
+            &type_name,
+            &property_name,
+            &resolve_info,
+        );
+        Box::new(resolved.map(|(context, value)| (context.map(&mut |x: Scoped<V>| x.0), value)))
+    }))
+}
+
+/// Resolves an edge of vertices from the binaries, each run of contexts from the same binary is
+/// resolved by the binary's adapter and the neighbors are from the same binary
+fn resolve_item_edge<'a, V: AsVertex<MultiVertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    type_name: Arc<str>,
+    edge_name: Arc<str>,
+    parameters: EdgeParameters,
+    resolve_info: ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, MultiVertex>> {
+    Box::new(batches(contexts).flat_map(move |(binary, batch)| {
+        let Some(binary) = binary else {
+            return Box::new(batch.into_iter().map(|x| {
+                (
+                    x,
+                    Box::new(iter::empty()) as VertexIterator<'a, MultiVertex>,
+                )
+            }))
+                as ContextOutcomeIterator<'a, V, VertexIterator<'a, MultiVertex>>;
+        };
+        let contexts = Box::new(batch.into_iter().map(|x| x.map(&mut Scoped)));
+        let resolved = trustfall::provider::Adapter::resolve_neighbors(
+            &binary.adapter,
+            contexts,
+            &type_name,
+            &edge_name,
+            &parameters,
+            &resolve_info,
+        );
+        Box::new(resolved.map(move |(context, neighbors)| {
+            let binary = binary.clone();
+            let neighbors: VertexIterator<'a, MultiVertex> =
+                Box::new(neighbors.map(move |vertex| MultiVertex::Item {
+                    binary: binary.clone(),
+                    vertex,
+                }));
+            (context.map(&mut |x: Scoped<V>| x.0), neighbors)
+        }))
+    }))
+}
+
+impl<'a> trustfall::provider::Adapter<'a> for MultiAdapter {
+    type Vertex = MultiVertex;
+
+    fn resolve_starting_vertices(
+        &self,
+        edge_name: &Arc<str>,
+        _parameters: &EdgeParameters,
+        _resolve_info: &ResolveInfo,
+    ) -> VertexIterator<'a, Self::Vertex> {
+        match edge_name.as_ref() {
+            "binaries" => {
+                let binaries = self.binaries().to_vec();
+                Box::new(binaries.into_iter().map(MultiVertex::Binary))
+            }
+            "sourceLines" => {
+                let lines = self.source_lines().to_vec();
+                Box::new(lines.into_iter().map(MultiVertex::SourceLine))
+            }
+            _ => {
+                unreachable!(
+                    "attempted to resolve starting vertices for unexpected edge name: {edge_name}"
+                )
+            }
+        }
+    }
+
+    fn resolve_property<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        type_name: &Arc<str>,
+        property_name: &Arc<str>,
+        resolve_info: &ResolveInfo,
+    ) -> ContextOutcomeIterator<'a, V, FieldValue> {
+        if property_name.as_ref() == "__typename" {
+            return resolve_property_with(contexts, |vertex| vertex.typename().into());
+        }
+        let name = property_name.clone();
+        match type_name.as_ref() {
+            "Binary" => resolve_property_with(contexts, move |vertex| match vertex {
+                MultiVertex::Binary(binary) => binary_property(binary, &name),
+                vertex => unreachable!("Invalid vertex: {:?}", vertex),
+            }),
+            "SourceLine" => resolve_property_with(contexts, move |vertex| match vertex {
+                MultiVertex::SourceLine(line) => source_line_property(line, &name),
+                vertex => unreachable!("Invalid vertex: {:?}", vertex),
+            }),
+            _ => resolve_item_property(
+                contexts,
+                type_name.clone(),
+                property_name.clone(),
+                resolve_info.clone(),
+            ),
+        }
+    }
+
+    fn resolve_neighbors<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        type_name: &Arc<str>,
+        edge_name: &Arc<str>,
+        parameters: &EdgeParameters,
+        resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Self::Vertex>> {
+        match type_name.as_ref() {
+            "Binary" => {
+                let edge_name = edge_name.clone();
+                let parameters = parameters.clone();
+                resolve_neighbors_with(contexts, move |vertex| match vertex {
+                    MultiVertex::Binary(binary) => {
+                        let vertices = binary.adapter.resolve_root_edge(&edge_name, &parameters);
+                        let binary = binary.clone();
+                        Box::new(vertices.map(move |vertex| MultiVertex::Item {
+                            binary: binary.clone(),
+                            vertex,
+                        }))
+                    }
+                    vertex => unreachable!("Invalid vertex: {:?}", vertex),
+                })
+            }
+            "SourceLine" => match edge_name.as_ref() {
+                "binaries" => resolve_neighbors_with(contexts, |vertex| match vertex {
+                    MultiVertex::SourceLine(line) => {
+                        let binaries = line.binaries.clone();
+                        Box::new(binaries.into_iter().map(MultiVertex::Binary))
+                    }
+                    vertex => unreachable!("Invalid vertex: {:?}", vertex),
+                }),
+                _ => unreachable!(
+                    "attempted to resolve unexpected edge '{edge_name}' on type 'SourceLine'"
+                ),
+            },
+            _ => resolve_item_edge(
+                contexts,
+                type_name.clone(),
+                edge_name.clone(),
+                parameters.clone(),
+                resolve_info.clone(),
+            ),
+        }
+    }
+
+    fn resolve_coercion<V: AsVertex<Self::Vertex> + 'a>(
+        &self,
+        contexts: ContextIterator<'a, V>,
+        _type_name: &Arc<str>,
+        coerce_to_type: &Arc<str>,
+        _resolve_info: &ResolveInfo,
+    ) -> ContextOutcomeIterator<'a, V, bool> {
+        resolve_coercion_using_schema(contexts, Self::schema(), coerce_to_type.as_ref())
+    }
+}
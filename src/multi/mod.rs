@@ -0,0 +1,154 @@
+//! Querying several binaries at once, such as every executable and test binary in a workspace.
+//! Each binary is loaded into its own [`Adapter`], and queries against a binary's vertices are
+//! passed on to the adapter the vertex came from.
+use crate::adapter::{execute, Adapter, AdapterBuilder, QueryResults, Vertex};
+use crate::cargo::Artifact;
+use crate::error::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use trustfall::{FieldValue, Schema};
+
+mod adapter;
+
+#[cfg(test)]
+mod tests;
+
+static SCHEMA: OnceLock<Schema> = OnceLock::new();
+
+/// A binary loaded along with others
+#[derive(Clone, Debug)]
+pub struct LoadedBinary {
+    pub path: PathBuf,
+    pub adapter: Adapter,
+    /// The cargo target the binary was built from, if it was found from cargo's output
+    pub artifact: Option<Artifact>,
+}
+
+/// A line of source and the binaries with code from it
+#[derive(Clone, Debug)]
+pub struct SourceLine {
+    pub file: PathBuf,
+    pub line: usize,
+    pub binaries: Vec<Arc<LoadedBinary>>,
+}
+
+/// A vertex of the multi-binary schema. Vertices from a binary keep the binary they're from so
+/// their properties and edges can be resolved by its adapter.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum MultiVertex {
+    Binary(Arc<LoadedBinary>),
+    SourceLine(Arc<SourceLine>),
+    Item {
+        binary: Arc<LoadedBinary>,
+        vertex: Vertex,
+    },
+}
+
+/// Several loaded binaries queried together. Cloning is cheap as the binaries are shared between
+/// clones.
+#[derive(Clone, Debug, Default)]
+pub struct MultiAdapter {
+    binaries: Arc<Vec<Arc<LoadedBinary>>>,
+    source_lines: Arc<OnceLock<Vec<Arc<SourceLine>>>>,
+}
+
+impl MultiAdapter {
+    /// The schema for multi-binary queries: the single binary schema with its root edges moved
+    /// to the `Binary` type. It's checked against the single binary schema by the tests, so it has
+    /// to be updated along with it.
+    pub const SCHEMA_TEXT: &'static str = include_str!("./schema.graphql");
+
+    pub fn schema() -> &'static Schema {
+        SCHEMA.get_or_init(|| Schema::parse(Self::SCHEMA_TEXT).expect("not a valid schema"))
+    }
+
+    /// Queries the already loaded binaries, `path` is the path each was loaded from
+    pub fn new(binaries: impl IntoIterator<Item = (PathBuf, Adapter)>) -> Self {
+        Self::from_binaries(binaries.into_iter().map(|(path, adapter)| LoadedBinary {
+            path,
+            adapter,
+            artifact: None,
+        }))
+    }
+
+    pub fn from_binaries(binaries: impl IntoIterator<Item = LoadedBinary>) -> Self {
+        Self {
+            binaries: Arc::new(binaries.into_iter().map(Arc::new).collect()),
+            ..Default::default()
+        }
+    }
+
+    /// Loads the executables cargo built, see [`crate::cargo`] for finding them
+    pub fn load_artifacts(
+        artifacts: impl IntoIterator<Item = Artifact>,
+        builder: &AdapterBuilder,
+    ) -> Result<Self> {
+        let binaries = artifacts
+            .into_iter()
+            .map(|artifact| {
+                Ok(LoadedBinary {
+                    path: artifact.path.clone(),
+                    adapter: builder.clone().load(&artifact.path)?,
+                    artifact: Some(artifact),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::from_binaries(binaries))
+    }
+
+    /// Loads each of the binaries, failing if any of them can't be loaded
+    pub fn load<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let binaries = paths
+            .into_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                Ok((path.to_path_buf(), Adapter::load(path)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(binaries))
+    }
+
+    pub fn binaries(&self) -> &[Arc<LoadedBinary>] {
+        &self.binaries
+    }
+
+    /// Every file and line with code in any of the binaries, sorted by file then line
+    pub fn source_lines(&self) -> &[Arc<SourceLine>] {
+        self.source_lines.get_or_init(|| {
+            let mut lines: BTreeMap<(&Path, usize), Vec<Arc<LoadedBinary>>> = BTreeMap::new();
+            for binary in self.binaries.iter() {
+                let mut seen = BTreeSet::new();
+                for location in binary.adapter.debug_info().values().flatten() {
+                    if seen.insert((location.file.as_path(), location.line)) {
+                        lines
+                            .entry((location.file.as_path(), location.line))
+                            .or_default()
+                            .push(binary.clone());
+                    }
+                }
+            }
+            lines
+                .into_iter()
+                .map(|((file, line), binaries)| {
+                    Arc::new(SourceLine {
+                        file: file.to_path_buf(),
+                        line,
+                        binaries,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Runs a query against the binaries, returning an error if the query, its variables or the
+    /// parameters of its edges are invalid rather than panicking.
+    pub fn query(
+        self: &Arc<Self>,
+        query: &str,
+        variables: BTreeMap<Arc<str>, FieldValue>,
+    ) -> Result<QueryResults> {
+        execute(Self::schema(), self.clone(), query, variables)
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+use crate::error::Error;
+use trustfall::provider::check_adapter_invariants;
+
+fn run_query(adapter: &Arc<MultiAdapter>, query: &str) -> Vec<BTreeMap<Arc<str>, FieldValue>> {
+    adapter.query(query, BTreeMap::new()).unwrap().collect()
+}
+
+#[test]
+fn adapter_satisfies_trustfall_invariants() {
+    check_adapter_invariants(MultiAdapter::schema(), MultiAdapter::default());
+}
+
+#[test]
+fn invalid_binary_edge_parameters() {
+    let adapter = Arc::new(MultiAdapter::default());
+    let result = adapter.query(
+        r#"{ binaries { sizeReport(groupBy: "crate") { name @output } } }"#,
+        BTreeMap::new(),
+    );
+    assert!(matches!(result, Err(Error::Query(e)) if e.contains("unknown size grouping")));
+}
+
+/// The fields of a type in a schema
+fn type_fields<'a>(schema: &'a str, name: &str) -> &'a str {
+    let start = schema
+        .find(&format!("\ntype {name} {{\n"))
+        .unwrap_or_else(|| panic!("no type {name}"));
+    let fields = &schema[start..];
+    let fields = &fields[fields.find('{').unwrap() + 1..];
+    &fields[..fields.find("\n}").unwrap()]
+}
+
+#[test]
+fn schema_matches_single_binary_schema() {
+    let single = Adapter::SCHEMA_TEXT;
+    let multi = MultiAdapter::SCHEMA_TEXT;
+    let root = type_fields(single, "RootSchemaQuery");
+    assert!(
+        type_fields(multi, "Binary").ends_with(root),
+        "the Binary type doesn't end with the single binary root fields"
+    );
+    let types = single
+        .lines()
+        .filter_map(|x| x.strip_prefix("type ")?.strip_suffix(" {"))
+        .filter(|x| *x != "RootSchemaQuery");
+    for name in types {
+        assert_eq!(
+            type_fields(multi, name),
+            type_fields(single, name),
+            "type {name} differs from the single binary schema"
+        );
+    }
+}
+
+#[test]
+fn queries_each_binary() {
+    let path = std::env::current_exe().unwrap();
+    let adapter = Arc::new(MultiAdapter::load([&path, &path]).unwrap());
+    let results = run_query(
+        &adapter,
+        r#"
+        {
+            binaries {
+                path @output
+                format @output
+                arch @output
+                x86_64Level @output
+                buildId @output
+                getFunction(name: "object_trustfall_adapter::multi::MultiAdapter::source_lines") {
+                    name @output
+                    instructions @fold @transform(op: "count") @output(name: "instructionCount")
+                }
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0], results[1]);
+    assert_eq!(
+        results[0]["path"],
+        FieldValue::from(path.display().to_string())
+    );
+    assert_eq!(results[0]["format"], FieldValue::from("elf"));
+    assert_eq!(results[0]["arch"], FieldValue::from("x86_64"));
+    let level = Adapter::load(&path).unwrap().x86_64_level();
+    assert!((1..=4).contains(&level));
+    assert_eq!(results[0]["x86_64Level"], FieldValue::from(level));
+    assert!(results[0]["buildId"]
+        .as_str()
+        .is_some_and(|x| x.len() == 40));
+    assert!(results[0]["instructionCount"].as_u64().unwrap() > 0);
+
+    let variables = [(Arc::from("file"), FieldValue::from("src/multi/mod.rs"))]
+        .into_iter()
+        .collect();
+    let results = adapter
+        .query(
+            r#"
+            {
+                sourceLines {
+                    file @filter(op: "has_suffix", value: ["$file"])
+                    line @output
+                    binaries @fold @transform(op: "count") @output(name: "binaryCount")
+                }
+            }
+            "#,
+            variables,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert!(!results.is_empty());
+    assert!(results
+        .iter()
+        .all(|x| x["binaryCount"] == FieldValue::Uint64(2)));
+}
+
+#[test]
+fn cargo_artifacts() {
+    let artifact = Artifact {
+        path: std::env::current_exe().unwrap(),
+        package: "object-trustfall-adapter".to_string(),
+        target_name: "object_trustfall_adapter".to_string(),
+        target_kind: "lib".to_string(),
+        is_test: true,
+    };
+    let adapter = MultiAdapter::load_artifacts([artifact], &AdapterBuilder::new()).unwrap();
+    let results = run_query(
+        &Arc::new(adapter),
+        r#"
+        {
+            binaries {
+                package @output
+                targetKind @output
+                isTest @output
+            }
+        }
+        "#,
+    );
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0]["package"],
+        FieldValue::from("object-trustfall-adapter")
+    );
+    assert_eq!(results[0]["targetKind"], FieldValue::from("lib"));
+    assert_eq!(results[0]["isTest"], FieldValue::Boolean(true));
+}
@@ -0,0 +1,229 @@
+//! Helpers for running queries from outside Rust: parsing variables given as text and writing
+//! results in a few common formats.
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use trustfall::{FieldValue, TransparentValue};
+
+pub type QueryRow = BTreeMap<Arc<str>, FieldValue>;
+
+/// How query results are written out.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One JSON object per result row
+    #[default]
+    JsonLines,
+    /// Aligned columns with a header, all results are read before anything is written
+    Table,
+    /// Comma separated values with a header row
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "jsonl" | "json" => Ok(Self::JsonLines),
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "unknown output format '{}', expected jsonl, table or csv",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::JsonLines => write!(f, "jsonl"),
+            Self::Table => write!(f, "table"),
+            Self::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+/// Parses a value given on the command line. Anything which is valid JSON is used as that
+/// (numbers, booleans, lists), `0x` prefixed hex is an integer and everything else is a string.
+pub fn parse_value(value: &str) -> FieldValue {
+    if let Some(hex) = value.strip_prefix("0x") {
+        if let Ok(x) = u64::from_str_radix(hex, 16) {
+            return FieldValue::Uint64(x);
+        }
+    }
+    match serde_json::from_str::<TransparentValue>(value) {
+        Ok(x) => x.into(),
+        Err(_) => FieldValue::String(value.into()),
+    }
+}
+
+/// Parses a `name=value` variable, see [`parse_value`] for how the value is interpreted.
+pub fn parse_variable(variable: &str) -> Result<(Arc<str>, FieldValue)> {
+    let (name, value) = variable
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| {
+            Error::Query(format!(
+                "invalid variable '{}', expected name=value",
+                variable
+            ))
+        })?;
+    Ok((name.into(), parse_value(value)))
+}
+
+/// Formats a value for the table and CSV outputs
+pub fn display_value(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Null => String::new(),
+        FieldValue::Int64(x) => x.to_string(),
+        FieldValue::Uint64(x) => x.to_string(),
+        FieldValue::Float64(x) => x.to_string(),
+        FieldValue::String(x) | FieldValue::Enum(x) => x.to_string(),
+        FieldValue::Boolean(x) => x.to_string(),
+        FieldValue::List(x) => {
+            let items = x.iter().map(display_value).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+        _ => format!("{:?}", value),
+    }
+}
+
+/// A single row as a JSON object
+pub fn to_json(row: &QueryRow) -> serde_json::Value {
+    let row = row
+        .iter()
+        .map(|(k, v)| (k.clone(), TransparentValue::from(v.clone())))
+        .collect::<BTreeMap<_, _>>();
+    serde_json::to_value(row).unwrap_or_default()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the results in the given format, JSON lines and CSV are written as each row arrives.
+/// Columns are taken from the first row.
+pub fn write_results(
+    results: impl IntoIterator<Item = QueryRow>,
+    format: OutputFormat,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut results = results.into_iter().peekable();
+    match format {
+        OutputFormat::JsonLines => {
+            for row in results {
+                writeln!(out, "{}", to_json(&row))?;
+            }
+        }
+        OutputFormat::Csv => {
+            let Some(first) = results.peek() else {
+                return Ok(());
+            };
+            let columns = first.keys().cloned().collect::<Vec<_>>();
+            let header = columns.iter().map(|x| csv_field(x)).collect::<Vec<_>>();
+            writeln!(out, "{}", header.join(","))?;
+            for row in results {
+                let fields = columns
+                    .iter()
+                    .map(|x| csv_field(&display_value(row.get(x).unwrap_or_default())))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        OutputFormat::Table => {
+            let Some(first) = results.peek() else {
+                return Ok(());
+            };
+            let columns = first.keys().cloned().collect::<Vec<_>>();
+            let rows = results
+                .map(|row| {
+                    columns
+                        .iter()
+                        .map(|x| display_value(row.get(x).unwrap_or_default()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let mut widths = columns.iter().map(|x| x.len()).collect::<Vec<_>>();
+            for row in &rows {
+                for (width, field) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(field.len());
+                }
+            }
+            let mut write_row = |fields: &[&str]| {
+                let line = fields
+                    .iter()
+                    .zip(&widths)
+                    .map(|(field, width)| format!("{:width$}", field, width = width))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", line.join("  ").trim_end())
+            };
+            write_row(&columns.iter().map(|x| x.as_ref()).collect::<Vec<_>>())?;
+            let rule = widths.iter().map(|x| "-".repeat(*x)).collect::<Vec<_>>();
+            write_row(&rule.iter().map(|x| x.as_str()).collect::<Vec<_>>())?;
+            for row in &rows {
+                write_row(&row.iter().map(|x| x.as_str()).collect::<Vec<_>>())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<QueryRow> {
+        vec![
+            BTreeMap::from([
+                ("name".into(), FieldValue::String("main".into())),
+                ("address".into(), FieldValue::Uint64(0x1000)),
+            ]),
+            BTreeMap::from([
+                ("name".into(), FieldValue::String("a, \"b\"".into())),
+                ("address".into(), FieldValue::Null),
+            ]),
+        ]
+    }
+
+    fn write(format: OutputFormat) -> String {
+        let mut out = vec![];
+        write_results(rows(), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn variables() {
+        let (name, value) = parse_variable("address=0x10").unwrap();
+        assert_eq!((name.as_ref(), value), ("address", FieldValue::Uint64(16)));
+        assert_eq!(parse_value("12"), FieldValue::Int64(12));
+        assert_eq!(parse_value("main"), FieldValue::String("main".into()));
+        assert_eq!(parse_value("\"12\""), FieldValue::String("12".into()));
+        assert!(parse_variable("=1").is_err());
+        assert!(parse_variable("name").is_err());
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            write(OutputFormat::JsonLines),
+            "{\"address\":4096,\"name\":\"main\"}\n{\"address\":null,\"name\":\"a, \\\"b\\\"\"}\n"
+        );
+        assert_eq!(
+            write(OutputFormat::Csv),
+            "address,name\n4096,main\n,\"a, \"\"b\"\"\"\n"
+        );
+        assert_eq!(
+            write(OutputFormat::Table),
+            "address  name\n-------  ------\n4096     main\n         a, \"b\"\n"
+        );
+    }
+}
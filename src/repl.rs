@@ -0,0 +1,613 @@
+//! Support for interactive querying: schema aware completion, telling when a query spanning
+//! several lines is finished, parsing REPL commands and describing how a query will be run.
+use crate::error::{Error, Result};
+use crate::query::{self, OutputFormat};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use trustfall::{execute_query, FieldValue, Schema, SchemaAdapter, TransparentValue};
+use trustfall_core::frontend;
+use trustfall_core::ir::{Argument, EdgeParameters, Eid, IRQueryComponent, Operation, Vid};
+
+/// Directives which can follow a field
+const DIRECTIVES: &[&str] = &[
+    "filter",
+    "fold",
+    "optional",
+    "output",
+    "recurse",
+    "tag",
+    "transform",
+];
+
+/// Commands accepted by the REPL
+pub const COMMANDS: &[&str] = &[
+    ":explain", ":format", ":help", ":load", ":quit", ":unset", ":vars",
+];
+
+pub const HELP: &str = "\
+Enter a query to run it, it's run once all its braces are closed. Commands:
+  :vars [NAME=VALUE...]   set query variables, or list them if none are given
+  :unset NAME...          remove query variables
+  :explain [QUERY]        show how a query will be run, the last query if none is given
+  :load PATH              load another binary
+  :format FORMAT          print results as jsonl, table or csv
+  :help                   show this message
+  :quit                   exit";
+
+/// A field of a vertex type
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SchemaField {
+    Property { name: Arc<str>, ty: Arc<str> },
+    Edge { name: Arc<str>, target: Arc<str> },
+}
+
+impl SchemaField {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Property { name, .. } | Self::Edge { name, .. } => name,
+        }
+    }
+}
+
+/// The vertex types and their fields, read from a schema with Trustfall's schema adapter.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SchemaIndex {
+    /// Edges which a query can start from
+    pub entrypoints: Vec<SchemaField>,
+    /// Fields of each vertex type
+    pub types: BTreeMap<Arc<str>, Vec<SchemaField>>,
+}
+
+const VERTEX_TYPE_QUERY: &str = r#"
+{
+    VertexType {
+        name @output
+        property @fold {
+            name @output(name: "properties")
+            type @output(name: "property_types")
+        }
+        edge @fold {
+            name @output(name: "edges")
+            target {
+                name @output(name: "edge_targets")
+            }
+        }
+    }
+}
+"#;
+
+const ENTRYPOINT_QUERY: &str = r#"
+{
+    Entrypoint {
+        name @output
+        target {
+            name @output(name: "target")
+        }
+    }
+}
+"#;
+
+fn strings(value: Option<&FieldValue>) -> Vec<Arc<str>> {
+    match value {
+        Some(FieldValue::List(x)) => x.iter().filter_map(|x| x.as_arc_str().cloned()).collect(),
+        _ => vec![],
+    }
+}
+
+fn string(value: Option<&FieldValue>) -> Arc<str> {
+    value
+        .and_then(|x| x.as_arc_str())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The vertex type a block of the query is selecting fields from
+#[derive(Clone, Debug, PartialEq)]
+enum Block {
+    Root,
+    Vertex(Arc<str>),
+    /// The block follows an edge which isn't in the schema
+    Unknown,
+}
+
+/// A `{` which hasn't been closed yet
+#[derive(Debug)]
+struct OpenBlock {
+    /// The edge the block follows
+    field: Option<Arc<str>>,
+    /// Type from `... on Type`
+    coerce_to: Option<Arc<str>>,
+}
+
+/// What is open at the end of a partial query
+#[derive(Debug, Default)]
+struct Scan {
+    blocks: Vec<OpenBlock>,
+    in_arguments: bool,
+    in_string: bool,
+    /// Just after `... on`
+    expecting_type: bool,
+}
+
+impl Scan {
+    fn new(text: &str) -> Self {
+        let mut scan = Self::default();
+        let mut field = None;
+        let mut coerce_to = None;
+        let mut parens = 0;
+        let mut directive = false;
+        let mut spread = false;
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if scan.in_string {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => scan.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '#' => while chars.next_if(|(_, c)| *c != '\n').is_some() {},
+                '"' => scan.in_string = true,
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ if parens > 0 => {}
+                '{' => scan.blocks.push(OpenBlock {
+                    field: field.take(),
+                    coerce_to: coerce_to.take(),
+                }),
+                '}' => {
+                    scan.blocks.pop();
+                }
+                '@' => directive = true,
+                '.' => spread = true,
+                ':' => field = None,
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut end = i + c.len_utf8();
+                    while let Some((j, c)) =
+                        chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+                    {
+                        end = j + c.len_utf8();
+                    }
+                    let ident = &text[i..end];
+                    if directive {
+                        directive = false;
+                    } else if scan.expecting_type {
+                        // Still expecting it if the type name is being typed
+                        scan.expecting_type = end == text.len();
+                        coerce_to = Some(ident.into());
+                    } else if spread && ident == "on" {
+                        spread = false;
+                        scan.expecting_type = true;
+                    } else {
+                        field = Some(ident.into());
+                    }
+                }
+                _ => {}
+            }
+        }
+        scan.in_arguments = parens > 0;
+        scan
+    }
+}
+
+impl SchemaIndex {
+    pub fn new(schema: &Schema) -> Result<Self> {
+        let schema_schema =
+            Schema::parse(SchemaAdapter::schema_text()).map_err(|e| Error::Query(e.to_string()))?;
+        let adapter = Arc::new(SchemaAdapter::new(schema));
+        let run = |query: &str| {
+            execute_query(
+                &schema_schema,
+                adapter.clone(),
+                query,
+                BTreeMap::<Arc<str>, FieldValue>::new(),
+            )
+            .map_err(|e| Error::Query(e.to_string()))
+        };
+
+        let mut index = Self::default();
+        for row in run(VERTEX_TYPE_QUERY)? {
+            let properties = strings(row.get("properties"))
+                .into_iter()
+                .zip(strings(row.get("property_types")))
+                .map(|(name, ty)| SchemaField::Property { name, ty });
+            let edges = strings(row.get("edges"))
+                .into_iter()
+                .zip(strings(row.get("edge_targets")))
+                .map(|(name, target)| SchemaField::Edge { name, target });
+            index
+                .types
+                .insert(string(row.get("name")), properties.chain(edges).collect());
+        }
+        for row in run(ENTRYPOINT_QUERY)? {
+            index.entrypoints.push(SchemaField::Edge {
+                name: string(row.get("name")),
+                target: string(row.get("target")),
+            });
+        }
+        Ok(index)
+    }
+
+    fn fields(&self, block: &Block) -> &[SchemaField] {
+        match block {
+            Block::Root => &self.entrypoints,
+            Block::Vertex(ty) => self.types.get(ty).map(|x| x.as_slice()).unwrap_or_default(),
+            Block::Unknown => &[],
+        }
+    }
+
+    /// The vertex type of the innermost block open at the end of the scanned text
+    fn innermost_block(&self, scan: &Scan) -> Option<Block> {
+        let mut blocks = scan.blocks.iter();
+        blocks.next()?;
+        let mut block = Block::Root;
+        for open in blocks {
+            block = match (&open.coerce_to, &open.field) {
+                (Some(ty), _) => Block::Vertex(ty.clone()),
+                (None, Some(field)) => self
+                    .fields(&block)
+                    .iter()
+                    .find_map(|x| match x {
+                        SchemaField::Edge { name, target } if name == field => {
+                            Some(Block::Vertex(target.clone()))
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(Block::Unknown),
+                (None, None) => Block::Unknown,
+            };
+        }
+        Some(block)
+    }
+
+    /// Completions for the word ending at the end of `text`, along with the offset in `text` the
+    /// word starts at. Inside a query these are the edges and properties of the vertex type at
+    /// that point, found by following the edges of the enclosing blocks from the root.
+    pub fn complete(&self, text: &str) -> (usize, Vec<String>) {
+        let start = text
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map(|x| x + c_len(text, x))
+            .unwrap_or_default();
+        let word = &text[start..];
+        let matching = |candidates: &mut dyn Iterator<Item = &str>| {
+            let mut candidates = candidates
+                .filter(|x| x.starts_with(word))
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>();
+            candidates.sort();
+            candidates.dedup();
+            candidates
+        };
+
+        let trimmed = text.trim_start();
+        if trimmed.starts_with(':') && !trimmed.contains(char::is_whitespace) {
+            let start = text.len() - trimmed.len();
+            let candidates = COMMANDS.iter().filter(|x| x.starts_with(trimmed));
+            return (start, candidates.map(|x| x.to_string()).collect());
+        }
+        let scan = Scan::new(text);
+        if scan.in_arguments || scan.in_string {
+            return (start, vec![]);
+        }
+        if text[..start].ends_with('@') {
+            return (start, matching(&mut DIRECTIVES.iter().copied()));
+        }
+        if scan.expecting_type {
+            return (start, matching(&mut self.types.keys().map(|x| x.as_ref())));
+        }
+        let candidates = match self.innermost_block(&scan) {
+            None => vec![],
+            Some(Block::Root) => matching(&mut self.entrypoints.iter().map(|x| x.name())),
+            Some(block) => {
+                let fields = self.fields(&block).iter().map(|x| x.name());
+                let typename = (block != Block::Unknown).then_some("__typename");
+                matching(&mut fields.chain(typename))
+            }
+        };
+        (start, candidates)
+    }
+}
+
+fn c_len(text: &str, index: usize) -> usize {
+    text[index..].chars().next().map_or(1, char::len_utf8)
+}
+
+/// Whether the input is ready to run rather than being the start of a longer query, which is
+/// the case once every brace opened outside of strings and comments has been closed.
+pub fn is_complete(input: &str) -> bool {
+    let scan = Scan::new(input);
+    scan.blocks.is_empty() && !scan.in_string
+}
+
+/// A line of input to the REPL
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Empty,
+    Query(String),
+    /// Set the given variables, or list them all if there aren't any
+    Vars(Vec<(Arc<str>, FieldValue)>),
+    Unset(Vec<String>),
+    /// Explain the given query or the last one run
+    Explain(Option<String>),
+    Load(PathBuf),
+    Format(OutputFormat),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let Some(command) = input.strip_prefix(':') else {
+            if input.is_empty() {
+                return Ok(Self::Empty);
+            }
+            return Ok(Self::Query(input.to_string()));
+        };
+        let (name, rest) = command
+            .split_once(char::is_whitespace)
+            .map(|(name, rest)| (name, rest.trim()))
+            .unwrap_or((command, ""));
+        let missing = |what: &str| Error::Query(format!(":{} expects {}", name, what));
+        match name {
+            "vars" => rest
+                .split_whitespace()
+                .map(query::parse_variable)
+                .collect::<Result<Vec<_>>>()
+                .map(Self::Vars),
+            "unset" if !rest.is_empty() => Ok(Self::Unset(
+                rest.split_whitespace().map(|x| x.to_string()).collect(),
+            )),
+            "unset" => Err(missing("variable names")),
+            "explain" => Ok(Self::Explain((!rest.is_empty()).then(|| rest.to_string()))),
+            "load" if !rest.is_empty() => Ok(Self::Load(rest.into())),
+            "load" => Err(missing("a path")),
+            "format" => rest.parse().map(Self::Format).map_err(Error::Query),
+            "help" => Ok(Self::Help),
+            "quit" | "q" | "exit" => Ok(Self::Quit),
+            _ => Err(Error::Query(format!(
+                "unknown command ':{}', try :help",
+                name
+            ))),
+        }
+    }
+}
+
+/// The name of a filter operation as written in `@filter`, with its operands.
+fn operation<L>(op: &Operation<L, Argument>) -> Option<(&'static str, &L, Option<&Argument>)>
+where
+    L: Debug + Clone + PartialEq + Eq,
+{
+    Some(match op {
+        Operation::IsNull(l) => ("is_null", l, None),
+        Operation::IsNotNull(l) => ("is_not_null", l, None),
+        Operation::Equals(l, r) => ("=", l, Some(r)),
+        Operation::NotEquals(l, r) => ("!=", l, Some(r)),
+        Operation::LessThan(l, r) => ("<", l, Some(r)),
+        Operation::LessThanOrEqual(l, r) => ("<=", l, Some(r)),
+        Operation::GreaterThan(l, r) => (">", l, Some(r)),
+        Operation::GreaterThanOrEqual(l, r) => (">=", l, Some(r)),
+        Operation::Contains(l, r) => ("contains", l, Some(r)),
+        Operation::NotContains(l, r) => ("not_contains", l, Some(r)),
+        Operation::OneOf(l, r) => ("one_of", l, Some(r)),
+        Operation::NotOneOf(l, r) => ("not_one_of", l, Some(r)),
+        Operation::HasPrefix(l, r) => ("has_prefix", l, Some(r)),
+        Operation::NotHasPrefix(l, r) => ("not_has_prefix", l, Some(r)),
+        Operation::HasSuffix(l, r) => ("has_suffix", l, Some(r)),
+        Operation::NotHasSuffix(l, r) => ("not_has_suffix", l, Some(r)),
+        Operation::HasSubstring(l, r) => ("has_substring", l, Some(r)),
+        Operation::NotHasSubstring(l, r) => ("not_has_substring", l, Some(r)),
+        Operation::RegexMatches(l, r) => ("regex", l, Some(r)),
+        Operation::NotRegexMatches(l, r) => ("not_regex", l, Some(r)),
+        _ => return None,
+    })
+}
+
+fn filter(field: &str, op: &Operation<impl Debug + Clone + Eq, Argument>) -> String {
+    match operation(op) {
+        Some((name, _, None)) => format!("filter {} {}", field, name),
+        Some((name, _, Some(Argument::Variable(var)))) => {
+            format!("filter {} {} ${}", field, name, var.variable_name)
+        }
+        Some((name, _, Some(Argument::Tag(tag)))) => {
+            format!("filter {} {} tagged {}", field, name, tag.field_name())
+        }
+        None => format!("filter {:?}", op),
+    }
+}
+
+fn parameters(parameters: &EdgeParameters) -> String {
+    if parameters.is_empty() {
+        return String::new();
+    }
+    let parameters = parameters
+        .iter()
+        .map(|(name, value)| {
+            let value = serde_json::to_string(&TransparentValue::from(value.clone()));
+            format!("{}: {}", name, value.unwrap_or_default())
+        })
+        .collect::<Vec<_>>();
+    format!("({})", parameters.join(", "))
+}
+
+fn explain_vertex(component: &IRQueryComponent, vid: Vid, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth + 1);
+    let vertex = &component.vertices[&vid];
+    for op in &vertex.filters {
+        let field = &operation(op).map_or("", |(_, l, _)| l.field_name.as_ref());
+        let _ = writeln!(out, "{}{}", indent, filter(field, op));
+    }
+    for (name, field) in component.outputs.iter().filter(|x| x.1.vertex_id == vid) {
+        let _ = writeln!(
+            out,
+            "{}output {} = {}: {}",
+            indent, name, field.field_name, field.field_type
+        );
+    }
+
+    let mut edges = BTreeMap::<Eid, String>::new();
+    for edge in component.edges.values().filter(|x| x.from_vid == vid) {
+        let mut line = format!("{}{}", edge.edge_name, parameters(&edge.parameters));
+        if edge.optional {
+            line.push_str(" @optional");
+        }
+        if let Some(recursive) = &edge.recursive {
+            let _ = write!(line, " @recurse(depth: {})", recursive.depth);
+        }
+        let target = &component.vertices[&edge.to_vid];
+        let mut edge_out = format!("{}{} -> {}\n", indent, line, vertex_type(target));
+        explain_vertex(component, edge.to_vid, depth + 1, &mut edge_out);
+        edges.insert(edge.eid, edge_out);
+    }
+    for fold in component.folds.values().filter(|x| x.from_vid == vid) {
+        let target = &fold.component.vertices[&fold.to_vid];
+        let mut fold_out = format!(
+            "{}{}{} @fold -> {}\n",
+            indent,
+            fold.edge_name,
+            parameters(&fold.parameters),
+            vertex_type(target)
+        );
+        let inner = "  ".repeat(depth + 2);
+        for op in &fold.post_filters {
+            let field = operation(op).map_or("", |(_, l, _)| l.field_name());
+            let _ = writeln!(fold_out, "{}{}", inner, filter(field, op));
+        }
+        for (name, kind) in &fold.fold_specific_outputs {
+            let _ = writeln!(
+                fold_out,
+                "{}output {} = {}: {}",
+                inner,
+                name,
+                kind.field_name(),
+                kind.field_type()
+            );
+        }
+        explain_vertex(&fold.component, fold.to_vid, depth + 1, &mut fold_out);
+        edges.insert(fold.eid, fold_out);
+    }
+    // Edges and folds are stored separately in the IR, sort them back into query order
+    for edge in edges.into_values() {
+        out.push_str(&edge);
+    }
+}
+
+fn vertex_type(vertex: &trustfall_core::ir::IRVertex) -> String {
+    match &vertex.coerced_from_type {
+        Some(from) => format!("{} (coerced from {})", vertex.type_name, from),
+        None => vertex.type_name.to_string(),
+    }
+}
+
+/// Describes how a query will be run: the edges it follows from the root and the filters and
+/// outputs at each vertex, followed by the variables it needs.
+pub fn explain(schema: &Schema, query: &str) -> Result<String> {
+    let query = frontend::parse(schema, query).map_err(|e| Error::Query(e.to_string()))?;
+    let ir = &query.ir_query;
+    let component = &ir.root_component;
+    let root = &component.vertices[&component.root];
+    let mut out = format!(
+        "{}{} -> {}\n",
+        ir.root_name,
+        parameters(&ir.root_parameters),
+        vertex_type(root)
+    );
+    explain_vertex(component, component.root, 0, &mut out);
+    if !ir.variables.is_empty() {
+        out.push_str("variables:\n");
+        for (name, ty) in &ir.variables {
+            let _ = writeln!(out, "  ${}: {}", name, ty);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::Adapter;
+
+    fn complete(text: &str) -> Vec<String> {
+        let index = SchemaIndex::new(Adapter::schema()).unwrap();
+        index.complete(text).1
+    }
+
+    #[test]
+    fn schema_completion() {
+        let index = SchemaIndex::new(Adapter::schema()).unwrap();
+        assert_eq!(index.complete("{ sections { na").0, 13);
+        assert_eq!(complete("{ sec"), ["sections"]);
+        assert_eq!(complete("{\n  sections {\n    na"), ["name"]);
+        assert_eq!(
+            complete("{ functions { name @output instructions { addr"),
+            ["address"]
+        );
+        assert_eq!(complete("{ sections { name @out"), ["output"]);
+        assert_eq!(complete("{ sections { n: name } sec"), ["sections"]);
+        assert_eq!(complete("{ getSection(name: \"{ sec"), Vec::<String>::new());
+        assert_eq!(complete("{ notAnEdge { na"), Vec::<String>::new());
+        assert!(complete("{ sections { ").contains(&"__typename".to_string()));
+        assert_eq!(complete(":ex"), [":explain"]);
+    }
+
+    #[test]
+    fn multi_line_input() {
+        assert!(is_complete(""));
+        assert!(!is_complete("{\n  sections {"));
+        assert!(!is_complete(
+            "{ sections { name @filter(op: \"=\", value: [\"}\"])"
+        ));
+        assert!(is_complete("{\n  sections { # }\n name @output }\n}"));
+        assert!(is_complete(":vars name=main"));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(Command::parse("  ").unwrap(), Command::Empty);
+        assert_eq!(
+            Command::parse(":vars a=1 b=0x10").unwrap(),
+            Command::Vars(vec![
+                ("a".into(), FieldValue::Int64(1)),
+                ("b".into(), FieldValue::Uint64(16))
+            ])
+        );
+        assert_eq!(Command::parse(":vars").unwrap(), Command::Vars(vec![]));
+        assert_eq!(
+            Command::parse(":load /bin/true").unwrap(),
+            Command::Load("/bin/true".into())
+        );
+        assert_eq!(
+            Command::parse(":format csv").unwrap(),
+            Command::Format(OutputFormat::Csv)
+        );
+        assert_eq!(Command::parse(":explain").unwrap(), Command::Explain(None));
+        assert!(Command::parse(":load").is_err());
+        assert!(Command::parse(":frobnicate").is_err());
+    }
+
+    #[test]
+    fn explain_query() {
+        let query = r#"
+        {
+            sections {
+                name @output @filter(op: "=", value: ["$name"])
+                data: address @output
+            }
+        }"#;
+        let explained = explain(Adapter::schema(), query).unwrap();
+        assert_eq!(
+            explained,
+            "sections -> Section\n  filter name = $name\n  output data = address: Int!\n  \
+             output name = name: String!\nvariables:\n  $name: String!\n"
+        );
+        assert!(explain(Adapter::schema(), "{ notAnEdge { name @output } }").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Serves queries over HTTP so tools which aren't written in Rust can query loaded binaries.
+//!
+//! Endpoints:
+//! - `POST /query` takes a JSON object with the `query`, its `variables` and the name of the
+//!   `binary` to run it on, which can be left out if only one is loaded. Results are streamed
+//!   back as JSON lines as the query runs.
+//! - `GET /schema` returns the schema in GraphQL SDL and `GET /schema.json` the vertex types and
+//!   their fields as JSON.
+//! - `GET /binaries` lists the names of the loaded binaries.
+//!
+//! Errors are returned as a JSON object with an `error` message.
+use crate::adapter::{Adapter, QueryResults};
+use crate::error::{Error, Result};
+use crate::query;
+use crate::repl::SchemaIndex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+use trustfall::TransparentValue;
+
+/// Body of a `POST /query` request
+#[derive(Debug, Deserialize)]
+pub struct QueryRequest {
+    pub query: String,
+    #[serde(default)]
+    pub variables: BTreeMap<Arc<str>, TransparentValue>,
+    /// Name of the binary to query, needed when more than one is loaded
+    pub binary: Option<String>,
+}
+
+/// Serves queries on a set of named binaries.
+pub struct Server {
+    http: tiny_http::Server,
+    binaries: Arc<BTreeMap<String, Arc<Adapter>>>,
+}
+
+/// Writes each result row as a line of JSON as it's read, so rows are sent as they're found
+/// instead of after the whole query has run.
+struct RowReader {
+    rows: QueryResults,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl Read for RowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.line.len() {
+            let Some(row) = self.rows.next() else {
+                return Ok(0);
+            };
+            self.line = query::to_json(&row).to_string().into_bytes();
+            self.line.push(b'\n');
+            self.position = 0;
+        }
+        let len = buf.len().min(self.line.len() - self.position);
+        buf[..len].copy_from_slice(&self.line[self.position..][..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("header is valid")
+}
+
+fn json_response(status: u16, value: &serde_json::Value) -> Response<io::Cursor<Vec<u8>>> {
+    Response::from_data(value.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status: u16, message: impl ToString) -> Response<io::Cursor<Vec<u8>>> {
+    json_response(status, &serde_json::json!({ "error": message.to_string() }))
+}
+
+impl Server {
+    /// Listens on the address, use port 0 to pick any free port. Each binary is queried by the
+    /// name it's given here.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        binaries: impl IntoIterator<Item = (String, Arc<Adapter>)>,
+    ) -> Result<Self> {
+        let http = tiny_http::Server::http(addr).map_err(|e| Error::Io(io::Error::other(e)))?;
+        Ok(Self {
+            http,
+            binaries: Arc::new(binaries.into_iter().collect()),
+        })
+    }
+
+    /// The address the server is listening on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Handles requests until [`Server::shutdown`] is called, each request on its own thread.
+    /// Failures to send a response, e.g. when the client has gone away, are passed to
+    /// `on_error`.
+    pub fn run(&self, on_error: impl Fn(Error) + Clone + Send + 'static) {
+        for request in self.http.incoming_requests() {
+            let binaries = self.binaries.clone();
+            let on_error = on_error.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle(&binaries, request) {
+                    on_error(e.into());
+                }
+            });
+        }
+    }
+
+    /// Stops [`Server::run`] once it's finished accepting the current request.
+    pub fn shutdown(&self) {
+        self.http.unblock();
+    }
+}
+
+fn handle(binaries: &BTreeMap<String, Arc<Adapter>>, mut request: Request) -> io::Result<()> {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/schema") => request.respond(
+            Response::from_string(Adapter::SCHEMA_TEXT)
+                .with_header(header("Content-Type", "application/graphql")),
+        ),
+        (Method::Get, "/schema.json") => {
+            let response = match SchemaIndex::new(Adapter::schema()) {
+                Ok(index) => json_response(200, &serde_json::to_value(index)?),
+                Err(e) => error_response(500, e),
+            };
+            request.respond(response)
+        }
+        (Method::Get, "/binaries") => request.respond(json_response(
+            200,
+            &serde_json::json!(binaries.keys().collect::<Vec<_>>()),
+        )),
+        (Method::Post, "/query") => {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                return request.respond(error_response(400, e));
+            }
+            match run_query(binaries, &body) {
+                Ok(rows) => request.respond(Response::new(
+                    StatusCode(200),
+                    vec![header("Content-Type", "application/x-ndjson")],
+                    RowReader {
+                        rows,
+                        line: vec![],
+                        position: 0,
+                    },
+                    None,
+                    None,
+                )),
+                Err(e) => request.respond(error_response(e.status(), e)),
+            }
+        }
+        (_, "/schema" | "/schema.json" | "/binaries" | "/query") => {
+            request.respond(error_response(405, "method not allowed"))
+        }
+        _ => request.respond(error_response(404, format!("no endpoint at {}", path))),
+    }
+}
+
+/// Why a `POST /query` request failed, each mapped to its HTTP status
+#[derive(Debug, thiserror::Error)]
+enum QueryError {
+    #[error("invalid request: {0}")]
+    InvalidRequest(#[from] serde_json::Error),
+    #[error("no binary named '{0}'")]
+    UnknownBinary(String),
+    #[error("binary must be given when several are loaded")]
+    MissingBinary,
+    #[error(transparent)]
+    Query(#[from] Error),
+}
+
+impl QueryError {
+    fn status(&self) -> u16 {
+        match self {
+            Self::UnknownBinary(_) => 404,
+            Self::InvalidRequest(_) | Self::MissingBinary | Self::Query(_) => 400,
+        }
+    }
+}
+
+fn run_query(
+    binaries: &BTreeMap<String, Arc<Adapter>>,
+    body: &str,
+) -> std::result::Result<QueryResults, QueryError> {
+    let request = serde_json::from_str::<QueryRequest>(body)?;
+    let adapter = match &request.binary {
+        Some(name) => binaries
+            .get(name)
+            .ok_or_else(|| QueryError::UnknownBinary(name.clone()))?,
+        None if binaries.len() == 1 => binaries.values().next().expect("one binary is loaded"),
+        None => return Err(QueryError::MissingBinary),
+    };
+    let variables = request
+        .variables
+        .into_iter()
+        .map(|(name, value)| (name, value.into()))
+        .collect();
+    Ok(adapter.query(&request.query, variables)?)
+}
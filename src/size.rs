@@ -0,0 +1,212 @@
+//! Breakdowns of where the bytes of a binary go, in the style of bloaty. Sizes are given both in
+//! memory (vm size) and in the file, which differ for sections such as `.bss` which take up memory
+//! but aren't stored in the file, and for sections like the debug info which aren't loaded.
+use crate::adapter::{Function, Section, SourceLocation, Symbol};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Name of the row for code which couldn't be attributed to anything
+pub const UNKNOWN: &str = "[unknown]";
+
+/// What to group the sizes in a report by
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SizeGrouping {
+    Section,
+    Symbol,
+    /// Source file of the code according to the DWARF line tables
+    File,
+    /// DWARF compile unit containing the code
+    CompileUnit,
+}
+
+impl FromStr for SizeGrouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "section" => Ok(Self::Section),
+            "symbol" => Ok(Self::Symbol),
+            "file" => Ok(Self::File),
+            "compileUnit" => Ok(Self::CompileUnit),
+            _ => Err(format!(
+                "unknown size grouping '{}', expected section, symbol, file or compileUnit",
+                s
+            )),
+        }
+    }
+}
+
+/// A row of a size report
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SizeRow {
+    pub name: String,
+    /// Bytes taken up in memory when the binary is loaded
+    pub vm_size: u64,
+    /// Bytes taken up in the file
+    pub file_size: u64,
+}
+
+/// Sorts the rows largest first, by vm size then file size
+fn sorted(rows: impl IntoIterator<Item = SizeRow>) -> Vec<SizeRow> {
+    let mut rows = rows.into_iter().collect::<Vec<_>>();
+    rows.sort_by(|a, b| {
+        (b.vm_size, b.file_size)
+            .cmp(&(a.vm_size, a.file_size))
+            .then(a.name.cmp(&b.name))
+    });
+    rows
+}
+
+/// The size of each section. Sections which aren't loaded have no vm size.
+pub fn by_section(sections: &[Arc<Section>]) -> Vec<SizeRow> {
+    sorted(sections.iter().map(|x| SizeRow {
+        name: x.name.clone(),
+        vm_size: if x.address > 0 { x.size } else { 0 },
+        file_size: x.data.len() as u64,
+    }))
+}
+
+/// The size of the symbols with each name. Symbols in sections which aren't in the file (such as
+/// `.bss`) have no file size.
+pub fn by_symbol(symbols: &[Arc<Symbol>], sections: &[Arc<Section>]) -> Vec<SizeRow> {
+    let mut rows: BTreeMap<&str, SizeRow> = BTreeMap::new();
+    for symbol in symbols.iter().filter(|x| x.size > 0) {
+        let in_file = sections
+            .iter()
+            .find(|x| x.address > 0 && x.contains(symbol.address))
+            .is_some_and(|x| !x.data.is_empty());
+        let row = rows.entry(&symbol.name).or_insert_with(|| SizeRow {
+            name: symbol.name.clone(),
+            ..Default::default()
+        });
+        row.vm_size += symbol.size;
+        if in_file {
+            row.file_size += symbol.size;
+        }
+    }
+    sorted(rows.into_values())
+}
+
+/// Bytes of code from each source file, found by adding up the lengths of the instructions each
+/// line table row covers. A row only covers instructions in the function it's in, so code without
+/// debug info after a function isn't attributed to the function's last line. Files are keyed by
+/// their path and compile unit directory.
+pub fn code_bytes_by_file(
+    functions: &[Arc<Function>],
+    debug_info: &BTreeMap<u64, Vec<Arc<SourceLocation>>>,
+) -> HashMap<(PathBuf, Option<Arc<Path>>), u64> {
+    let mut files: HashMap<(PathBuf, Option<Arc<Path>>), u64> = HashMap::new();
+    for func in functions {
+        for instr in func.instructions() {
+            let row = debug_info
+                .range(func.address..=instr.ip())
+                .next_back()
+                .and_then(|(_, x)| x.last());
+            if let Some(location) = row {
+                let file = (location.file.clone(), location.comp_dir.clone());
+                *files.entry(file).or_default() += instr.len() as u64;
+            }
+        }
+    }
+    files
+}
+
+/// Rows with the same vm and file size, along with a row for the rest of `total` which wasn't
+/// attributed to any of them.
+pub fn code_rows(sizes: impl IntoIterator<Item = (String, u64)>, total: u64) -> Vec<SizeRow> {
+    let mut attributed = 0;
+    let mut rows = sizes
+        .into_iter()
+        .map(|(name, size)| {
+            attributed += size;
+            SizeRow {
+                name,
+                vm_size: size,
+                file_size: size,
+            }
+        })
+        .collect::<Vec<_>>();
+    if total > attributed {
+        rows.push(SizeRow {
+            name: UNKNOWN.to_string(),
+            vm_size: total - attributed,
+            file_size: total - attributed,
+        });
+    }
+    sorted(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(name: &str, address: u64, size: u64, file_size: usize) -> Arc<Section> {
+        Arc::new(Section {
+            name: name.to_string(),
+            address,
+            size,
+            data: vec![0; file_size].into(),
+        })
+    }
+
+    #[test]
+    fn section_and_symbol_sizes() {
+        let sections = [
+            section(".text", 0x1000, 0x100, 0x100),
+            section(".bss", 0x2000, 0x400, 0),
+            section(".debug_info", 0, 0x200, 0x200),
+        ];
+        assert_eq!(
+            by_section(&sections),
+            vec![
+                SizeRow {
+                    name: ".bss".to_string(),
+                    vm_size: 0x400,
+                    file_size: 0
+                },
+                SizeRow {
+                    name: ".text".to_string(),
+                    vm_size: 0x100,
+                    file_size: 0x100
+                },
+                SizeRow {
+                    name: ".debug_info".to_string(),
+                    vm_size: 0,
+                    file_size: 0x200
+                },
+            ]
+        );
+
+        let symbol = |name: &str, address, size| {
+            Arc::new(Symbol {
+                name: name.to_string(),
+                address,
+                size,
+                kind: "text".to_string(),
+            })
+        };
+        let symbols = [
+            symbol("main", 0x1000, 0x20),
+            symbol("helper", 0x1020, 0x10),
+            symbol("helper", 0x1030, 0x10),
+            symbol("BUFFER", 0x2000, 0x400),
+            symbol("label", 0x1040, 0),
+        ];
+        let rows = by_symbol(&symbols, &sections);
+        assert_eq!(rows.len(), 3);
+        assert_eq!((rows[0].name.as_str(), rows[0].file_size), ("BUFFER", 0));
+        assert_eq!((rows[1].vm_size, rows[2].vm_size), (0x20, 0x20));
+    }
+
+    #[test]
+    fn unknown_code() {
+        let rows = code_rows([("a.rs".to_string(), 10), ("b.rs".to_string(), 30)], 100);
+        let rows = rows
+            .iter()
+            .map(|x| (x.name.as_str(), x.vm_size))
+            .collect::<Vec<_>>();
+        assert_eq!(rows, vec![(UNKNOWN, 60), ("b.rs", 30), ("a.rs", 10)]);
+    }
+}
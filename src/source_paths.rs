@@ -0,0 +1,319 @@
+//! Making sense of the source paths in the DWARF line tables. Paths are often relative to the
+//! directory the compile unit was built in, the standard library's are under `/rustc/<commit>/`
+//! and dependencies are in cargo's registry, so these are classified by their resolved path.
+//! Files can be found by exact path, path suffix, glob or regular expression.
+use crate::error::{Error, Result};
+use glob::MatchOptions;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// How a pattern is compared with source paths
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FileMatch {
+    /// The whole path, after normalizing both
+    #[default]
+    Exact,
+    /// The last components of the path, see [`has_suffix`]
+    Suffix,
+    /// A glob such as `src/**/*.rs`, where a relative pattern matches the end of the path
+    Glob,
+    /// A regular expression found anywhere in the path
+    Regex,
+}
+
+impl FromStr for FileMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(Self::Exact),
+            "suffix" => Ok(Self::Suffix),
+            "glob" => Ok(Self::Glob),
+            "regex" => Ok(Self::Regex),
+            _ => Err(format!(
+                "unknown file match '{}', expected exact, suffix, glob or regex",
+                s
+            )),
+        }
+    }
+}
+
+/// A pattern for finding source files
+#[derive(Clone, Debug)]
+pub enum FilePattern {
+    Exact(PathBuf),
+    Suffix(PathBuf),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl FilePattern {
+    pub fn new(pattern: &str, mode: FileMatch) -> Result<Self> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::Query(format!("invalid file pattern '{}': {}", pattern, e))
+        };
+        Ok(match mode {
+            FileMatch::Exact => Self::Exact(normalize(Path::new(pattern))),
+            FileMatch::Suffix => Self::Suffix(pattern.into()),
+            FileMatch::Glob => {
+                let anchored = pattern.starts_with('/') || pattern.starts_with("**");
+                let pattern = if anchored {
+                    pattern.to_string()
+                } else {
+                    format!("**/{}", pattern)
+                };
+                Self::Glob(glob::Pattern::new(&pattern).map_err(|e| invalid(&e))?)
+            }
+            FileMatch::Regex => Self::Regex(Regex::new(pattern).map_err(|e| invalid(&e))?),
+        })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            Self::Exact(pattern) => normalize(path) == *pattern,
+            Self::Suffix(suffix) => has_suffix(path, suffix),
+            Self::Glob(pattern) => pattern.matches_path_with(
+                path,
+                MatchOptions {
+                    require_literal_separator: true,
+                    ..Default::default()
+                },
+            ),
+            Self::Regex(regex) => regex.is_match(&path.to_string_lossy()),
+        }
+    }
+}
+
+/// Removes `.` components and resolves `..` against the component before it without touching
+/// the file system. A `..` which would go above the start of a relative path is kept.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => result.push(".."),
+            },
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// The path resolved against the compile unit's directory if it's relative, and normalized
+pub fn absolute_path(path: &Path, comp_dir: Option<&Path>) -> PathBuf {
+    match comp_dir {
+        Some(dir) if path.is_relative() => normalize(&dir.join(path)),
+        _ => normalize(path),
+    }
+}
+
+fn names(path: &Path) -> Vec<&str> {
+    path.components()
+        .filter_map(|x| match x {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the file is part of the standard library: under `/rustc/<commit>/` where rustc was
+/// built, the `rust-src` component of a toolchain or `/rust/deps/` for the standard library's own
+/// dependencies.
+pub fn is_stdlib(path: &Path) -> bool {
+    let names = names(path);
+    let is_commit = |x: &str| x.len() == 40 && x.bytes().all(|x| x.is_ascii_hexdigit());
+    match names.as_slice() {
+        ["rustc", commit, ..] if path.has_root() && is_commit(commit) => true,
+        ["rust", "deps", _, ..] if path.has_root() => true,
+        _ => names
+            .windows(5)
+            .any(|x| x == ["lib", "rustlib", "src", "rust", "library"]),
+    }
+}
+
+/// Whether the file is from a crate downloaded by cargo, in `~/.cargo/registry` or a git
+/// checkout in `~/.cargo/git`
+pub fn is_dependency(path: &Path) -> bool {
+    names(path)
+        .windows(2)
+        .any(|x| x == [".cargo", "registry"] || x == [".cargo", "git"])
+}
+
+/// Removes the `-<version>` from a package directory name such as `serde-1.0.204`
+fn strip_version(name: &str) -> &str {
+    let starts_version = |rest: &str| {
+        let mut parts = rest.splitn(3, '.');
+        let mut numeric = || {
+            parts
+                .next()
+                .is_some_and(|x| !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit()))
+        };
+        numeric() && numeric() && parts.next().is_some()
+    };
+    name.match_indices('-')
+        .map(|(index, _)| index)
+        .find(|&index| starts_version(&name[index + 1..]))
+        .map_or(name, |index| &name[..index])
+}
+
+/// The crate the file belongs to for standard library files and dependencies, with `-` replaced by
+/// `_` as in the crate names of symbols. Other files have no crate in their path.
+pub fn path_crate(path: &Path) -> Option<String> {
+    let names = names(path);
+    let after = |window: &[&str]| {
+        names
+            .windows(window.len())
+            .position(|x| x == window)
+            .map(|x| &names[x + window.len()..])
+    };
+    let package = if let Some(rest) = after(&[".cargo", "registry", "src"]) {
+        // The first directory is the registry index
+        strip_version(rest.get(1)?)
+    } else if let Some(rest) = after(&[".cargo", "git", "checkouts"]) {
+        // Checkouts are `<repo>-<hash>/<revision>/` and may hold several crates, so use the
+        // directory containing `src`
+        let src = rest.iter().position(|x| *x == "src")?;
+        match src {
+            0..=1 => return None,
+            2 => rest[0].rsplit_once('-').map_or(rest[0], |x| x.0),
+            _ => rest[src - 1],
+        }
+    } else if let Some(rest) = after(&["rust", "deps"]).filter(|_| is_stdlib(path)) {
+        strip_version(rest.first()?)
+    } else if let Some(rest) = after(&["library"]).filter(|_| is_stdlib(path)) {
+        // std::arch is in its own repository with the crates under `crates/`
+        match rest {
+            ["stdarch", "crates", name, ..] => name,
+            [name, ..] => name,
+            [] => return None,
+        }
+    } else {
+        return None;
+    };
+    Some(package.replace('-', "_"))
+}
+
+/// Whether `path` ends with the components of `suffix`, so `src/lib.rs` matches
+/// `/home/me/project/src/lib.rs` but `lib.rs` doesn't match `src/mylib.rs`
+pub fn has_suffix(path: &Path, suffix: &Path) -> bool {
+    let suffix = normalize(suffix);
+    !suffix.as_os_str().is_empty() && path.ends_with(suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUSTC: &str = "/rustc/59807616e1fa2540724bfbac14d7976d7e4a3860";
+
+    #[test]
+    fn normalized_paths() {
+        assert_eq!(
+            normalize(Path::new("library/std/src/../../backtrace/src/lib.rs")),
+            PathBuf::from("library/backtrace/src/lib.rs")
+        );
+        assert_eq!(
+            normalize(Path::new("./src/../../x/./y.rs")),
+            PathBuf::from("../x/y.rs")
+        );
+        assert_eq!(normalize(Path::new("/../a.rs")), PathBuf::from("/a.rs"));
+        assert_eq!(
+            absolute_path(Path::new("src/lib.rs"), Some(Path::new("/src/app"))),
+            PathBuf::from("/src/app/src/lib.rs")
+        );
+        assert_eq!(
+            absolute_path(Path::new("/a/src/lib.rs"), Some(Path::new("/src/app"))),
+            PathBuf::from("/a/src/lib.rs")
+        );
+        assert_eq!(
+            absolute_path(Path::new("src/lib.rs"), None),
+            PathBuf::from("src/lib.rs")
+        );
+    }
+
+    #[test]
+    fn classified_paths() {
+        let std_file = PathBuf::from(RUSTC).join("library/core/src/fmt/mod.rs");
+        let arch = PathBuf::from(RUSTC).join("library/stdarch/crates/core_arch/src/x86/sse2.rs");
+        let rust_src = Path::new(
+            "/home/me/.rustup/toolchains/stable/lib/rustlib/src/rust/library/alloc/src/vec/mod.rs",
+        );
+        let std_dep = Path::new("/rust/deps/hashbrown-0.16.1/src/map.rs");
+        let registry = Path::new(
+            "/home/me/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/aho-corasick-1.1.3/src/lib.rs",
+        );
+        let prerelease =
+            Path::new("/home/me/.cargo/registry/src/index/sha-1-0.10.0-rc.1/src/lib.rs");
+        let git = Path::new(
+            "/home/me/.cargo/git/checkouts/repo-0123abcd/a1b2c3d/crates/foo-bar/src/lib.rs",
+        );
+        let local = Path::new("/home/me/project/src/main.rs");
+
+        for path in [&std_file, &arch, rust_src, std_dep] {
+            assert!(is_stdlib(path), "{}", path.display());
+            assert!(!is_dependency(path), "{}", path.display());
+        }
+        for path in [registry, prerelease, git] {
+            assert!(!is_stdlib(path), "{}", path.display());
+            assert!(is_dependency(path), "{}", path.display());
+        }
+        assert!(!is_stdlib(local) && !is_dependency(local));
+        assert!(!is_stdlib(Path::new(
+            "rustc/59807616e1fa2540724bfbac14d7976d7e4a3860/x.rs"
+        )));
+
+        assert_eq!(path_crate(&std_file).as_deref(), Some("core"));
+        assert_eq!(path_crate(&arch).as_deref(), Some("core_arch"));
+        assert_eq!(path_crate(rust_src).as_deref(), Some("alloc"));
+        assert_eq!(path_crate(std_dep).as_deref(), Some("hashbrown"));
+        assert_eq!(path_crate(registry).as_deref(), Some("aho_corasick"));
+        assert_eq!(path_crate(prerelease).as_deref(), Some("sha_1"));
+        assert_eq!(path_crate(git).as_deref(), Some("foo_bar"));
+        assert_eq!(path_crate(local), None);
+    }
+
+    #[test]
+    fn file_patterns() {
+        let path = Path::new("/home/me/project/src/adapter/mod.rs");
+        let matches = |pattern: &str, mode: &str| {
+            FilePattern::new(pattern, mode.parse().unwrap())
+                .unwrap()
+                .matches(path)
+        };
+        assert!(matches(
+            "/home/me/project/src/adapter/../adapter/mod.rs",
+            "exact"
+        ));
+        assert!(!matches("src/adapter/mod.rs", "exact"));
+        assert!(matches("adapter/mod.rs", "suffix"));
+        assert!(matches("src/**/*.rs", "glob"));
+        assert!(matches("adapter/*.rs", "glob"));
+        assert!(matches("/home/**/mod.rs", "glob"));
+        assert!(!matches("src/*.rs", "glob"));
+        assert!(!matches("/project/**/*.rs", "glob"));
+        assert!(matches(r"src/.*\.rs$", "regex"));
+        assert!(!matches(r"^src/", "regex"));
+        assert!(matches!(
+            FilePattern::new("(", FileMatch::Regex),
+            Err(Error::Query(_))
+        ));
+        assert!(FilePattern::new("[", FileMatch::Glob).is_err());
+        assert!("fuzzy".parse::<FileMatch>().is_err());
+    }
+
+    #[test]
+    fn suffixes() {
+        let path = Path::new("/home/me/project/src/lib.rs");
+        assert!(has_suffix(path, Path::new("src/lib.rs")));
+        assert!(has_suffix(path, Path::new("./lib.rs")));
+        assert!(has_suffix(path, path));
+        assert!(!has_suffix(path, Path::new("ib.rs")));
+        assert!(!has_suffix(path, Path::new("")));
+    }
+}
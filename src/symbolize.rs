@@ -0,0 +1,252 @@
+//! Maps addresses to the functions and source lines they came from, including the functions
+//! inlined at each address, in the same way as `addr2line`.
+use crate::adapter::{Adapter, Symbol};
+use crate::bytes::Bytes;
+use crate::error::{Error, Result};
+use crate::loader::LoadOptions;
+use gimli::{EndianReader, RunTimeEndian, SectionId};
+use object::{
+    elf, CompressionFormat, Object, ObjectSection, ObjectSymbol, SectionFlags, SectionKind,
+    SymbolKind,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Reads DWARF straight from the loaded file without copying it
+pub type Reader = EndianReader<RunTimeEndian, Bytes>;
+
+/// A function and source location for an address. When code has been inlined an address has a
+/// frame for each function it was inlined into.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Frame {
+    /// Name of the function, this is the linkage name and so is mangled if the compiler mangles
+    /// names
+    pub function: Option<String>,
+    pub language: Option<gimli::DwLang>,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl Frame {
+    /// The function name demangled using the frame's language, or guessing the language if
+    /// it isn't known.
+    pub fn demangled_function(&self) -> Option<String> {
+        let name = self.function.as_deref()?;
+        Some(addr2line::demangle_auto(name.into(), self.language).into_owned())
+    }
+}
+
+/// Looks up the frames for addresses in a binary with its adapter, which keeps the DWARF index
+/// so a symbolizer is cheap to create.
+pub struct Symbolizer {
+    adapter: Adapter,
+    /// Address ranges of the sections loaded into memory
+    loaded: Vec<Range<u64>>,
+    /// Function symbols sorted by address, only the one GNU addr2line would use at each address
+    symbols: Vec<FileSymbol>,
+}
+
+/// A function symbol and the source file it came from according to the symbol table
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileSymbol {
+    pub name: String,
+    pub address: u64,
+    /// Name of the file symbol before the function in the symbol table. This is only set for
+    /// global functions if the symbol table has a single file symbol before every other symbol,
+    /// as otherwise there's no telling which file it came from.
+    pub file: Option<String>,
+}
+
+/// The adapter's function symbols along with the files they're from according to the symbol
+/// table
+fn file_symbols(file: &object::File, symbols: &[Arc<Symbol>]) -> Vec<FileSymbol> {
+    let mut files = HashMap::new();
+    let mut current_file = None;
+    let mut symbol_seen = false;
+    let mut file_after_symbol = false;
+    for (index, symbol) in file.symbols().enumerate() {
+        match symbol.kind() {
+            SymbolKind::File => {
+                current_file = symbol.name().ok().map(|x| x.to_string());
+                file_after_symbol |= symbol_seen;
+                continue;
+            }
+            SymbolKind::Text | SymbolKind::Unknown => {
+                let file = current_file
+                    .clone()
+                    .filter(|_| symbol.is_local() || !file_after_symbol);
+                if let Ok(name) = symbol.name() {
+                    files.insert((symbol.address(), name), (index, file));
+                }
+            }
+            _ => {}
+        }
+        symbol_seen = true;
+    }
+    let mut symbols = symbols
+        .iter()
+        .filter(|x| x.kind == "text" || x.kind == "unknown")
+        .map(|x| {
+            let (index, file) = files
+                .get(&(x.address, x.name.as_str()))
+                .cloned()
+                .unwrap_or((usize::MAX, None));
+            (
+                index,
+                FileSymbol {
+                    name: x.name.clone(),
+                    address: x.address,
+                    file,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    // GNU addr2line uses the first of several symbols at the same address in the symbol table
+    symbols.sort_by_key(|(index, x)| (x.address, *index));
+    symbols.dedup_by_key(|(_, x)| x.address);
+    symbols.into_iter().map(|(_, x)| x).collect()
+}
+
+/// Whether the section takes up memory when the binary is loaded, unlike sections such as
+/// the debug info which are only in the file
+fn is_loaded(section: &object::Section) -> bool {
+    match section.flags() {
+        SectionFlags::Elf { sh_flags } => sh_flags & u64::from(elf::SHF_ALLOC) != 0,
+        _ => !matches!(
+            section.kind(),
+            SectionKind::Debug
+                | SectionKind::DebugString
+                | SectionKind::Linker
+                | SectionKind::Metadata
+                | SectionKind::Note
+                | SectionKind::Other
+                | SectionKind::Unknown
+        ),
+    }
+}
+
+fn load_section(file: &object::File, data: &Bytes, id: SectionId) -> Result<Bytes> {
+    let Some(section) = file.section_by_name(id.name()) else {
+        return Ok(Bytes::default());
+    };
+    let range = section.compressed_file_range()?;
+    if range.format == CompressionFormat::None {
+        let start = range.offset as usize;
+        let end = start.checked_add(range.uncompressed_size as usize);
+        if let Some(bytes) = end.and_then(|end| data.slice(start..end)) {
+            return Ok(bytes);
+        }
+    }
+    Ok(section.uncompressed_data()?.into_owned().into())
+}
+
+/// Finds the inlined functions at an address. The DWARF units are indexed when it's created and
+/// each unit's functions and line program are parsed the first time an address in it is looked
+/// up. The parsed units are cached without synchronization, so lookups take turns.
+pub(crate) struct FrameIndex {
+    context: Mutex<addr2line::Context<Reader>>,
+}
+
+impl fmt::Debug for FrameIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrameIndex").finish_non_exhaustive()
+    }
+}
+
+impl FrameIndex {
+    /// Indexes the DWARF in the object file `data`
+    pub(crate) fn new(data: &Bytes) -> Result<Self> {
+        let file = object::File::parse(&**data)?;
+        let endian = if file.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let dwarf = gimli::Dwarf::load(|id| {
+            load_section(&file, data, id).map(|x| EndianReader::new(x, endian))
+        })?;
+        Ok(Self {
+            context: Mutex::new(addr2line::Context::from_dwarf(dwarf)?),
+        })
+    }
+
+    /// The frames for the address, innermost first, with file names remapped by the options
+    pub(crate) fn frames(&self, address: u64, options: &LoadOptions) -> Result<Vec<Frame>> {
+        let context = self.context.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut frames = vec![];
+        let mut iter = context.find_frames(address).skip_all_loads()?;
+        while let Some(frame) = iter.next()? {
+            let function = frame
+                .function
+                .as_ref()
+                .map(|x| x.raw_name().map(|x| x.into_owned()))
+                .transpose()
+                .map_err(Error::from)?;
+            let location = frame.location.as_ref();
+            frames.push(Frame {
+                function,
+                language: frame.function.as_ref().and_then(|x| x.language),
+                file: location
+                    .and_then(|x| x.file)
+                    .map(|x| options.remap_path(x.into())),
+                line: location.and_then(|x| x.line),
+                column: location.and_then(|x| x.column),
+            });
+        }
+        Ok(frames)
+    }
+}
+
+impl Symbolizer {
+    pub fn new(adapter: &Adapter) -> Self {
+        let (loaded, symbols) = match adapter.object() {
+            Some(file) => {
+                let loaded = file
+                    .sections()
+                    .filter(is_loaded)
+                    .map(|x| x.address()..x.address().saturating_add(x.size()))
+                    .collect();
+                (loaded, file_symbols(&file, adapter.symbols()))
+            }
+            None => (vec![], vec![]),
+        };
+        Self {
+            adapter: adapter.clone(),
+            loaded,
+            symbols,
+        }
+    }
+
+    /// The frames for the address starting with the innermost inlined function and ending with
+    /// the function the code was inlined into. If there's line information but no function for
+    /// the address there's a single frame without a function, and no frames if there's no debug
+    /// info covering the address or it's outside of the sections loaded into memory. Problems
+    /// reading the DWARF are reported as the adapter's diagnostics.
+    pub fn frames(&self, address: u64) -> Vec<Frame> {
+        // Code removed by the linker can leave DWARF describing addresses near zero
+        if !self.is_loaded(address) {
+            return vec![];
+        }
+        self.adapter.frames(address)
+    }
+
+    /// Whether the address is in one of the sections loaded into memory
+    pub fn is_loaded(&self, address: u64) -> bool {
+        self.loaded.iter().any(|x| x.contains(&address))
+    }
+
+    /// The function symbol for an address without debug info, found in the same way as GNU
+    /// addr2line. This is the closest function at or before the address in the same section, so
+    /// padding between functions is attributed to the function before it.
+    pub fn nearest_symbol(&self, address: u64) -> Option<&FileSymbol> {
+        let section = self.loaded.iter().find(|x| x.contains(&address))?;
+        let index = self.symbols.partition_point(|x| x.address <= address);
+        self.symbols[..index]
+            .last()
+            .filter(|x| section.contains(&x.address))
+    }
+}
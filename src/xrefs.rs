@@ -0,0 +1,132 @@
+use crate::adapter::Section;
+use iced_x86::{Instruction, OpKind, Register};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// How an instruction refers to an address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum ReferenceKind {
+    /// A memory operand relative to the instruction pointer i.e. `[rip + 0x1234]`
+    RipRelative,
+    /// A memory operand with an absolute address and no registers
+    Absolute,
+    /// An immediate value which happens to be an address in a loaded section
+    Immediate,
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RipRelative => write!(f, "rip-relative"),
+            Self::Absolute => write!(f, "absolute"),
+            Self::Immediate => write!(f, "immediate"),
+        }
+    }
+}
+
+/// A reference from an instruction to an address in one of the loaded sections.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataReference {
+    pub instruction: Arc<Instruction>,
+    pub address: u64,
+    pub kind: ReferenceKind,
+}
+
+fn is_loaded(sections: &[Arc<Section>], address: u64) -> bool {
+    sections
+        .iter()
+        .any(|x| x.address > 0 && x.contains(address))
+}
+
+/// Finds the addresses an instruction refers to through its memory operand or immediates. Only
+/// addresses within a loaded section are returned. Branch targets aren't included as they're
+/// control flow rather than data.
+pub fn data_references(instr: &Arc<Instruction>, sections: &[Arc<Section>]) -> Vec<DataReference> {
+    let mut refs = vec![];
+    let mut push = |address, kind| {
+        if is_loaded(sections, address) {
+            refs.push(DataReference {
+                instruction: instr.clone(),
+                address,
+                kind,
+            });
+        }
+    };
+    for op in 0..instr.op_count() {
+        match instr.op_kind(op) {
+            OpKind::Memory if instr.is_ip_rel_memory_operand() => {
+                push(instr.ip_rel_memory_address(), ReferenceKind::RipRelative);
+            }
+            OpKind::Memory
+                if instr.memory_base() == Register::None
+                    && instr.memory_index() == Register::None
+                    && instr.segment_prefix() == Register::None =>
+            {
+                push(instr.memory_displacement64(), ReferenceKind::Absolute);
+            }
+            OpKind::Immediate32 | OpKind::Immediate64 | OpKind::Immediate32to64 => {
+                push(instr.immediate(op), ReferenceKind::Immediate);
+            }
+            _ => {}
+        }
+    }
+    refs
+}
+
+/// Builds a map from referenced address to every reference to it.
+pub fn reference_index(
+    instructions: &[Arc<Instruction>],
+    sections: &[Arc<Section>],
+) -> BTreeMap<u64, Vec<Arc<DataReference>>> {
+    let mut index: BTreeMap<u64, Vec<Arc<DataReference>>> = BTreeMap::new();
+    for instr in instructions {
+        for reference in data_references(instr, sections) {
+            index
+                .entry(reference.address)
+                .or_default()
+                .push(Arc::new(reference));
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_x86::{Decoder, DecoderOptions};
+
+    #[test]
+    fn finds_references() {
+        // 0x1000: mov rax, [rip + 0xff9] (0x2000)
+        // 0x1007: mov eax, 0x2010
+        // 0x100c: mov eax, 0x10
+        let code = [
+            0x48, 0x8b, 0x05, 0xf9, 0x0f, 0x00, 0x00, 0xb8, 0x10, 0x20, 0x00, 0x00, 0xb8, 0x10,
+            0x00, 0x00, 0x00,
+        ];
+        let instructions = Decoder::with_ip(64, &code, 0x1000, DecoderOptions::NONE)
+            .iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        let sections = vec![Arc::new(Section {
+            name: ".data".to_string(),
+            address: 0x2000,
+            size: 0x100,
+            data: vec![0; 0x100].into(),
+        })];
+        let index = reference_index(&instructions, &sections);
+        let summary = index
+            .iter()
+            .map(|(k, v)| (*k, v[0].instruction.ip(), v[0].kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0x2000, 0x1000, ReferenceKind::RipRelative),
+                (0x2010, 0x1007, ReferenceKind::Immediate)
+            ]
+        );
+    }
+}
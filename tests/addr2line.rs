@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_object-addr2line");
+const GNU_ADDR2LINE: &str = "/usr/bin/addr2line";
+
+fn run(program: &str, args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Addresses spread through the functions of the binary, including some in the middle of
+/// functions and some which aren't in the binary at all
+fn sample_addresses(binary: &str) -> Vec<String> {
+    let nm = Command::new("nm").args(["-n", binary]).output().unwrap();
+    let mut addresses = String::from_utf8(nm.stdout)
+        .unwrap()
+        .lines()
+        .filter(|x| x.split(' ').nth(1).is_some_and(|x| x == "t" || x == "T"))
+        .filter_map(|x| u64::from_str_radix(x.split(' ').next()?, 16).ok())
+        .enumerate()
+        .filter(|(i, _)| i % 41 == 0)
+        .map(|(i, x)| format!("{:x}", x + (i % 23) as u64 * 3))
+        .collect::<Vec<_>>();
+    addresses.extend(["0", "1", "0xffffffff", "deadbeef"].map(String::from));
+    addresses
+}
+
+#[test]
+fn matches_gnu_addr2line() {
+    if !Path::new(GNU_ADDR2LINE).exists() {
+        eprintln!("skipping as {} isn't installed", GNU_ADDR2LINE);
+        return;
+    }
+    let addresses = sample_addresses(BIN);
+    for flags in ["", "-f", "-fi", "-fiC", "-afip", "-afipC", "-ai", "-fips"] {
+        let mut args = vec!["-e", BIN];
+        args.extend(Some(flags).filter(|x| !x.is_empty()));
+        args.extend(addresses.iter().map(|x| x.as_str()));
+        assert_eq!(
+            run(BIN, &args, ""),
+            run(GNU_ADDR2LINE, &args, ""),
+            "flags: {}",
+            flags
+        );
+    }
+}
+
+#[test]
+fn reads_addresses_from_stdin() {
+    let addresses = sample_addresses(BIN);
+    let mut args = vec!["-e", BIN, "-f"];
+    let from_stdin = run(BIN, &args, &addresses.join("\n"));
+    args.extend(addresses.iter().map(|x| x.as_str()));
+    assert_eq!(from_stdin, run(BIN, &args, ""));
+}
+
+#[test]
+fn missing_section() {
+    let output = Command::new(BIN)
+        .args(["-e", BIN, "-j", ".not_a_section", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "object-addr2line: cannot find section .not_a_section\n"
+    );
+}
+
+#[test]
+fn section_offset_overflow() {
+    let args = ["-e", BIN, "-af", "-j", ".text", "ffffffffffffffff"];
+    assert_eq!(run(BIN, &args, ""), "0xffffffffffffffff\n??\n??:0\n");
+}
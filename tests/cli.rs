@@ -0,0 +1,316 @@
+use object_trustfall_adapter::adapter::Adapter;
+use object_trustfall_adapter::diff::BinaryDiff;
+use object_trustfall_adapter::multi::MultiAdapter;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_object-query");
+
+const SECTION_QUERY: &str = r#"
+{
+    sections {
+        name @output @filter(op: "=", value: ["$name"])
+        address @output
+    }
+}
+"#;
+
+fn run(args: &[&str], stdin: &str) -> (bool, String, String) {
+    let mut child = Command::new(BIN)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn prints_schema() {
+    let (success, stdout, _) = run(&["--schema"], "");
+    assert!(success);
+    assert_eq!(stdout, Adapter::SCHEMA_TEXT);
+}
+
+#[test]
+fn query_from_stdin_with_variables() {
+    let (success, stdout, stderr) = run(&[BIN, "--var", "name=.text"], SECTION_QUERY);
+    assert!(success, "{}", stderr);
+    let rows = stdout
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["name"], ".text");
+    assert!(rows[0]["address"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn query_from_file_as_csv() {
+    let path = std::env::temp_dir().join(format!("object-query-{}.graphql", std::process::id()));
+    std::fs::write(&path, SECTION_QUERY).unwrap();
+    let (success, stdout, stderr) = run(
+        &[
+            BIN,
+            path.to_str().unwrap(),
+            "--var",
+            "name=.text",
+            "--format",
+            "csv",
+        ],
+        "",
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(success, "{}", stderr);
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "address,name");
+    assert!(lines[1].ends_with(",.text"));
+}
+
+#[test]
+fn invalid_query_fails() {
+    let (success, _, stderr) = run(&[BIN], "{ notAnEdge { name @output } }");
+    assert!(!success);
+    assert!(stderr.starts_with("error: invalid query"));
+}
+
+#[test]
+fn repl_session() {
+    let home = std::env::temp_dir().join(format!("object-query-home-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let mut child = Command::new(BIN)
+        .args(["--repl", BIN])
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = format!(
+        ":explain\n:vars name=.text\n{}\n:explain\n:notACommand\n",
+        SECTION_QUERY.trim()
+    );
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let history = std::fs::read_to_string(home.join(".object_query_history"));
+    std::fs::remove_dir_all(&home).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    let mut lines = stdout.lines();
+    let row = serde_json::from_str::<serde_json::Value>(lines.next().unwrap()).unwrap();
+    assert_eq!(row["name"], ".text");
+    assert_eq!(lines.next(), Some("sections -> Section"));
+    assert!(stdout.contains("$name: String!"));
+    assert!(stderr.contains("error: no query to explain\n"));
+    assert!(stderr.contains("unknown command ':notACommand'"));
+    assert!(history.unwrap().contains(":vars name=.text"));
+}
+
+#[test]
+fn disassembly_listing() {
+    let query = r#"
+{
+    functions {
+        name @output @filter(op: "has_substring", value: ["$name"])
+        instructions @fold {
+            address @output
+            bytes @output
+        }
+    }
+}"#;
+    let (success, stdout, stderr) = run(&[BIN, "--var", "name=object_query6Loader4load"], query);
+    assert!(success, "{}", stderr);
+    let function =
+        serde_json::from_str::<serde_json::Value>(stdout.lines().next().unwrap()).unwrap();
+    let name = function["name"].as_str().unwrap();
+
+    let (success, listing, stderr) = run(&["disasm", "-S", "--function", name, BIN], "");
+    assert!(success, "{}", stderr);
+    assert!(listing.contains(&format!(" <{}>:\n", name)));
+    assert!(listing.contains("src/bin/object-query/main.rs:"));
+    assert!(listing.contains("pub fn load("));
+    let addresses = function["address"].as_array().unwrap();
+    let bytes = function["bytes"].as_array().unwrap();
+    assert!(!addresses.is_empty());
+    for (address, bytes) in addresses.iter().zip(bytes) {
+        let address = address.as_u64().unwrap();
+        let first_bytes = bytes
+            .as_str()
+            .unwrap()
+            .split(' ')
+            .take(7)
+            .collect::<Vec<_>>();
+        let line = format!("{:>8x}:\t{}", address, first_bytes.join(" "));
+        assert!(listing.contains(&line), "missing {}", line);
+    }
+}
+
+#[test]
+fn diff_subcommand() {
+    let (success, stdout, _) = run(&["diff", "--schema"], "");
+    assert!(success);
+    assert_eq!(stdout, BinaryDiff::SCHEMA_TEXT);
+
+    let query = r#"
+{
+    symbols {
+        name @output
+        status @filter(op: "!=", value: ["$status"])
+    }
+}"#;
+    let (success, stdout, stderr) = run(&["diff", BIN, BIN, "--var", "status=unchanged"], query);
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout, "");
+}
+
+#[test]
+fn multi_subcommand() {
+    let (success, stdout, _) = run(&["multi", "--schema"], "");
+    assert!(success);
+    assert_eq!(stdout, MultiAdapter::SCHEMA_TEXT);
+
+    let query = r#"
+{
+    binaries {
+        path @output
+        getSection(name: ".text") {
+            name @output(name: "section")
+        }
+    }
+}"#;
+    let addr2line = env!("CARGO_BIN_EXE_object-addr2line");
+    let (success, stdout, stderr) = run(&["multi", BIN, addr2line], query);
+    assert!(success, "{}", stderr);
+    let rows = stdout
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["path"], BIN);
+    assert_eq!(rows[1]["path"], addr2line);
+    assert!(rows.iter().all(|x| x["section"] == ".text"));
+}
+
+#[test]
+fn multi_from_cargo_messages() {
+    let message = serde_json::json!({
+        "reason": "compiler-artifact",
+        "package_id": "path+file:///src/object-trustfall-adapter#0.1.0",
+        "target": {"kind": ["bin"], "name": "object-query"},
+        "profile": {"test": false},
+        "executable": BIN,
+    });
+    let messages = format!(
+        "{}\n{}\n",
+        message,
+        serde_json::json!({"reason": "build-finished", "success": true})
+    );
+    let path = std::env::temp_dir().join(format!("cargo-messages-{}.json", std::process::id()));
+    std::fs::write(&path, messages).unwrap();
+    let query = r#"
+{
+    binaries {
+        path @output
+        package @output
+        targetName @output
+        targetKind @output
+        isTest @output
+    }
+}"#;
+    let (success, stdout, stderr) = run(
+        &["multi", "--cargo-messages", path.to_str().unwrap()],
+        query,
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(success, "{}", stderr);
+    let row = serde_json::from_str::<serde_json::Value>(stdout.trim()).unwrap();
+    assert_eq!(
+        row,
+        serde_json::json!({
+            "path": BIN,
+            "package": "object-trustfall-adapter",
+            "targetName": "object-query",
+            "targetKind": "bin",
+            "isTest": false,
+        })
+    );
+}
+
+#[test]
+fn cargo_messages_from_stdin_need_query_file() {
+    let (success, _, stderr) = run(&["multi", "--cargo-messages", "-"], "");
+    assert!(!success);
+    assert!(stderr.contains("--query <QUERY>"), "{}", stderr);
+
+    let (success, _, stderr) = run(&["multi", "--cargo-messages", "-", "--query", "-"], "");
+    assert!(!success);
+    assert!(stderr.contains("`--cargo-messages -`"), "{}", stderr);
+}
+
+#[test]
+fn remap_path_prefix() {
+    let query = r#"
+{
+    findSourceFiles(path: "bin/object-query/main.rs") {
+        absolutePath @output
+    }
+}"#;
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let remapping = format!("{}=/remapped", manifest_dir);
+    let (success, stdout, stderr) = run(&[BIN, "--remap-path-prefix", &remapping], query);
+    assert!(success, "{}", stderr);
+    let row = serde_json::from_str::<serde_json::Value>(stdout.trim()).unwrap();
+    assert_eq!(
+        row["absolutePath"],
+        "/remapped/src/bin/object-query/main.rs"
+    );
+
+    let (success, _, stderr) = run(&[BIN, "--remap-path-prefix", "no-equals"], query);
+    assert!(!success);
+    assert!(stderr.contains("expected FROM=TO"));
+}
+
+#[test]
+fn coverage_subcommand() {
+    let (success, stdout, stderr) = run(&["coverage", BIN], "");
+    assert!(success, "{}", stderr);
+    let map = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+    let path = format!(
+        "{}/src/bin/object-query/main.rs",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let traces = map["traces"][&path].as_array().unwrap();
+    assert!(!traces.is_empty());
+    assert!(traces
+        .iter()
+        .all(|x| !x["address"].as_array().unwrap().is_empty()));
+
+    let (success, stdout, stderr) = run(&["coverage", BIN, "--coverage-format", "lcov"], "");
+    assert!(success, "{}", stderr);
+    assert!(stdout.starts_with("TN:\n"));
+    assert!(stdout.contains(&format!("SF:{}\n", path)));
+    assert_eq!(
+        stdout.matches("SF:").count(),
+        stdout.matches("end_of_record").count()
+    );
+}
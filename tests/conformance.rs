@@ -1,5 +1,6 @@
 use glob::glob;
 use object_trustfall_adapter::adapter::Adapter as ObjectFile;
+use object_trustfall_adapter::coverage::CoverageOptions;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
@@ -122,7 +123,7 @@ fn check_addresses_match(path: &Path) {
         for trace in traces {
             println!("Assessing: {:?}", trace);
             for address in &trace.address {
-                let locations = object.debug_info.get(address).unwrap();
+                let locations = object.debug_info().get(address).unwrap();
                 assert!(
                     locations
                         .iter()
@@ -141,6 +142,27 @@ fn check_addresses_match(path: &Path) {
 
     assert!(actually_checked_something);
 
+    // The coverage map should have a breakpoint for every trace tarpaulin found
+    let coverage = object.coverage_map(&CoverageOptions::default()).unwrap();
+    for (path, traces) in trace_map.traces.iter() {
+        let lines = coverage
+            .files
+            .get(path)
+            .unwrap_or_else(|| panic!("Missing file: {}", path.display()));
+        for trace in traces {
+            let line = lines
+                .iter()
+                .find(|x| x.line == trace.line)
+                .unwrap_or_else(|| panic!("Missing line: {:?}", trace));
+            assert!(
+                trace.address.iter().all(|x| line.addresses.contains(x)),
+                "Coverage map has {:?} for {:?}",
+                line.addresses,
+                trace
+            );
+        }
+    }
+
     // Save a lil dump just to make sure.
     let file = fs::File::create(path.join("object.json")).unwrap();
     let mut writer = io::BufWriter::new(file);
@@ -0,0 +1,91 @@
+use object_trustfall_adapter::adapter::Adapter;
+use object_trustfall_adapter::diff::{BinaryDiff, DiffStatus};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use trustfall::FieldValue;
+
+const QUERY: &str = env!("CARGO_BIN_EXE_object-query");
+const ADDR2LINE: &str = env!("CARGO_BIN_EXE_object-addr2line");
+
+fn run_query(diff: &Arc<BinaryDiff>, query: &str) -> Vec<BTreeMap<Arc<str>, FieldValue>> {
+    diff.query(query, BTreeMap::new()).unwrap().collect()
+}
+
+#[test]
+fn same_binary_is_unchanged() {
+    let adapter = Adapter::load(ADDR2LINE).unwrap();
+    let diff = BinaryDiff::new(adapter.clone(), adapter);
+    assert!(!diff.functions().is_empty());
+    assert!(diff
+        .functions()
+        .iter()
+        .all(|x| x.status == DiffStatus::Unchanged));
+    assert!(diff
+        .symbols()
+        .iter()
+        .all(|x| x.status == DiffStatus::Unchanged));
+}
+
+#[test]
+fn different_binaries() {
+    let diff = Arc::new(BinaryDiff::load(ADDR2LINE, QUERY).unwrap());
+    let added = run_query(
+        &diff,
+        r#"
+        {
+            added {
+                name @output
+                status @output
+                oldSize @output
+                newSize @output
+                sizeDelta @output
+            }
+        }
+        "#,
+    );
+    assert!(
+        added
+            .iter()
+            .any(|x| x["name"]
+                == FieldValue::from("object_trustfall_adapter::listing::write_listing"))
+    );
+    for row in &added {
+        assert_eq!(row["status"], FieldValue::from("added"));
+        assert_eq!(row["oldSize"].as_u64(), Some(0));
+        assert_eq!(row["sizeDelta"].as_i64(), row["newSize"].as_i64());
+    }
+    let deltas = added
+        .iter()
+        .map(|x| x["sizeDelta"].as_i64().unwrap())
+        .collect::<Vec<_>>();
+    assert!(deltas.windows(2).all(|x| x[0] >= x[1]));
+
+    let removed = run_query(
+        &diff,
+        r#"
+        {
+            removed {
+                name @output
+                instructions @fold {
+                    status @output(name: "line_status")
+                    oldAddress @output
+                    newAddress @output
+                }
+            }
+        }
+        "#,
+    );
+    let main = removed
+        .iter()
+        .find(|x| x["name"] == FieldValue::from("object_addr2line::main"))
+        .expect("addr2line's main should be removed");
+    let FieldValue::List(statuses) = &main["line_status"] else {
+        panic!("statuses should be a list");
+    };
+    assert!(!statuses.is_empty());
+    assert!(statuses.iter().all(|x| *x == FieldValue::from("removed")));
+    let FieldValue::List(new_addresses) = &main["newAddress"] else {
+        panic!("addresses should be a list");
+    };
+    assert!(new_addresses.iter().all(|x| *x == FieldValue::Null));
+}
@@ -0,0 +1,135 @@
+use object_trustfall_adapter::adapter::Adapter;
+use object_trustfall_adapter::server::Server;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+/// Starts a server on a free port with the test binary loaded under two names
+fn server() -> SocketAddr {
+    static ADDR: OnceLock<SocketAddr> = OnceLock::new();
+    *ADDR.get_or_init(|| {
+        let adapter = Arc::new(Adapter::load(std::env::current_exe().unwrap()).unwrap());
+        let binaries = [
+            ("first".to_string(), adapter.clone()),
+            ("second".to_string(), adapter),
+        ];
+        let server = Server::bind("127.0.0.1:0", binaries).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|e| panic!("failed to send response: {e}")));
+        addr
+    })
+}
+
+/// Sends an HTTP/1.0 request so the response isn't chunked, returning the status and body
+fn request(method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(server()).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.0\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+fn query(body: serde_json::Value) -> (u16, Vec<serde_json::Value>) {
+    let (status, body) = request("POST", "/query", &body.to_string());
+    let rows = body
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect();
+    (status, rows)
+}
+
+const SECTION_QUERY: &str = r#"
+{
+    sections {
+        name @output @filter(op: "=", value: ["$name"])
+        address @output
+    }
+}"#;
+
+#[test]
+fn schema() {
+    assert_eq!(
+        request("GET", "/schema", ""),
+        (200, Adapter::SCHEMA_TEXT.to_string())
+    );
+    let (status, body) = request("GET", "/schema.json", "");
+    assert_eq!(status, 200);
+    let schema = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+    let section = schema["types"]["Section"].as_array().unwrap();
+    assert!(section
+        .iter()
+        .any(|x| x["name"] == "name" && x["kind"] == "property" && x["ty"] == "String!"));
+    assert!(schema["entrypoints"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|x| x["name"] == "sections" && x["target"] == "Section"));
+}
+
+#[test]
+fn list_binaries() {
+    assert_eq!(
+        request("GET", "/binaries", ""),
+        (200, r#"["first","second"]"#.to_string())
+    );
+}
+
+#[test]
+fn query_with_variables() {
+    let (status, rows) = query(serde_json::json!({
+        "query": SECTION_QUERY,
+        "variables": { "name": ".text" },
+        "binary": "second",
+    }));
+    assert_eq!(status, 200);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["name"], ".text");
+    assert!(rows[0]["address"].as_u64().unwrap() > 0);
+
+    let (status, rows) = query(serde_json::json!({
+        "query": "{ functions { name @output } }",
+        "binary": "first",
+    }));
+    assert_eq!(status, 200);
+    assert!(rows.len() > 100);
+}
+
+#[test]
+fn errors() {
+    let (status, rows) = query(serde_json::json!({ "query": SECTION_QUERY }));
+    assert_eq!(status, 400);
+    assert_eq!(
+        rows[0]["error"],
+        "binary must be given when several are loaded"
+    );
+    let (status, rows) = query(serde_json::json!({ "query": SECTION_QUERY, "binary": "third" }));
+    assert_eq!(status, 404);
+    assert_eq!(rows[0]["error"], "no binary named 'third'");
+    let (status, rows) =
+        query(serde_json::json!({ "query": "{ notAnEdge { name @output } }", "binary": "first" }));
+    assert_eq!(status, 400);
+    assert!(rows[0]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid query"));
+    let (status, rows) = query(serde_json::json!({
+        "query": r#"{ findBytes(pattern: "zz") { address @output } }"#,
+        "binary": "first",
+    }));
+    assert_eq!(status, 400);
+    assert_eq!(rows[0]["error"], "invalid query: invalid byte pattern 'zz'");
+    assert_eq!(request("POST", "/query", "not json").0, 400);
+    assert_eq!(request("GET", "/query", "").0, 405);
+    assert_eq!(request("GET", "/elsewhere", "").0, 404);
+}